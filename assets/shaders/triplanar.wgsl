@@ -0,0 +1,84 @@
+#import bevy_pbr::pbr_fragment::pbr_input_from_standard_material
+#import bevy_pbr::pbr_functions::{alpha_discard, apply_pbr_lighting, main_pass_post_lighting_processing}
+#import bevy_pbr::forward_io::{VertexOutput, FragmentOutput}
+
+struct Triplanar {
+    scale: f32,
+    blend_sharpness: f32,
+    steep_threshold: f32,
+    steep_blend: f32,
+    flags: u32,
+};
+
+const HAS_NORMAL_MAP: u32 = 1u;
+const HAS_STEEP_ALBEDO: u32 = 2u;
+
+@group(1) @binding(100)
+var<uniform> triplanar: Triplanar;
+@group(1) @binding(101)
+var albedo_texture: texture_2d<f32>;
+@group(1) @binding(102)
+var albedo_sampler: sampler;
+@group(1) @binding(103)
+var normal_texture: texture_2d<f32>;
+@group(1) @binding(104)
+var normal_sampler: sampler;
+@group(1) @binding(105)
+var roughness_texture: texture_2d<f32>;
+@group(1) @binding(106)
+var roughness_sampler: sampler;
+@group(1) @binding(107)
+var steep_albedo_texture: texture_2d<f32>;
+@group(1) @binding(108)
+var steep_albedo_sampler: sampler;
+
+// Weight of the projection along each axis, summing to one
+fn blend_weights(normal: vec3<f32>) -> vec3<f32> {
+    let weights = pow(abs(normal), vec3<f32>(triplanar.blend_sharpness));
+    return weights / (weights.x + weights.y + weights.z);
+}
+
+fn sample_triplanar(t: texture_2d<f32>, s: sampler, p: vec3<f32>, weights: vec3<f32>) -> vec4<f32> {
+    return textureSample(t, s, p.zy) * weights.x
+         + textureSample(t, s, p.xz) * weights.y
+         + textureSample(t, s, p.xy) * weights.z;
+}
+
+// Whiteout blend of the normal map projected along each axis onto the surface normal
+fn triplanar_normal(p: vec3<f32>, normal: vec3<f32>, weights: vec3<f32>) -> vec3<f32> {
+    var tangent_x = textureSample(normal_texture, normal_sampler, p.zy).xyz * 2.0 - 1.0;
+    var tangent_y = textureSample(normal_texture, normal_sampler, p.xz).xyz * 2.0 - 1.0;
+    var tangent_z = textureSample(normal_texture, normal_sampler, p.xy).xyz * 2.0 - 1.0;
+    tangent_x = vec3<f32>(tangent_x.xy + normal.zy, abs(tangent_x.z) * normal.x);
+    tangent_y = vec3<f32>(tangent_y.xy + normal.xz, abs(tangent_y.z) * normal.y);
+    tangent_z = vec3<f32>(tangent_z.xy + normal.xy, abs(tangent_z.z) * normal.z);
+    // Swizzle each back so that its z lines up with the axis it was projected along
+    return normalize(tangent_x.zyx * weights.x + tangent_y.xzy * weights.y + tangent_z.xyz * weights.z);
+}
+
+@fragment
+fn fragment(in: VertexOutput, @builtin(front_facing) is_front: bool) -> FragmentOutput {
+    var pbr_input = pbr_input_from_standard_material(in, is_front);
+
+    let p = in.world_position.xyz * triplanar.scale;
+    let normal = normalize(in.world_normal);
+    let weights = blend_weights(normal);
+    var albedo = sample_triplanar(albedo_texture, albedo_sampler, p, weights);
+    if (triplanar.flags & HAS_STEEP_ALBEDO) != 0u {
+        let steep_albedo = sample_triplanar(steep_albedo_texture, steep_albedo_sampler, p, weights);
+        let half_band = triplanar.steep_blend * 0.5;
+        let flatness = smoothstep(triplanar.steep_threshold - half_band, triplanar.steep_threshold + half_band, normal.y);
+        albedo = mix(steep_albedo, albedo, flatness);
+    }
+    pbr_input.material.base_color *= albedo;
+    pbr_input.material.perceptual_roughness *= sample_triplanar(roughness_texture, roughness_sampler, p, weights).g;
+    if (triplanar.flags & HAS_NORMAL_MAP) != 0u {
+        pbr_input.N = triplanar_normal(p, normal, weights);
+    }
+    pbr_input.material.base_color = alpha_discard(pbr_input.material, pbr_input.material.base_color);
+
+    var out: FragmentOutput;
+    out.color = apply_pbr_lighting(pbr_input);
+    out.color = main_pass_post_lighting_processing(pbr_input, out.color);
+    return out;
+}
@@ -1,6 +1,7 @@
 struct Voxel {
     flags: u32,
     density: f32,
+    material: u32,
 };
 
 struct VoxelBuffer {
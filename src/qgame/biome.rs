@@ -0,0 +1,219 @@
+use bevy::prelude::*;
+
+use crate::{Chunk, ConsoleCommandEvent, DensityField, LAVA_MATERIAL, TerrainDensity, WorldSeed};
+
+/// Terrain parameters of one kind of region
+#[derive(Clone, Debug)]
+pub struct Biome {
+    pub name: &'static str,
+    /// Surface height where the detail noise is zero
+    pub base_height: f32,
+    /// How far the detail noise moves the surface up or down
+    pub roughness: f32,
+    /// Frequency of the first detail octave, in cycles per voxel
+    pub frequency: f32,
+    pub octaves: u32,
+    /// Written to [`crate::Voxel::material`] where this biome dominates
+    pub material: u32,
+}
+
+/// Blends biomes by a low frequency noise per biome, so parameters change smoothly across chunk borders.
+/// Terrain switches to it while the resource exists, toggled with the `biomes` console command.
+#[derive(Resource, Clone, Debug)]
+pub struct BiomeMap {
+    pub seed: u32,
+    pub biomes: Vec<Biome>,
+    /// Frequency of the biome selection noise, in cycles per voxel
+    pub frequency: f32,
+    /// Higher makes borders between biomes narrower
+    pub sharpness: f32,
+}
+
+pub struct BiomePlugin;
+
+impl Plugin for BiomePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<WorldSeed>()
+            .add_systems(Update, (
+                biome_command_sys,
+                seed_biome_map_sys.run_if(resource_exists_and_changed::<WorldSeed>()),
+                apply_biome_map_sys.run_if(resource_exists_and_changed::<BiomeMap>()),
+            ).chain());
+    }
+}
+
+impl Default for BiomeMap {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            biomes: vec![
+                Biome { name: "plains", base_height: 9.0, roughness: 1.5, frequency: 0.03, octaves: 2, material: 0 },
+                Biome { name: "hills", base_height: 12.0, roughness: 5.0, frequency: 0.04, octaves: 3, material: 1 },
+                Biome { name: "mountains", base_height: 16.0, roughness: 12.0, frequency: 0.05, octaves: 5, material: 2 },
+                Biome { name: "lava", base_height: 6.0, roughness: 0.5, frequency: 0.02, octaves: 1, material: LAVA_MATERIAL },
+            ],
+            frequency: 0.004,
+            sharpness: 6.0,
+        }
+    }
+}
+
+impl BiomeMap {
+    pub fn with_seed(seed: u32) -> Self {
+        Self { seed, ..default() }
+    }
+
+    /// Weight of each biome at a column, summing to one
+    pub fn weights(&self, column: Vec2) -> Vec<f32> {
+        let mut weights: Vec<f32> = (0..self.biomes.len())
+            .map(|i| (self.sharpness * value_noise(column * self.frequency, self.seed.wrapping_add(i as u32))).exp())
+            .collect();
+        let total: f32 = weights.iter().sum();
+        for weight in weights.iter_mut() {
+            *weight /= total;
+        }
+        weights
+    }
+
+    pub fn surface_height(&self, column: Vec2) -> f32 {
+        // Detail noise shares a seed across biomes so blending does not average it out
+        let detail_seed = self.seed.wrapping_add(self.biomes.len() as u32);
+        self.biomes.iter().zip(self.weights(column))
+            .filter(|(_, weight)| *weight > 1e-3)
+            .map(|(biome, weight)| {
+                let detail = fbm(column * biome.frequency, biome.octaves, detail_seed);
+                weight * (biome.base_height + biome.roughness * detail)
+            })
+            .sum()
+    }
+
+    /// Material of the biome with the highest weight
+    pub fn dominant_material(&self, column: Vec2) -> u32 {
+        self.biomes.iter().zip(self.weights(column))
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map_or(0, |(biome, _)| biome.material)
+    }
+}
+
+impl DensityField for BiomeMap {
+    fn sample(&self, world_pos: Vec3) -> f32 {
+        (self.surface_height(world_pos.xz()) - world_pos.y).clamp(0.0, 1.0)
+    }
+
+    fn material(&self, world_pos: Vec3) -> u32 {
+        self.dominant_material(world_pos.xz())
+    }
+}
+
+fn hash(x: i32, y: i32, seed: u32) -> f32 {
+    let mut h = (x as u32).wrapping_mul(0x8DA6_B343)
+        ^ (y as u32).wrapping_mul(0xD816_3841)
+        ^ seed.wrapping_mul(0xCB1A_B31F);
+    h = (h ^ (h >> 13)).wrapping_mul(0x5BD1_E995);
+    h ^= h >> 15;
+    (h >> 8) as f32 / (1u32 << 24) as f32
+}
+
+/// Smoothly interpolated lattice noise in [-1, 1]
+pub fn value_noise(p: Vec2, seed: u32) -> f32 {
+    let cell = p.floor();
+    let t = p - cell;
+    let t = t * t * (3.0 - 2.0 * t);
+    let (x, y) = (cell.x as i32, cell.y as i32);
+    let v00 = hash(x, y, seed);
+    let v10 = hash(x + 1, y, seed);
+    let v01 = hash(x, y + 1, seed);
+    let v11 = hash(x + 1, y + 1, seed);
+    let v0 = v00 + (v10 - v00) * t.x;
+    let v1 = v01 + (v11 - v01) * t.x;
+    (v0 + (v1 - v0) * t.y) * 2.0 - 1.0
+}
+
+/// Octaves of [`value_noise`], each double the frequency and half the amplitude, normalized to [-1, 1]
+pub fn fbm(p: Vec2, octaves: u32, seed: u32) -> f32 {
+    let (mut sum, mut total, mut amplitude, mut frequency) = (0.0, 0.0, 1.0, 1.0);
+    for octave in 0..octaves {
+        sum += amplitude * value_noise(p * frequency, seed.wrapping_add(octave));
+        total += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+    if total > 0.0 { sum / total } else { 0.0 }
+}
+
+pub fn biome_command_sys(
+    mut commands: Commands,
+    mut command_events: EventReader<ConsoleCommandEvent>,
+    seed: Res<WorldSeed>,
+    biome_map: Option<Res<BiomeMap>>,
+    mut chunk_query: Query<&mut Chunk>,
+) {
+    for command in command_events.read() {
+        if command.name != "biomes" { continue; }
+        if biome_map.is_some() {
+            commands.remove_resource::<BiomeMap>();
+            commands.insert_resource(TerrainDensity::default());
+            for mut chunk in chunk_query.iter_mut() {
+                chunk.dirty = true;
+            }
+        } else {
+            commands.insert_resource(BiomeMap::with_seed(seed.noise_seed()));
+        }
+    }
+}
+
+/// Keep biomes in step with the world seed, changing the map regenerates the terrain
+pub fn seed_biome_map_sys(seed: Res<WorldSeed>, biome_map: Option<ResMut<BiomeMap>>) {
+    let Some(mut biome_map) = biome_map else { return; };
+    let noise_seed = seed.noise_seed();
+    if biome_map.seed != noise_seed {
+        biome_map.seed = noise_seed;
+    }
+}
+
+pub fn apply_biome_map_sys(
+    mut commands: Commands,
+    biome_map: Res<BiomeMap>,
+    mut chunk_query: Query<&mut Chunk>,
+) {
+    commands.insert_resource(TerrainDensity::new(biome_map.clone()));
+    for mut chunk in chunk_query.iter_mut() {
+        chunk.dirty = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Approximate surface height of a few columns through the active density field
+    fn column_heights(app: &App) -> Vec<f32> {
+        let field = app.world.resource::<TerrainDensity>().0.as_ref().unwrap();
+        (0..16)
+            .map(|i| {
+                let column = Vec2::new(i as f32 * 37.0, i as f32 * 11.0);
+                (0..48).map(|y| field.sample(Vec3::new(column.x, y as f32, column.y))).sum()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn world_seed_changes_cpu_terrain() {
+        let mut app = App::new();
+        app
+            .add_event::<ConsoleCommandEvent>()
+            .insert_resource(WorldSeed(1))
+            .add_plugins(BiomePlugin);
+        app.world.send_event(ConsoleCommandEvent::parse("biomes").unwrap());
+        app.update();
+        app.update();
+        assert_eq!(app.world.resource::<BiomeMap>().seed, WorldSeed(1).noise_seed());
+        let first = column_heights(&app);
+
+        app.insert_resource(WorldSeed(2));
+        app.update();
+        assert_eq!(app.world.resource::<BiomeMap>().seed, WorldSeed(2).noise_seed());
+        assert_ne!(first, column_heights(&app));
+    }
+}
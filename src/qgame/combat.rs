@@ -0,0 +1,613 @@
+use std::time::Duration;
+
+use bevy::{
+    prelude::*,
+    prelude::shape::UVSphere,
+    utils::HashMap,
+};
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{aim_direction, AttackKind, camera_transform, eye_transform, FIRE_STATE, GameRng, ItemSound, MoveMode, PlayerInput, PlayerInputFlags, PlaySoundEvent, Respawning, SoundCategory, ThirdPerson, GameTime, GunProps, Item, ItemStateEvent, PlayerController};
+
+const MAX_PROJECTILE_AGE: Duration = Duration::from_secs(10);
+const LIMB_DAMAGE_FACTOR: f32 = 0.75;
+/// Knockback never pushes something faster than this along the hit direction, however many hits stack up
+const MAX_KNOCKBACK_SPEED: f32 = 20.0;
+/// How far to look for what the crosshair is on when correcting third person aim
+const MAX_AIM_DIST: f32 = 512.0;
+
+#[derive(Component, Debug)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DamageType {
+    Kinetic,
+    Explosive,
+    Fire,
+}
+
+/// Multipliers on incoming damage by type, below one resists and above one is a weakness.
+/// Types that are not listed take full damage.
+#[derive(Component, Default, Debug)]
+pub struct Resistances(pub HashMap<DamageType, f32>);
+
+/// Absorbs damage before [`Health`], regenerates once nothing has hit it for `regen_delay`
+#[derive(Component, Debug)]
+pub struct Shield {
+    pub current: f32,
+    pub max: f32,
+    pub regen_delay: Duration,
+    /// Points per second
+    pub regen_rate: f32,
+    pub since_hit: Duration,
+}
+
+/// Sent whenever damage is applied to something with [`Health`]
+#[derive(Event, Debug)]
+pub struct HitEvent {
+    pub attacker: Entity,
+    pub target: Entity,
+    pub damage: f32,
+    /// Where the damage came from in the world, such as the eye of the attacker or the center of an explosion
+    pub source: Vec3,
+    /// Velocity to push the target with, zero for none
+    pub knockback: Vec3,
+    /// This hit took the target from alive to dead
+    pub is_kill: bool,
+}
+
+/// Sent for every hitscan shot so tracers and impact decals can be drawn
+#[derive(Event, Debug)]
+pub struct ShotEvent {
+    pub shooter: Entity,
+    pub origin: Vec3,
+    /// Where the shot hit, or where it gave up if it hit nothing
+    pub end: Vec3,
+    /// Normal of the surface that was hit, none for misses and damaged targets, which get no decal
+    pub normal: Option<Vec3>,
+}
+
+/// Sent once when something with [`Health`] dies
+#[derive(Event, Debug)]
+pub struct DeathEvent {
+    pub entity: Entity,
+    pub killer: Entity,
+}
+
+/// Part of a damageable entity that a shot can hit
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HitboxRegion {
+    Head,
+    Body,
+    Limb,
+}
+
+/// Sensor collider child of a damageable entity
+#[derive(Component)]
+pub struct Hitbox {
+    pub region: HitboxRegion,
+}
+
+/// Shots ignore the physics collider of this entity and hit its [`Hitbox`] children instead
+#[derive(Component)]
+pub struct HitboxOwner;
+
+/// Shared by every projectile so firing does not add assets
+#[derive(Resource)]
+pub struct ProjectileAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+#[derive(Component)]
+pub struct Projectile {
+    pub shooter: Entity,
+    pub damage_type: DamageType,
+    pub explosion_radius: f32,
+    pub damage: f32,
+    pub knockback: f32,
+    pub impact_sound: Option<ItemSound>,
+    pub age: Duration,
+}
+
+/// Combat events and shared assets, the systems are ordered with the item systems by [`crate::QGamePlugin`]
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<HitEvent>()
+            .add_event::<DeathEvent>()
+            .add_event::<ShotEvent>()
+            .init_resource::<ProjectileAssets>();
+    }
+}
+
+impl FromWorld for ProjectileAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mesh = world.resource_mut::<Assets<Mesh>>().add(Mesh::from(UVSphere { radius: 0.1, ..default() }));
+        let material = world.resource_mut::<Assets<StandardMaterial>>().add(StandardMaterial {
+            base_color: Color::DARK_GRAY,
+            ..default()
+        });
+        Self { mesh, material }
+    }
+}
+
+impl HitboxRegion {
+    pub fn damage_factor(self, headshot_factor: f32) -> f32 {
+        match self {
+            HitboxRegion::Head => headshot_factor,
+            HitboxRegion::Body => 1.0,
+            HitboxRegion::Limb => LIMB_DAMAGE_FACTOR,
+        }
+    }
+}
+
+/// Hitboxes matching a player capsule of this height, with a small head sphere at the top.
+/// Proportions are those of a standing player two units tall.
+pub fn player_hitboxes(height: f32) -> [(HitboxRegion, Vec3, Collider); 2] {
+    let scale = height / 2.0;
+    [
+        (HitboxRegion::Head, Vec3::Y * 1.75 * scale, Collider::ball(0.25)),
+        (HitboxRegion::Body, Vec3::Y * 0.95 * scale, Collider::capsule_y(0.45 * scale, 0.45)),
+    ]
+}
+
+pub fn spawn_player_hitboxes(parent: &mut ChildBuilder, height: f32) {
+    for (region, offset, collider) in player_hitboxes(height) {
+        parent.spawn((
+            TransformBundle::from(Transform::from_translation(offset)),
+            collider,
+            Sensor,
+            // Would otherwise add to the mass of the player
+            ColliderMassProperties::Density(0.0),
+            Hitbox { region },
+        ));
+    }
+}
+
+/// Keep hitboxes lined up with the player capsule as it crouches and stands back up
+pub fn sync_player_hitboxes_sys(
+    player_query: Query<(&PlayerController, &Children), Changed<PlayerController>>,
+    mut hitbox_query: Query<(&Hitbox, &mut Transform, &mut Collider)>,
+) {
+    for (controller, children) in player_query.iter() {
+        let hitboxes = player_hitboxes(controller.height);
+        for &child in children.iter() {
+            let Ok((hitbox, mut transform, mut collider)) = hitbox_query.get_mut(child) else { continue; };
+            let Some((_, offset, shape)) = hitboxes.iter().find(|(region, ..)| *region == hitbox.region) else { continue; };
+            // Both offsets move with the height, so an unchanged offset means an unchanged shape
+            if transform.translation == *offset { continue; }
+            transform.translation = *offset;
+            *collider = shape.clone();
+        }
+    }
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.0
+    }
+
+    pub fn apply_damage(&mut self, damage: f32) {
+        self.current = f32::max(self.current - damage, 0.0);
+    }
+}
+
+impl Shield {
+    pub fn new(max: f32, regen_delay: Duration, regen_rate: f32) -> Self {
+        Self { current: max, max, regen_delay, regen_rate, since_hit: Duration::ZERO }
+    }
+
+    /// Take as much of the damage as possible, returns what is left over for health
+    pub fn absorb(&mut self, damage: f32) -> f32 {
+        self.since_hit = Duration::ZERO;
+        let absorbed = f32::min(self.current, damage);
+        self.current -= absorbed;
+        damage - absorbed
+    }
+
+    pub fn regen(&mut self, delta: Duration) {
+        self.since_hit = self.since_hit.saturating_add(delta);
+        if self.since_hit >= self.regen_delay {
+            self.current = f32::min(self.current + self.regen_rate * delta.as_secs_f32(), self.max);
+        }
+    }
+}
+
+impl Resistances {
+    #[inline]
+    pub fn multiplier(&self, damage_type: DamageType) -> f32 {
+        self.0.get(&damage_type).copied().unwrap_or(1.0)
+    }
+}
+
+/// Damage the target of the hit, draining its shield first, and report it. Whether it was a kill is filled in here.
+pub fn apply_hit(
+    hit_events: &mut EventWriter<HitEvent>,
+    death_events: &mut EventWriter<DeathEvent>,
+    mut hit: HitEvent, health: &mut Health, shield: Option<Mut<Shield>>,
+) {
+    let was_dead = health.is_dead();
+    let damage_to_health = shield.map_or(hit.damage, |mut shield| shield.absorb(hit.damage));
+    health.apply_damage(damage_to_health);
+    hit.is_kill = !was_dead && health.is_dead();
+    if hit.is_kill {
+        death_events.send(DeathEvent { entity: hit.target, killer: hit.attacker });
+    }
+    hit_events.send(hit);
+}
+
+/// Half angle of the cone a shot can go in right now
+pub fn current_spread(props: &GunProps, input: &PlayerInput, controller: &PlayerController) -> f32 {
+    let is_airborne = controller.move_mode == MoveMode::Ground && controller.ground_tick == 0;
+    let mut spread = props.spread;
+    if input.movement != Vec3::ZERO || is_airborne {
+        spread *= props.moving_spread_multiplier;
+    }
+    if input.flags.contains(PlayerInputFlags::Aim) {
+        spread *= props.aim_spread_multiplier;
+    }
+    spread
+}
+
+/// Add speed along the impulse direction without going over [`MAX_KNOCKBACK_SPEED`] in that direction
+fn add_knockback(velocity: &mut Vec3, impulse: Vec3) {
+    let strength = impulse.length();
+    if strength < 1e-6 { return; }
+    let dir = impulse / strength;
+    let added = f32::min(strength, MAX_KNOCKBACK_SPEED - velocity.dot(dir));
+    if added > 0.0 {
+        *velocity += dir * added;
+    }
+}
+
+/// Damage left after the target's resistances
+pub fn final_damage(raw_damage: f32, damage_type: DamageType, resistances: Option<&Resistances>) -> f32 {
+    raw_damage * resistances.map_or(1.0, |resistances| resistances.multiplier(damage_type))
+}
+
+// ██╗      ██████╗  ██████╗ ██╗ ██████╗
+// ██║     ██╔═══██╗██╔════╝ ██║██╔════╝
+// ██║     ██║   ██║██║  ███╗██║██║
+// ██║     ██║   ██║██║   ██║██║██║
+// ███████╗╚██████╔╝╚██████╔╝██║╚██████╗
+// ╚══════╝ ╚═════╝  ╚═════╝ ╚═╝ ╚═════╝
+
+pub fn fire_sys(
+    mut commands: Commands,
+    projectile_assets: Res<ProjectileAssets>,
+    asset_server: Res<AssetServer>,
+    physics_context: Res<RapierContext>,
+    gun_props: Res<Assets<GunProps>>,
+    mut rng: ResMut<GameRng>,
+    mut state_events: EventReader<ItemStateEvent>,
+    mut hit_events: EventWriter<HitEvent>,
+    mut death_events: EventWriter<DeathEvent>,
+    mut sound_events: EventWriter<PlaySoundEvent>,
+    mut shot_events: EventWriter<ShotEvent>,
+    item_query: Query<&Item>,
+    mut player_query: Query<(&Transform, &PlayerInput, &mut PlayerController, Option<&ThirdPerson>), Without<Respawning>>,
+    mut health_query: Query<(&Transform, &mut Health, Option<&Resistances>, Option<&mut Shield>)>,
+    collider_query: Query<(Option<(&Hitbox, &Parent)>, Has<HitboxOwner>, Has<Sensor>)>,
+) {
+    for event in state_events.read() {
+        if event.state_name != FIRE_STATE { continue; }
+        let Ok(item) = item_query.get(event.item_ent) else { continue; };
+        let Some(props) = gun_props.get(&item.props) else { continue; };
+        let Ok((transform, input, mut controller, third_person)) = player_query.get_mut(event.inv_ent) else { continue; };
+        let camera = camera_transform(transform, &controller, third_person);
+        let eye = eye_transform(transform, &controller);
+        let eye = eye.looking_to(aim_direction(&physics_context, event.inv_ent, &eye, &camera, MAX_AIM_DIST), Vec3::Y);
+
+        if let Some(recoil_impulse) = props.recoil_impulse {
+            controller.add_world_velocity(-eye.forward() * recoil_impulse);
+        }
+
+        let spread = current_spread(props, input, &controller);
+        let shot_dir = rng.cone(eye.forward(), spread);
+
+        match &props.weapon_props.attack {
+            AttackKind::Projectile(projectile_props) => {
+                let fwd = shot_dir;
+                commands.spawn((
+                    PbrBundle {
+                        mesh: projectile_assets.mesh.clone(),
+                        material: projectile_assets.material.clone(),
+                        // Start in front of the eye so we do not immediately hit the shooter
+                        transform: Transform::from_translation(eye.translation + fwd * 0.75),
+                        ..default()
+                    },
+                    RigidBody::Dynamic,
+                    Collider::ball(0.1),
+                    Velocity::linear(fwd * projectile_props.speed),
+                    GravityScale(projectile_props.gravity_scale),
+                    Ccd::enabled(),
+                    Projectile {
+                        shooter: event.inv_ent,
+                        damage_type: props.weapon_props.damage_type,
+                        explosion_radius: projectile_props.explosion_radius,
+                        damage: projectile_props.damage,
+                        knockback: props.weapon_props.knockback,
+                        impact_sound: props.weapon_props.impact_sound.clone(),
+                        age: Duration::ZERO,
+                    },
+                ));
+            }
+            AttackKind::Melee(melee_props) => {
+                let hit_ent = find_melee_target(
+                    &physics_context, &health_query, event.inv_ent, &eye,
+                    melee_props.range, melee_props.arc_degrees.to_radians(),
+                );
+                if let Some(hit_ent) = hit_ent {
+                    if let Ok((hit_transform, mut health, resistances, shield)) = health_query.get_mut(hit_ent) {
+                        let damage = final_damage(melee_props.damage, props.weapon_props.damage_type, resistances);
+                        let knockback_dir = (hit_transform.translation - eye.translation).try_normalize().unwrap_or(eye.forward());
+                        let knockback = knockback_dir * props.weapon_props.knockback;
+                        let hit = HitEvent { attacker: event.inv_ent, target: hit_ent, damage, source: eye.translation, knockback, is_kill: false };
+                        apply_hit(&mut hit_events, &mut death_events, hit, &mut health, shield);
+                        if let Some(impact_sound) = &props.weapon_props.impact_sound {
+                            sound_events.send(impact_sound.event(&asset_server, SoundCategory::Impact, hit_transform.translation));
+                        }
+                    }
+                }
+            }
+            AttackKind::Hitscan => {
+                let is_shootable = |collider_ent: Entity| match collider_query.get(collider_ent) {
+                    // Our own hitboxes surround the eye
+                    Ok((Some((_, parent)), ..)) => parent.get() != event.inv_ent,
+                    Ok((None, is_hitbox_owner, is_sensor)) => !is_hitbox_owner && !is_sensor,
+                    Err(_) => true,
+                };
+                let filter = QueryFilter::default().exclude_collider(event.inv_ent).predicate(&is_shootable);
+                let hit = physics_context.cast_ray_and_get_normal(eye.translation, shot_dir, MAX_AIM_DIST, true, filter);
+                let Some((hit_ent, intersection)) = hit else {
+                    let end = eye.translation + shot_dir * MAX_AIM_DIST;
+                    shot_events.send(ShotEvent { shooter: event.inv_ent, origin: eye.translation, end, normal: None });
+                    continue;
+                };
+                let (target_ent, damage_factor) = match collider_query.get(hit_ent) {
+                    Ok((Some((hitbox, parent)), ..)) => (parent.get(), hitbox.region.damage_factor(props.weapon_props.headshot_factor)),
+                    _ => (hit_ent, 1.0),
+                };
+                let is_damageable = health_query.contains(target_ent);
+                shot_events.send(ShotEvent {
+                    shooter: event.inv_ent,
+                    origin: eye.translation,
+                    end: intersection.point,
+                    normal: (!is_damageable).then_some(intersection.normal),
+                });
+                if let Ok((_, mut health, resistances, shield)) = health_query.get_mut(target_ent) {
+                    let damage = final_damage(props.weapon_props.damage as f32 * damage_factor, props.weapon_props.damage_type, resistances);
+                    let knockback = shot_dir * props.weapon_props.knockback;
+                    let hit = HitEvent { attacker: event.inv_ent, target: target_ent, damage, source: eye.translation, knockback, is_kill: false };
+                    apply_hit(&mut hit_events, &mut death_events, hit, &mut health, shield);
+                }
+                if let Some(impact_sound) = &props.weapon_props.impact_sound {
+                    sound_events.send(impact_sound.event(&asset_server, SoundCategory::Impact, intersection.point));
+                }
+            }
+        }
+    }
+}
+
+/// Closest damageable entity within `range` of the eye and inside the swing arc centered on forward
+fn find_melee_target(
+    physics_context: &RapierContext,
+    health_query: &Query<(&Transform, &mut Health, Option<&Resistances>, Option<&mut Shield>)>,
+    attacker: Entity,
+    eye: &Transform,
+    range: f32,
+    arc: f32,
+) -> Option<Entity> {
+    let fwd = eye.forward();
+    let mut closest: Option<(Entity, f32)> = None;
+    physics_context.intersections_with_shape(
+        eye.translation, Quat::IDENTITY, &Collider::ball(range),
+        QueryFilter::default().exclude_collider(attacker).exclude_sensors(),
+        |hit_ent| {
+            if let Ok((hit_transform, _, _, _)) = health_query.get(hit_ent) {
+                let to_hit = hit_transform.translation - eye.translation;
+                let dist = to_hit.length();
+                let is_in_arc = dist < 1e-6 || fwd.angle_between(to_hit) <= arc * 0.5;
+                if is_in_arc && closest.map_or(true, |(_, closest_dist)| dist < closest_dist) {
+                    closest = Some((hit_ent, dist));
+                }
+            }
+            true
+        },
+    );
+    closest.map(|(hit_ent, _)| hit_ent)
+}
+
+pub fn projectile_sys(
+    time: Res<GameTime>,
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    physics_context: Res<RapierContext>,
+    mut hit_events: EventWriter<HitEvent>,
+    mut death_events: EventWriter<DeathEvent>,
+    mut sound_events: EventWriter<PlaySoundEvent>,
+    mut projectile_query: Query<(Entity, &Transform, &mut Projectile)>,
+    mut health_query: Query<(&Transform, &mut Health, Option<&Resistances>, Option<&mut Shield>)>,
+) {
+    for (projectile_ent, transform, mut projectile) in projectile_query.iter_mut() {
+        projectile.age = projectile.age.saturating_add(time.delta());
+
+        let has_hit = physics_context.contacts_with(projectile_ent).any(|contact_pair| {
+            let other_ent = if contact_pair.collider1() == projectile_ent {
+                contact_pair.collider2()
+            } else {
+                contact_pair.collider1()
+            };
+            other_ent != projectile.shooter && contact_pair.has_any_active_contacts()
+        });
+        if !has_hit {
+            if projectile.age > MAX_PROJECTILE_AGE {
+                commands.entity(projectile_ent).despawn_recursive();
+            }
+            continue;
+        }
+
+        let center = transform.translation;
+        let radius = projectile.explosion_radius;
+        if let Some(impact_sound) = &projectile.impact_sound {
+            sound_events.send(impact_sound.event(&asset_server, SoundCategory::Impact, center));
+        }
+        let mut hit_ents = Vec::new();
+        physics_context.intersections_with_shape(
+            center, Quat::IDENTITY, &Collider::ball(radius), QueryFilter::default(),
+            |hit_ent| {
+                hit_ents.push(hit_ent);
+                true
+            },
+        );
+        for hit_ent in hit_ents {
+            if let Ok((hit_transform, mut health, resistances, shield)) = health_query.get_mut(hit_ent) {
+                // Linear falloff from the center of the explosion to the closest point of the target,
+                // so large targets are not spared because their origin is far away
+                let is_target = |collider_ent: Entity| collider_ent == hit_ent;
+                let closest = physics_context.project_point(center, true, QueryFilter::default().predicate(&is_target))
+                    .map_or(hit_transform.translation, |(_, projection)| projection.point);
+                let falloff = 1.0 - (closest.distance(center) / radius).clamp(0.0, 1.0);
+                let damage = final_damage(projectile.damage * falloff, projectile.damage_type, resistances);
+                let knockback = (hit_transform.translation - center).normalize_or_zero() * projectile.knockback * falloff;
+                let hit = HitEvent { attacker: projectile.shooter, target: hit_ent, damage, source: center, knockback, is_kill: false };
+                apply_hit(&mut hit_events, &mut death_events, hit, &mut health, shield);
+            }
+        }
+        commands.entity(projectile_ent).despawn_recursive();
+    }
+}
+
+pub fn shield_regen_sys(time: Res<GameTime>, mut shield_query: Query<&mut Shield>) {
+    for mut shield in shield_query.iter_mut() {
+        shield.regen(time.delta());
+    }
+}
+
+/// Push whatever was hit, players through their controller so that movement carries it
+pub fn knockback_sys(
+    mut hit_events: EventReader<HitEvent>,
+    mut target_query: Query<(Option<&mut PlayerController>, Option<&mut Velocity>)>,
+) {
+    for event in hit_events.read() {
+        if event.knockback == Vec3::ZERO { continue; }
+        let Ok((controller, velocity)) = target_query.get_mut(event.target) else { continue; };
+        match (controller, velocity) {
+            (Some(mut controller), _) => {
+                // Other move modes keep their velocity relative to the view and have no use for it
+                if controller.move_mode != MoveMode::Ground { continue; }
+                add_knockback(&mut controller.velocity, event.knockback);
+                // Otherwise the next move would treat us as grounded and cancel the vertical part
+                controller.ground_tick = 0;
+            }
+            (None, Some(mut velocity)) => add_knockback(&mut velocity.linvel, event.knockback),
+            (None, None) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resistances_scale_listed_types() {
+        let mut resistances = Resistances::default();
+        resistances.0.insert(DamageType::Explosive, 0.5);
+        resistances.0.insert(DamageType::Fire, 2.0);
+        assert_eq!(final_damage(40.0, DamageType::Explosive, Some(&resistances)), 20.0);
+        assert_eq!(final_damage(40.0, DamageType::Fire, Some(&resistances)), 80.0);
+        assert_eq!(final_damage(40.0, DamageType::Kinetic, Some(&resistances)), 40.0);
+        assert_eq!(final_damage(40.0, DamageType::Explosive, None), 40.0);
+    }
+
+    #[test]
+    fn items_declare_damage_types() {
+        for (source, damage_type) in [
+            (include_str!("../../assets/items/rifle.item.ron"), DamageType::Kinetic),
+            (include_str!("../../assets/items/launcher.item.ron"), DamageType::Explosive),
+        ] {
+            let props: GunProps = ron::from_str(source).unwrap();
+            assert_eq!(props.weapon_props.damage_type, damage_type);
+        }
+    }
+
+    #[test]
+    fn shield_absorbs_before_health() {
+        let mut shield = Shield::new(30.0, Duration::from_secs(2), 10.0);
+        assert_eq!(shield.absorb(20.0), 0.0);
+        assert_eq!(shield.absorb(25.0), 15.0);
+        assert_eq!(shield.current, 0.0);
+    }
+
+    #[test]
+    fn shield_regens_after_delay() {
+        let mut shield = Shield::new(30.0, Duration::from_secs(2), 10.0);
+        shield.absorb(30.0);
+        shield.regen(Duration::from_millis(1500));
+        assert_eq!(shield.current, 0.0);
+        shield.regen(Duration::from_millis(500));
+        assert_eq!(shield.current, 5.0);
+        shield.regen(Duration::from_secs(10));
+        assert_eq!(shield.current, 30.0);
+
+        // A hit restarts the delay
+        shield.absorb(10.0);
+        shield.regen(Duration::from_secs(1));
+        assert_eq!(shield.current, 20.0);
+    }
+
+    #[test]
+    fn apply_hit_drains_shield_then_reports_kill() {
+        let mut app = App::new();
+        app
+            .add_event::<HitEvent>()
+            .add_event::<DeathEvent>()
+            .add_systems(Update, |mut hit_events: EventWriter<HitEvent>, mut death_events: EventWriter<DeathEvent>, mut query: Query<(Entity, &mut Health, Option<&mut Shield>)>| {
+                for (target, mut health, shield) in query.iter_mut() {
+                    let hit = HitEvent { attacker: target, target, damage: 60.0, source: Vec3::ZERO, knockback: Vec3::ZERO, is_kill: false };
+                    apply_hit(&mut hit_events, &mut death_events, hit, &mut health, shield);
+                }
+            });
+        let target = app.world.spawn((Health::new(50.0), Shield::new(20.0, Duration::from_secs(1), 5.0))).id();
+
+        app.update();
+        assert_eq!(app.world.get::<Health>(target).unwrap().current, 10.0);
+        assert_eq!(app.world.resource::<Events<DeathEvent>>().len(), 0);
+
+        app.update();
+        assert!(app.world.get::<Health>(target).unwrap().is_dead());
+        let kills: Vec<bool> = app.world.resource_mut::<Events<HitEvent>>().drain().map(|hit| hit.is_kill).collect();
+        assert_eq!(kills, [false, true]);
+        assert_eq!(app.world.resource::<Events<DeathEvent>>().len(), 1);
+    }
+
+    #[test]
+    fn spread_widens_moving_and_tightens_aiming() {
+        let props: GunProps = ron::from_str(include_str!("../../assets/items/rifle.item.ron")).unwrap();
+        let grounded = PlayerController { move_mode: MoveMode::Ground, ground_tick: 1, ..default() };
+        let still = PlayerInput::default();
+        let moving = PlayerInput { movement: Vec3::NEG_Z, ..default() };
+        let aiming = PlayerInput { flags: PlayerInputFlags::Aim.into(), ..default() };
+
+        assert_eq!(current_spread(&props, &still, &grounded), props.spread);
+        assert_eq!(current_spread(&props, &moving, &grounded), props.spread * props.moving_spread_multiplier);
+        assert_eq!(current_spread(&props, &aiming, &grounded), props.spread * props.aim_spread_multiplier);
+        // Falling counts as moving, flying does not
+        let airborne = PlayerController { ground_tick: 0, ..grounded };
+        assert_eq!(current_spread(&props, &still, &airborne), props.spread * props.moving_spread_multiplier);
+        let flying = PlayerController { move_mode: MoveMode::Fly, ..default() };
+        assert_eq!(current_spread(&props, &still, &flying), props.spread);
+    }
+}
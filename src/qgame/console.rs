@@ -0,0 +1,144 @@
+use bevy::prelude::*;
+
+use crate::{Config, ConfigState};
+
+/// Developer console, typed commands are sent as [`ConsoleCommandEvent`]
+#[derive(Resource, Default)]
+pub struct Console {
+    pub open: bool,
+    pub line: String,
+}
+
+/// A submitted console line split on whitespace, `name` is the first word
+#[derive(Event, Debug)]
+pub struct ConsoleCommandEvent {
+    pub name: String,
+    pub args: Vec<String>,
+}
+
+#[derive(Component)]
+pub struct ConsoleText;
+
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Console>()
+            .add_event::<ConsoleCommandEvent>()
+            .add_systems(Startup, spawn_console_sys)
+            .add_systems(PreUpdate, console_input_sys)
+            .add_systems(Update, update_console_text_sys);
+    }
+}
+
+impl ConsoleCommandEvent {
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut words = line.split_whitespace().map(String::from);
+        let name = words.next()?;
+        Some(Self { name, args: words.collect() })
+    }
+
+    /// Parse argument `index`, `None` if it is missing or malformed
+    pub fn arg<T: std::str::FromStr>(&self, index: usize) -> Option<T> {
+        self.args.get(index)?.parse().ok()
+    }
+}
+
+fn spawn_console_sys(mut commands: Commands) {
+    commands.spawn((
+        TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(24.0),
+                left: Val::Px(5.0),
+                ..default()
+            },
+            text: Text {
+                sections: vec![
+                    TextSection {
+                        value: "".to_string(),
+                        style: TextStyle { font_size: 16.0, color: Color::YELLOW, ..default() },
+                    },
+                ],
+                ..default()
+            },
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        ConsoleText,
+    ));
+}
+
+pub fn console_input_sys(
+    key_input: Res<Input<KeyCode>>,
+    config: Res<Assets<Config>>,
+    config_state: Res<ConfigState>,
+    mut console: ResMut<Console>,
+    mut char_events: EventReader<ReceivedCharacter>,
+    mut command_events: EventWriter<ConsoleCommandEvent>,
+) {
+    let Some(config) = config.get(&config_state.handle) else { return; };
+
+    if key_input.just_pressed(config.key_console) {
+        console.open = !console.open;
+        console.line.clear();
+        char_events.clear();
+        return;
+    }
+    if !console.open {
+        char_events.clear();
+        return;
+    }
+
+    for event in char_events.read() {
+        if !event.char.is_control() {
+            console.line.push(event.char);
+        }
+    }
+    if key_input.just_pressed(KeyCode::Back) {
+        console.line.pop();
+    }
+    if key_input.just_pressed(KeyCode::Return) {
+        if let Some(command) = ConsoleCommandEvent::parse(&console.line) {
+            command_events.send(command);
+        }
+        console.line.clear();
+        console.open = false;
+    }
+}
+
+fn update_console_text_sys(
+    console: Res<Console>,
+    mut text_query: Query<(&mut Text, &mut Visibility), With<ConsoleText>>,
+) {
+    if !console.is_changed() { return; }
+
+    for (mut text, mut visibility) in text_query.iter_mut() {
+        *visibility = if console.open { Visibility::Visible } else { Visibility::Hidden };
+        let text = &mut text.sections[0].value;
+        text.clear();
+        text.push_str("> ");
+        text.push_str(&console.line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_splits_name_and_args() {
+        let command = ConsoleCommandEvent::parse("  timescale   0.5 extra ").unwrap();
+        assert_eq!(command.name, "timescale");
+        assert_eq!(command.args, ["0.5", "extra"]);
+        assert_eq!(command.arg::<f32>(0), Some(0.5));
+        assert_eq!(command.arg::<f32>(1), None);
+        assert_eq!(command.arg::<f32>(2), None);
+    }
+
+    #[test]
+    fn parse_blank_line_is_none() {
+        assert!(ConsoleCommandEvent::parse("   ").is_none());
+    }
+}
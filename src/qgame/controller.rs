@@ -1,14 +1,70 @@
+use std::{
+    f32::consts::{FRAC_PI_4, TAU},
+    time::Duration,
+};
+
 use bevy::{
+    asset::{
+        AssetLoader,
+        AsyncReadExt,
+        io::Reader,
+        LoadContext,
+    },
     math::Vec3Swizzles,
     prelude::*,
+    reflect::TypePath,
+    utils::BoxedFuture,
 };
 use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use crate::{PlayerInput, PlayerInputFlags};
+use crate::{ActionCooldowns, AimProgress, Chunk, clamp_pitch, Config, ConfigState, GameTime, Grapple, Health, HitboxOwner, InputAccumulator, Interactor, Inventory, latched_input_flags, PlayerInput, PlayerInputFlags, Respawning, RonLoaderError, Shield, spawn_player_hitboxes, TerrainHeightmap, ViewModelMotion, wrap_angle};
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum MoveMode {
-    Noclip,
+    /// Flying while still colliding with terrain
+    #[serde(alias = "Noclip")]
+    Fly,
     Ground,
+    /// Flying without colliding with anything, also what the noclip toggle switches to
+    Spectate,
+}
+
+/// Movement ticks per second, physics steps at the same rate
+pub const MOVE_TICK_RATE: f64 = 64.0;
+
+pub const PLAYER_RADIUS: f32 = 0.5;
+
+/// Collision group of chunk colliders, left out of the filters of players using [`GroundCollision::AnalyticTerrain`]
+pub const TERRAIN_GROUP: Group = Group::GROUP_2;
+
+/// How the player collides with terrain while walking
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GroundCollision {
+    /// Shape cast against every collider, including chunk trimeshes
+    #[default]
+    Rapier,
+    /// Ground and penetration come from the terrain heightmap, chunk colliders are ignored.
+    /// Rapier is only used for everything that is not terrain.
+    AnalyticTerrain,
+}
+
+/// What a player is standing on
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Surface {
+    /// Voxel chunk
+    Terrain,
+    /// Any other collider
+    Solid,
+}
+
+/// Sent every stride while walking on the ground, more often the faster the player moves
+#[derive(Event, Debug)]
+pub struct FootstepEvent {
+    pub player_ent: Entity,
+    /// Feet of the player
+    pub position: Vec3,
+    pub surface: Surface,
 }
 
 #[derive(Component)]
@@ -20,9 +76,103 @@ pub struct RenderPlayer(pub u8);
 #[derive(Component)]
 pub struct VisualTransform(pub Transform);
 
+/// Over the shoulder camera placement, the player still aims at what is under the crosshair
+#[derive(Component)]
+pub struct ThirdPerson {
+    pub enabled: bool,
+    /// Distance behind the eye
+    pub distance: f32,
+    /// Distance to the side of the eye
+    pub shoulder_offset: f32,
+    pub is_right_shoulder: bool,
+}
+
+/// How the render camera field of view reacts to movement, all angles are vertical and in radians
+#[derive(Resource)]
+pub struct CameraFeel {
+    pub base_fov: f32,
+    /// Used while sprinting and moving
+    pub sprint_fov: f32,
+    /// Used while aiming down sights, takes priority over sprinting
+    pub aim_fov: f32,
+    /// How quickly the field of view eases towards its target, higher is snappier
+    pub fov_ease_rate: f32,
+}
+
+/// Scales walk and run speed, set from the equipped item
+#[derive(Component)]
+pub struct MoveFactor(pub f32);
+
+impl Default for ThirdPerson {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            distance: 3.0,
+            shoulder_offset: 0.75,
+            is_right_shoulder: true,
+        }
+    }
+}
+
+impl Default for CameraFeel {
+    fn default() -> Self {
+        Self {
+            base_fov: FRAC_PI_4,
+            sprint_fov: FRAC_PI_4 * 1.15,
+            aim_fov: FRAC_PI_4 * 0.7,
+            fov_ease_rate: 8.0,
+        }
+    }
+}
+
+impl CameraFeel {
+    pub fn target_fov(&self, input: &PlayerInput) -> f32 {
+        let is_sprinting = input.flags.contains(PlayerInputFlags::Sprint) && input.movement != Vec3::ZERO;
+        if input.flags.contains(PlayerInputFlags::Aim) {
+            self.aim_fov
+        } else if is_sprinting {
+            self.sprint_fov
+        } else {
+            self.base_fov
+        }
+    }
+}
+
+impl Default for MoveFactor {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+/// Drained by sprinting, regenerates once not sprinting for `regen_delay`.
+/// Running out drops the player back to walk speed until sprint is released.
+#[derive(Component, Debug)]
+pub struct Stamina {
+    pub current: f32,
+    pub max: f32,
+    /// Points per second while sprinting
+    pub drain_rate: f32,
+    /// Points per second
+    pub regen_rate: f32,
+    pub regen_delay: Duration,
+    pub since_drain: Duration,
+    /// Ran out while sprinting, sprint has to be pressed again even once some stamina is back
+    pub is_exhausted: bool,
+}
+
+/// View kick from firing, stored as (pitch, yaw)
+#[derive(Component, Default)]
+pub struct Recoil {
+    /// Kick to apply on the next look update
+    pub impulse: Vec2,
+    /// Kick that has been applied but not yet recovered from
+    pub offset: Vec2,
+}
+
 #[derive(Component)]
 pub struct PlayerController {
     pub move_mode: MoveMode,
+    pub ground_collision: GroundCollision,
     pub gravity: f32,
     pub walk_speed: f32,
     pub run_speed: f32,
@@ -43,12 +193,38 @@ pub struct PlayerController {
     pub velocity: Vec3,
     pub ground_tick: u8,
     pub stop_speed: f32,
+    pub recoil_recovery: f32,
+    /// Sprinting sideways or backwards moves at walk speed
+    pub sprint_requires_forward: bool,
+    pub crouch_speed: f32,
+    /// Capsule height from the feet, the eye is at the top
+    pub stand_height: f32,
+    pub crouch_height: f32,
+    /// Eases towards the stand or crouch height, the capsule and eye follow it
+    pub height: f32,
+    /// How quickly the height eases, higher is snappier
+    pub crouch_ease_rate: f32,
+    /// Replaces `friction` while sliding, sprinting into a crouch on the ground starts a slide
+    pub slide_friction: f32,
+    /// Slides end below this lateral speed, and need at least this much to start
+    pub slide_min_speed: f32,
+    /// Longest a slide lasts, in seconds
+    pub slide_duration: f32,
+    /// Seconds left in the current slide, zero when not sliding
+    pub slide_time_left: f32,
+    /// Crouch was held last tick, so that only a fresh press starts a slide
+    pub was_crouching: bool,
+    /// Distance covered on the ground between footsteps
+    pub stride_length: f32,
+    /// Distance covered since the last footstep
+    pub stride_progress: f32,
 }
 
 impl Default for PlayerController {
     fn default() -> Self {
         Self {
-            move_mode: MoveMode::Noclip,
+            move_mode: MoveMode::Fly,
+            ground_collision: GroundCollision::Rapier,
             fly_speed: 10.0,
             fast_fly_speed: 30.0,
             gravity: 23.0,
@@ -69,10 +245,356 @@ impl Default for PlayerController {
             ground_tick: 0,
             stop_speed: 1.0,
             jump_speed: 8.5,
+            recoil_recovery: 0.5,
+            sprint_requires_forward: true,
+            crouch_speed: 4.0,
+            stand_height: 2.0,
+            crouch_height: 1.25,
+            height: 2.0,
+            crouch_ease_rate: 12.0,
+            slide_friction: 1.0,
+            slide_min_speed: 8.0,
+            slide_duration: 1.0,
+            slide_time_left: 0.0,
+            was_crouching: false,
+            stride_length: 2.5,
+            stride_progress: 0.0,
         }
     }
 }
 
+impl PlayerController {
+    /// Whether sprint speed applies to this input
+    pub fn is_sprinting(&self, input: &PlayerInput) -> bool {
+        input.flags.contains(PlayerInputFlags::Sprint) && (!self.sprint_requires_forward || input.movement.z > 0.0)
+    }
+
+    /// Chunk colliders are left out when the heightmap stands in for them
+    pub fn collision_groups(&self) -> CollisionGroups {
+        match self.ground_collision {
+            GroundCollision::Rapier => CollisionGroups::default(),
+            GroundCollision::AnalyticTerrain => CollisionGroups::new(Group::ALL, Group::ALL - TERRAIN_GROUP),
+        }
+    }
+
+    /// Ease the height towards `target_height`, snapping once close, returns whether it changed
+    pub fn ease_height(&mut self, target_height: f32, dt: f32) -> bool {
+        if self.height == target_height { return false; }
+
+        let ease = 1.0 - (-self.crouch_ease_rate * dt).exp();
+        self.height += (target_height - self.height) * ease;
+        if (target_height - self.height).abs() < 1e-3 {
+            self.height = target_height;
+        }
+        true
+    }
+
+    /// Change velocity by a world space amount in any move mode.
+    /// Flying keeps velocity along the view instead, and moving input replaces it on the next tick.
+    pub fn add_world_velocity(&mut self, delta: Vec3) {
+        match self.move_mode {
+            MoveMode::Ground => {
+                self.velocity += delta;
+                // Otherwise the next move would treat us as grounded and cancel the vertical part
+                self.ground_tick = 0;
+            }
+            MoveMode::Fly | MoveMode::Spectate => {
+                // Stored as (right, world up, forward), which are not orthogonal once pitched
+                let rot = look_quat(self.pitch, self.yaw);
+                let (right, fwd) = (rot * Vec3::X, rot * -Vec3::Z);
+                let along_right = delta.dot(right);
+                let rest = delta - right * along_right;
+                let fwd_xz_sq = fwd.xz().length_squared();
+                let along_fwd = if fwd_xz_sq > 1e-6 { rest.xz().dot(fwd.xz()) / fwd_xz_sq } else { 0.0 };
+                self.velocity += Vec3::new(along_right, rest.y - along_fwd * fwd.y, along_fwd);
+            }
+        }
+    }
+}
+
+impl MoveMode {
+    /// Mode after a tick of input, a wanted mode takes priority over the noclip and fly toggles
+    pub fn after_input(self, input: &PlayerInput) -> MoveMode {
+        if let Some(wanted_move_mode) = input.wanted_move_mode {
+            wanted_move_mode
+        } else if input.flags.contains(PlayerInputFlags::Noclip) {
+            match self {
+                MoveMode::Spectate => MoveMode::Ground,
+                MoveMode::Ground | MoveMode::Fly => MoveMode::Spectate,
+            }
+        } else if input.flags.contains(PlayerInputFlags::Fly) {
+            match self {
+                MoveMode::Ground => MoveMode::Fly,
+                MoveMode::Fly | MoveMode::Spectate => MoveMode::Ground,
+            }
+        } else {
+            self
+        }
+    }
+}
+
+impl Stamina {
+    pub fn new(max: f32, drain_rate: f32, regen_rate: f32, regen_delay: Duration) -> Self {
+        Self { current: max, max, drain_rate, regen_rate, regen_delay, since_drain: Duration::ZERO, is_exhausted: false }
+    }
+
+    /// Drain if sprinting and regenerate otherwise, returns whether there was stamina left to sprint with
+    pub fn tick(&mut self, is_sprinting: bool, delta: Duration) -> bool {
+        if !is_sprinting {
+            self.is_exhausted = false;
+        } else if self.current <= 0.0 {
+            // Otherwise regenerating a sliver would let sprint flicker back on every few ticks
+            self.is_exhausted = true;
+        }
+        if is_sprinting && !self.is_exhausted {
+            self.since_drain = Duration::ZERO;
+            self.current = f32::max(self.current - self.drain_rate * delta.as_secs_f32(), 0.0);
+            return true;
+        }
+        self.since_drain = self.since_drain.saturating_add(delta);
+        if self.since_drain >= self.regen_delay {
+            self.current = f32::min(self.current + self.regen_rate * delta.as_secs_f32(), self.max);
+        }
+        false
+    }
+}
+
+/// Movement feel applied to every [`PlayerController`], loaded from `default.movement.ron`
+#[derive(Asset, Copy, Clone, Debug, PartialEq, Serialize, Deserialize, TypePath)]
+pub struct MovementTuning {
+    pub gravity: f32,
+    pub walk_speed: f32,
+    pub run_speed: f32,
+    pub fwd_speed: f32,
+    pub side_speed: f32,
+    pub air_speed_cap: f32,
+    pub air_accel: f32,
+    pub max_air_speed: f32,
+    pub accel: f32,
+    pub friction: f32,
+    pub friction_cutoff: f32,
+    pub stop_speed: f32,
+    pub jump_speed: f32,
+    pub fly_speed: f32,
+    pub fast_fly_speed: f32,
+    pub fly_friction: f32,
+    pub sprint_requires_forward: bool,
+    pub crouch_speed: f32,
+    pub slide_friction: f32,
+    pub slide_min_speed: f32,
+    pub slide_duration: f32,
+    pub ground_collision: GroundCollision,
+}
+
+#[derive(Resource)]
+pub struct MovementTuningState {
+    pub handle: Handle<MovementTuning>,
+}
+
+impl Default for MovementTuning {
+    fn default() -> Self {
+        let controller = PlayerController::default();
+        Self {
+            gravity: controller.gravity,
+            walk_speed: controller.walk_speed,
+            run_speed: controller.run_speed,
+            fwd_speed: controller.fwd_speed,
+            side_speed: controller.side_speed,
+            air_speed_cap: controller.air_speed_cap,
+            air_accel: controller.air_accel,
+            max_air_speed: controller.max_air_speed,
+            accel: controller.accel,
+            friction: controller.friction,
+            friction_cutoff: controller.friction_cutoff,
+            stop_speed: controller.stop_speed,
+            jump_speed: controller.jump_speed,
+            fly_speed: controller.fly_speed,
+            fast_fly_speed: controller.fast_fly_speed,
+            fly_friction: controller.fly_friction,
+            sprint_requires_forward: controller.sprint_requires_forward,
+            crouch_speed: controller.crouch_speed,
+            slide_friction: controller.slide_friction,
+            slide_min_speed: controller.slide_min_speed,
+            slide_duration: controller.slide_duration,
+            ground_collision: controller.ground_collision,
+        }
+    }
+}
+
+impl MovementTuning {
+    pub fn apply(&self, controller: &mut PlayerController) {
+        controller.gravity = self.gravity;
+        controller.walk_speed = self.walk_speed;
+        controller.run_speed = self.run_speed;
+        controller.fwd_speed = self.fwd_speed;
+        controller.side_speed = self.side_speed;
+        controller.air_speed_cap = self.air_speed_cap;
+        controller.air_accel = self.air_accel;
+        controller.max_air_speed = self.max_air_speed;
+        controller.accel = self.accel;
+        controller.friction = self.friction;
+        controller.friction_cutoff = self.friction_cutoff;
+        controller.stop_speed = self.stop_speed;
+        controller.jump_speed = self.jump_speed;
+        controller.fly_speed = self.fly_speed;
+        controller.fast_fly_speed = self.fast_fly_speed;
+        controller.fly_friction = self.fly_friction;
+        controller.sprint_requires_forward = self.sprint_requires_forward;
+        controller.crouch_speed = self.crouch_speed;
+        controller.slide_friction = self.slide_friction;
+        controller.slide_min_speed = self.slide_min_speed;
+        controller.slide_duration = self.slide_duration;
+        controller.ground_collision = self.ground_collision;
+    }
+}
+
+#[derive(Default)]
+pub struct MovementTuningAssetLoader;
+
+impl AssetLoader for MovementTuningAssetLoader {
+    type Asset = MovementTuning;
+    type Settings = ();
+    type Error = RonLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a Self::Settings,
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<MovementTuning, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let asset = ron::de::from_bytes::<MovementTuning>(&bytes)?;
+            Ok(asset)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["movement.ron"]
+    }
+}
+
+/// Apply the tuning to newly spawned controllers, and to every controller when the asset (re)loads
+pub fn apply_movement_tuning_sys(
+    mut asset_events: EventReader<AssetEvent<MovementTuning>>,
+    tuning_state: Res<MovementTuningState>,
+    tunings: Res<Assets<MovementTuning>>,
+    mut controller_query: Query<&mut PlayerController>,
+) {
+    let id = tuning_state.handle.id();
+    let is_tuning_changed = asset_events.read()
+        .any(|event| event.is_loaded_with_dependencies(id) || event.is_modified(id));
+    let Some(tuning) = tunings.get(id) else { return; };
+    for mut controller in controller_query.iter_mut() {
+        if is_tuning_changed || controller.is_added() {
+            tuning.apply(&mut controller);
+        }
+    }
+}
+
+/// Keep the collision groups of players in line with how they collide with terrain
+pub fn player_collision_groups_sys(
+    mut commands: Commands,
+    query: Query<(Entity, &PlayerController, Option<&CollisionGroups>)>,
+) {
+    for (entity, controller, groups) in query.iter() {
+        let wanted = controller.collision_groups();
+        if groups != Some(&wanted) {
+            commands.entity(entity).insert(wanted);
+        }
+    }
+}
+
+/// How far `feet` are below the heightmap surface, `None` above it or over columns that are not generated
+pub fn terrain_penetration(heightmap: &TerrainHeightmap, feet: Vec3) -> Option<f32> {
+    let height = heightmap.sample(feet.x, feet.z)?;
+    (feet.y < height).then_some(height - feet.y)
+}
+
+/// Capsule standing on the origin of the player, `height` tall
+pub fn player_capsule(height: f32) -> Collider {
+    Collider::capsule(Vec3::Y * PLAYER_RADIUS, Vec3::Y * (height - PLAYER_RADIUS), PLAYER_RADIUS)
+}
+
+/// Physics body and gameplay state of a player, without a camera.
+/// Hitboxes are children, see [`spawn_logical_player`] for spawning both.
+#[derive(Bundle)]
+pub struct PlayerBundle {
+    pub collider: Collider,
+    pub velocity: Velocity,
+    pub rigid_body: RigidBody,
+    pub sleeping: Sleeping,
+    pub locked_axes: LockedAxes,
+    pub mass: AdditionalMassProperties,
+    pub read_mass: ReadMassProperties,
+    pub gravity_scale: GravityScale,
+    pub ccd: Ccd,
+    pub transform: TransformBundle,
+    pub player: LogicalPlayer,
+    pub input: PlayerInput,
+    pub controller: PlayerController,
+    pub inventory: Inventory,
+    pub aim: AimProgress,
+    pub view_model_motion: ViewModelMotion,
+    pub recoil: Recoil,
+    pub third_person: ThirdPerson,
+    pub cooldowns: ActionCooldowns,
+    pub move_factor: MoveFactor,
+    pub grapple: Grapple,
+    pub interactor: Interactor,
+    pub input_accumulator: InputAccumulator,
+    pub stamina: Stamina,
+    pub health: Health,
+    pub shield: Shield,
+    pub hitbox_owner: HitboxOwner,
+}
+
+impl PlayerBundle {
+    pub fn new(player_id: u8, transform: Transform) -> Self {
+        let controller = PlayerController::default();
+        Self {
+            collider: player_capsule(controller.stand_height),
+            velocity: Velocity::zero(),
+            rigid_body: RigidBody::Dynamic,
+            sleeping: Sleeping::disabled(),
+            locked_axes: LockedAxes::ROTATION_LOCKED,
+            mass: AdditionalMassProperties::Mass(1.0),
+            read_mass: ReadMassProperties::default(),
+            gravity_scale: GravityScale(0.0),
+            ccd: Ccd { enabled: true },
+            transform: TransformBundle::from(transform),
+            player: LogicalPlayer(player_id),
+            input: PlayerInput {
+                pitch: -TAU / 12.0,
+                yaw: TAU * 5.0 / 8.0,
+                ..default()
+            },
+            controller,
+            inventory: Inventory::default(),
+            aim: AimProgress::default(),
+            view_model_motion: ViewModelMotion::default(),
+            recoil: Recoil::default(),
+            third_person: ThirdPerson::default(),
+            cooldowns: ActionCooldowns::default(),
+            move_factor: MoveFactor::default(),
+            grapple: Grapple::default(),
+            interactor: Interactor::default(),
+            input_accumulator: InputAccumulator::default(),
+            stamina: Stamina::new(100.0, 20.0, 25.0, Duration::from_secs(1)),
+            health: Health::new(100.0),
+            shield: Shield::new(50.0, Duration::from_secs(3), 25.0),
+            hitbox_owner: HitboxOwner,
+        }
+    }
+}
+
+/// Spawn a [`PlayerBundle`] along with its hitboxes
+pub fn spawn_logical_player(commands: &mut Commands, player_id: u8, transform: Transform) -> Entity {
+    let bundle = PlayerBundle::new(player_id, transform);
+    let height = bundle.controller.height;
+    commands.spawn(bundle).with_children(|parent| spawn_player_hitboxes(parent, height)).id()
+}
 
 // ██╗      ██████╗  ██████╗ ██╗ ██████╗
 // ██║     ██╔═══██╗██╔════╝ ██║██╔════╝
@@ -81,40 +603,108 @@ impl Default for PlayerController {
 // ███████╗╚██████╔╝╚██████╔╝██║╚██████╗
 // ╚══════╝ ╚═════╝  ╚═════╝ ╚═╝ ╚═════╝
 
+/// Look follows input every frame, including motion that the next movement tick has yet to consume
 pub fn player_look_sys(
-    mut query: Query<(&mut PlayerController, &PlayerInput)>
+    time: Res<GameTime>,
+    mut query: Query<(&mut PlayerController, &mut PlayerInput, Option<&InputAccumulator>, Option<&mut Recoil>)>
 ) {
-    for (mut controller, input) in query.iter_mut() {
-        controller.pitch = input.pitch;
-        controller.yaw = input.yaw;
+    let dt = time.delta_seconds();
+
+    for (mut controller, mut input, accumulator, recoil) in query.iter_mut() {
+        let pending = accumulator.map_or(Vec2::ZERO, |accumulator| accumulator.look_delta);
+        if let Some(mut recoil) = recoil {
+            // Pulling down against the kick counts towards recovering from it
+            let input_pitch = clamp_pitch(input.pitch - pending.y) - controller.pitch;
+            if recoil.offset.x > 0.0 && input_pitch < 0.0 {
+                recoil.offset.x = f32::max(recoil.offset.x + input_pitch, 0.0);
+            }
+
+            let impulse = std::mem::take(&mut recoil.impulse);
+            recoil.offset += impulse;
+
+            let recovery = recoil.offset.clamp_length_max(controller.recoil_recovery * dt);
+            recoil.offset -= recovery;
+
+            let delta = impulse - recovery;
+            input.pitch = clamp_pitch(input.pitch + delta.x);
+            input.yaw += delta.y;
+        }
+        input.yaw = wrap_angle(input.yaw);
+        controller.pitch = clamp_pitch(input.pitch - pending.y);
+        controller.yaw = wrap_angle(input.yaw - pending.x);
     }
 }
 
+/// Runs in [`FixedUpdate`], so every tick advances by the same amount regardless of frame rate
 pub fn player_move_sys(
+    mut commands: Commands,
     time: Res<Time>,
+    game_time: Res<GameTime>,
     physics_context: Res<RapierContext>,
+    heightmap: Res<TerrainHeightmap>,
+    chunk_query: Query<(), With<Chunk>>,
+    mut footstep_events: EventWriter<FootstepEvent>,
     mut query: Query<(
-        Entity, &PlayerInput, &mut PlayerController,
-        &Collider, &mut Transform, &mut Velocity
-    )>,
+        Entity, &mut PlayerInput, &mut PlayerController,
+        &mut Collider, &mut Transform, &mut Velocity, Option<&MoveFactor>, Option<&Grapple>, Option<&mut Stamina>,
+    ), Without<Respawning>>,
 ) {
-    let dt = time.delta_seconds();
+    // Fixed step inside of the fixed schedule
+    let dt = time.delta_seconds() * game_time.scale;
 
-    for (entity, input, mut controller, collider, transform, mut vel) in query.iter_mut() {
-        if input.flags.contains(PlayerInputFlags::Fly) {
-            controller.move_mode = match controller.move_mode {
-                MoveMode::Noclip => MoveMode::Ground,
-                MoveMode::Ground => MoveMode::Noclip
+    for (entity, mut input, mut controller, mut collider, mut transform, mut vel, move_factor, grapple, stamina) in query.iter_mut() {
+        let prev_move_mode = controller.move_mode;
+        // Toggles are latched by input polling until a tick consumes them
+        controller.move_mode = controller.move_mode.after_input(&input);
+        input.wanted_move_mode = None;
+        input.flags -= latched_input_flags();
+        if controller.move_mode != prev_move_mode {
+            if controller.move_mode == MoveMode::Spectate {
+                commands.entity(entity).insert(ColliderDisabled);
+            } else if prev_move_mode == MoveMode::Spectate {
+                commands.entity(entity).remove::<ColliderDisabled>();
             }
         }
 
+        let is_crouching = controller.move_mode == MoveMode::Ground && input.flags.contains(PlayerInputFlags::Crouch);
+        let is_crouch_pressed = is_crouching && !controller.was_crouching;
+        controller.was_crouching = is_crouching;
+        if !is_crouching {
+            controller.slide_time_left = 0.0;
+        }
+        let wants_sprint = controller.move_mode == MoveMode::Ground
+            && !is_crouching
+            && input.movement != Vec3::ZERO
+            && controller.is_sprinting(&input);
+        let is_sprinting = match stamina {
+            Some(mut stamina) => stamina.tick(wants_sprint, Duration::from_secs_f32(dt)),
+            None => wants_sprint,
+        };
+
         let rot = look_quat(input.pitch, input.yaw);
         let right = rot * Vec3::X;
         let fwd = rot * -Vec3::Z;
-        let pos = transform.translation;
+        let mut pos = transform.translation;
+
+        // The capsule shrinks and grows from the top, so the feet and whether we are grounded stay the same
+        let mut target_height = if is_crouching { controller.crouch_height } else { controller.stand_height };
+        if target_height > controller.height {
+            // Only stand up as far as the space above the head allows
+            let head = Collider::ball(PLAYER_RADIUS * 0.99);
+            let head_pos = pos + Vec3::Y * (controller.height - PLAYER_RADIUS);
+            let filter = QueryFilter::default().exclude_rigid_body(entity).exclude_sensors();
+            if let Some((_, hit)) = physics_context.cast_shape(
+                head_pos, Quat::IDENTITY, Vec3::Y, &head, target_height - controller.height, true, filter,
+            ) {
+                target_height = controller.height + hit.toi;
+            }
+        }
+        if controller.ease_height(target_height, dt) {
+            *collider = player_capsule(controller.height);
+        }
 
         match controller.move_mode {
-            MoveMode::Noclip => {
+            MoveMode::Fly | MoveMode::Spectate => {
                 if input.movement == Vec3::ZERO {
                     let friction = controller.fly_friction.clamp(0.0, 1.0);
                     controller.velocity *= 1.0 - friction;
@@ -141,17 +731,54 @@ pub fn player_move_sys(
                     let mut end_vel = init_vel;
                     let lateral_speed = init_vel.xz().length();
 
-                    // Capsule cast downwards to find ground
                     let mut ground_hit = None;
+                    let mut ground_surface = Surface::Solid;
+                    let max_dist = 0.125;
+
+                    // Chunk colliders are filtered out by the collision groups, so the heightmap has to resolve penetration too
+                    let is_analytic = controller.ground_collision == GroundCollision::AnalyticTerrain
+                        && heightmap.sample(pos.x, pos.z).is_some();
+                    if is_analytic {
+                        let segment_a: Vec3 = capsule.segment.a.into();
+                        let segment_b: Vec3 = capsule.segment.b.into();
+                        let mut feet = pos + Vec3::Y * (f32::min(segment_a.y, segment_b.y) - capsule.radius);
+                        if let Some(depth) = terrain_penetration(&heightmap, feet) {
+                            transform.translation.y += depth;
+                            pos.y += depth;
+                            feet.y += depth;
+                            init_vel.y = init_vel.y.max(0.0);
+                            end_vel.y = end_vel.y.max(0.0);
+                        }
+                        if let Some(hit) = heightmap.cast_down(feet, max_dist) {
+                            ground_hit = Some(hit.toi);
+                            ground_surface = Surface::Terrain;
+                        }
+                    }
+
+                    // Capsule cast downwards to find ground
                     let cast_capsule = Collider::capsule(capsule.segment.a.into(), capsule.segment.b.into(), capsule.radius * 0.99);
                     let cast_vel = Vec3::Y * -1.0;
-                    let max_dist = 0.125;
-                    let groups = QueryFilter::default().exclude_collider(entity);
+                    // Terrain was already handled analytically if we have a height
+                    let is_not_chunk = |collider_ent: Entity| !chunk_query.contains(collider_ent);
+                    // Excluding the whole body also skips our own hitboxes
+                    let mut groups = QueryFilter::default().exclude_rigid_body(entity);
+                    if is_analytic {
+                        groups = groups.predicate(&is_not_chunk);
+                    }
 
-                    if let Some((_handle, hit)) = physics_context.cast_shape(
-                        pos, rot, cast_vel, &cast_capsule, max_dist, true, groups,
-                    ) {
-                        ground_hit = Some(hit);
+                    if ground_hit.is_none() {
+                        if let Some((hit_ent, hit)) = physics_context.cast_shape(
+                            pos, rot, cast_vel, &cast_capsule, max_dist, true, groups,
+                        ) {
+                            ground_hit = Some(hit.toi);
+                            ground_surface = if chunk_query.contains(hit_ent) { Surface::Terrain } else { Surface::Solid };
+                        }
+                    }
+
+                    // The rope lifts the player off the ground, so friction and ground snapping must not fight it
+                    let is_grappling = grapple.is_some_and(Grapple::is_attached);
+                    if is_grappling {
+                        ground_hit = None;
                     }
 
                     let mut wish_dir = input.movement.z * controller.fwd_speed * fwd + input.movement.x * controller.side_speed * right;
@@ -160,7 +787,9 @@ pub fn player_move_sys(
                         wish_dir /= wish_speed; // Effectively normalize, avoid length computation twice
                     }
 
-                    let max_speed = if input.flags.contains(PlayerInputFlags::Sprint) {
+                    let max_speed = move_factor.map_or(1.0, |factor| factor.0) * if is_crouching {
+                        controller.crouch_speed
+                    } else if is_sprinting {
                         controller.run_speed
                     } else {
                         controller.walk_speed
@@ -169,17 +798,29 @@ pub fn player_move_sys(
                     wish_speed = f32::min(wish_speed, max_speed);
 
                     if let Some(_ground_hit) = ground_hit {
-                        // Only apply friction after at least one tick, allows b-hopping without losing speed
-                        if controller.ground_tick >= 1 {
-                            if lateral_speed > controller.friction_cutoff {
-                                friction(lateral_speed, controller.friction, controller.stop_speed, dt, &mut end_vel);
-                            } else {
-                                end_vel.x = 0.0;
-                                end_vel.z = 0.0;
-                            }
+                        let can_slide = controller.ground_tick >= 1 && lateral_speed >= controller.slide_min_speed;
+                        if is_crouch_pressed && can_slide && controller.is_sprinting(&input) {
+                            controller.slide_time_left = controller.slide_duration;
+                        }
+                        if controller.slide_time_left > 0.0 && can_slide {
+                            // Momentum carries the slide, movement input is ignored until it ends
+                            controller.slide_time_left = f32::max(controller.slide_time_left - dt, 0.0);
+                            friction(lateral_speed, controller.slide_friction, controller.stop_speed, dt, &mut end_vel);
                             end_vel.y = 0.0;
+                        } else {
+                            controller.slide_time_left = 0.0;
+                            // Only apply friction after at least one tick, allows b-hopping without losing speed
+                            if controller.ground_tick >= 1 {
+                                if lateral_speed > controller.friction_cutoff {
+                                    friction(lateral_speed, controller.friction, controller.stop_speed, dt, &mut end_vel);
+                                } else {
+                                    end_vel.x = 0.0;
+                                    end_vel.z = 0.0;
+                                }
+                                end_vel.y = 0.0;
+                            }
+                            accelerate(wish_dir, wish_speed, controller.accel, dt, &mut end_vel);
                         }
-                        accelerate(wish_dir, wish_speed, controller.accel, dt, &mut end_vel);
                         if input.flags.contains(PlayerInputFlags::Jump) {
                             // Simulate one update ahead, since this is an instant velocity change
                             init_vel.y = controller.jump_speed;
@@ -187,13 +828,19 @@ pub fn player_move_sys(
                         }
                         // Increment ground tick but cap at max value
                         controller.ground_tick = controller.ground_tick.saturating_add(1);
+
+                        controller.stride_progress += lateral_speed * dt;
+                        if controller.stride_progress >= controller.stride_length {
+                            controller.stride_progress -= controller.stride_length;
+                            footstep_events.send(FootstepEvent { player_ent: entity, position: pos, surface: ground_surface });
+                        }
                     } else {
                         controller.ground_tick = 0;
                         wish_speed = f32::min(wish_speed, controller.air_speed_cap);
                         accelerate(wish_dir, wish_speed, controller.air_accel, dt, &mut end_vel);
                         end_vel.y -= controller.gravity * dt;
                         let air_speed = end_vel.xz().length();
-                        if air_speed > controller.max_air_speed {
+                        if air_speed > controller.max_air_speed && !is_grappling {
                             let ratio = controller.max_air_speed / air_speed;
                             end_vel.x *= ratio;
                             end_vel.z *= ratio;
@@ -217,10 +864,58 @@ pub fn player_move_sys(
     }
 }
 
-fn look_quat(pitch: f32, yaw: f32) -> Quat {
+pub fn look_quat(pitch: f32, yaw: f32) -> Quat {
     Quat::from_euler(EulerRot::ZYX, 0.0, yaw, pitch)
 }
 
+/// Where the player sees from, in world space
+pub fn eye_transform(transform: &Transform, controller: &PlayerController) -> Transform {
+    Transform::from_translation(transform.translation + Vec3::Y * controller.height)
+        .with_rotation(look_quat(controller.pitch, controller.yaw))
+}
+
+/// Where the render camera sits, the eye in first person or behind a shoulder in third person
+pub fn camera_transform(transform: &Transform, controller: &PlayerController, third_person: Option<&ThirdPerson>) -> Transform {
+    let eye = eye_transform(transform, controller);
+    match third_person {
+        Some(third_person) if third_person.enabled => {
+            let side = if third_person.is_right_shoulder { 1.0 } else { -1.0 };
+            let offset = eye.right() * third_person.shoulder_offset * side - eye.forward() * third_person.distance;
+            eye.with_translation(eye.translation + offset)
+        }
+        _ => eye,
+    }
+}
+
+/// Direction from the eye towards what the camera is looking at.
+/// The camera is offset from the eye in third person, so shooting along the eye forward would miss the crosshair.
+pub fn aim_direction(
+    physics_context: &RapierContext, player_ent: Entity, eye: &Transform, camera: &Transform, max_dist: f32,
+) -> Vec3 {
+    let filter = QueryFilter::default().exclude_collider(player_ent).exclude_sensors();
+    let target_dist = physics_context.cast_ray(camera.translation, camera.forward(), max_dist, true, filter)
+        .map_or(max_dist, |(_, toi)| toi);
+    let target = camera.translation + camera.forward() * target_dist;
+    (target - eye.translation).try_normalize().unwrap_or(camera.forward())
+}
+
+pub fn third_person_toggle_sys(
+    key_input: Res<Input<KeyCode>>,
+    config: Res<Assets<Config>>,
+    config_state: Res<ConfigState>,
+    mut query: Query<&mut ThirdPerson, With<LogicalPlayer>>,
+) {
+    let Some(config) = config.get(&config_state.handle) else { return; };
+    for mut third_person in query.iter_mut() {
+        if key_input.just_pressed(config.key_third_person) {
+            third_person.enabled = !third_person.enabled;
+        }
+        if key_input.just_pressed(config.key_swap_shoulder) {
+            third_person.is_right_shoulder = !third_person.is_right_shoulder;
+        }
+    }
+}
+
 fn friction(lateral_speed: f32, friction: f32, stop_speed: f32, dt: f32, velocity: &mut Vec3) {
     let control = f32::max(lateral_speed, stop_speed);
     let drop = control * friction * dt;
@@ -248,16 +943,22 @@ fn accelerate(wish_dir: Vec3, wish_speed: f32, accel: f32, dt: f32, velocity: &m
 // ╚═╝  ╚═╝╚══════╝╚═╝  ╚═══╝╚═════╝ ╚══════╝╚═╝  ╚═╝
 
 pub fn render_player_camera_sys(
-    logical_query: Query<(&Transform, &PlayerController, &LogicalPlayer), With<LogicalPlayer>>,
-    mut render_query: Query<(&mut Transform, &RenderPlayer), Without<LogicalPlayer>>,
+    time: Res<Time>,
+    camera_feel: Res<CameraFeel>,
+    logical_query: Query<(&Transform, &PlayerController, &PlayerInput, &LogicalPlayer, Option<&ThirdPerson>), With<LogicalPlayer>>,
+    mut render_query: Query<(&mut Transform, &mut Projection, &RenderPlayer), Without<LogicalPlayer>>,
 ) {
-    for (logical_transform, controller, logical_player_id) in logical_query.iter() {
-        for (mut render_transform, render_player_id) in render_query.iter_mut() {
+    // Frame rate independent exponential ease
+    let ease = 1.0 - (-camera_feel.fov_ease_rate * time.delta_seconds()).exp();
+    for (logical_transform, controller, input, logical_player_id, third_person) in logical_query.iter() {
+        for (mut render_transform, mut projection, render_player_id) in render_query.iter_mut() {
             if logical_player_id.0 != render_player_id.0 {
                 continue;
             }
-            render_transform.translation = logical_transform.translation + Vec3::Y * 2.0;
-            render_transform.rotation = look_quat(controller.pitch, controller.yaw);
+            *render_transform = camera_transform(logical_transform, controller, third_person);
+            if let Projection::Perspective(perspective) = &mut *projection {
+                perspective.fov += (camera_feel.target_fov(input) - perspective.fov) * ease;
+            }
         }
     }
 }
@@ -307,4 +1008,233 @@ pub fn render_player_camera_sys(
 //             }
 //         }
 //     }
-// }
\ No newline at end of file
+// }
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use crate::{CHUNK_SZ, CHUNK_SZ_2};
+
+    use super::*;
+
+    /// Runs [`player_move_sys`] alone, without the physics plugin nothing else moves the player
+    fn move_app(heightmap: TerrainHeightmap) -> App {
+        let mut app = App::new();
+        app
+            .init_resource::<Time>()
+            .init_resource::<GameTime>()
+            .init_resource::<RapierContext>()
+            .insert_resource(heightmap)
+            .add_event::<FootstepEvent>()
+            .add_systems(Update, player_move_sys);
+        app
+    }
+
+    fn spawn_mover(app: &mut App, controller: PlayerController, input: PlayerInput, translation: Vec3) -> Entity {
+        let collider = player_capsule(controller.height);
+        app.world.spawn((input, controller, collider, Transform::from_translation(translation), Velocity::zero())).id()
+    }
+
+    /// Run one movement tick, then move the player by the velocity it left like a physics step would,
+    /// which is scaled by the game time scale like the `timescale` command scales the physics step
+    fn tick(app: &mut App, player: Entity) {
+        let dt = Duration::from_secs_f64(1.0 / MOVE_TICK_RATE);
+        app.world.resource_mut::<Time>().advance_by(dt);
+        app.update();
+        let linvel = app.world.get::<Velocity>(player).unwrap().linvel;
+        let scale = app.world.resource::<GameTime>().scale;
+        app.world.get_mut::<Transform>(player).unwrap().translation += linvel * dt.as_secs_f32() * scale;
+    }
+
+    #[test]
+    fn world_velocity_on_ground_leaves_ground() {
+        let mut controller = PlayerController { move_mode: MoveMode::Ground, ground_tick: 3, ..default() };
+        controller.add_world_velocity(Vec3::new(1.0, 4.0, -2.0));
+        assert_eq!(controller.velocity, Vec3::new(1.0, 4.0, -2.0));
+        assert_eq!(controller.ground_tick, 0);
+    }
+
+    #[test]
+    fn world_velocity_while_flying_is_in_view_space() {
+        let mut controller = PlayerController { move_mode: MoveMode::Fly, pitch: 0.5, yaw: 1.0, ..default() };
+        let delta = Vec3::new(3.0, -2.0, 5.0);
+        controller.add_world_velocity(delta);
+
+        // Same mapping that flying movement uses to get the world velocity
+        let rot = look_quat(controller.pitch, controller.yaw);
+        let velocity = controller.velocity;
+        let world = velocity.x * (rot * Vec3::X) + velocity.y * Vec3::Y + velocity.z * (rot * -Vec3::Z);
+        assert!(world.abs_diff_eq(delta, 1e-5));
+    }
+
+    #[test]
+    fn wanted_move_mode_beats_toggles() {
+        let input = PlayerInput { wanted_move_mode: Some(MoveMode::Ground), flags: PlayerInputFlags::Noclip.into(), ..default() };
+        assert_eq!(MoveMode::Spectate.after_input(&input), MoveMode::Ground);
+        assert_eq!(MoveMode::Fly.after_input(&PlayerInput::default()), MoveMode::Fly);
+    }
+
+    #[test]
+    fn toggles_return_to_ground() {
+        let fly = PlayerInput { flags: PlayerInputFlags::Fly.into(), ..default() };
+        let noclip = PlayerInput { flags: PlayerInputFlags::Noclip.into(), ..default() };
+        assert_eq!(MoveMode::Ground.after_input(&fly), MoveMode::Fly);
+        assert_eq!(MoveMode::Fly.after_input(&fly), MoveMode::Ground);
+        assert_eq!(MoveMode::Spectate.after_input(&fly), MoveMode::Ground);
+        assert_eq!(MoveMode::Fly.after_input(&noclip), MoveMode::Spectate);
+        assert_eq!(MoveMode::Spectate.after_input(&noclip), MoveMode::Ground);
+    }
+
+    #[test]
+    fn camera_sits_behind_chosen_shoulder() {
+        let transform = Transform::from_xyz(1.0, 2.0, 3.0);
+        let controller = PlayerController::default();
+        let eye = eye_transform(&transform, &controller);
+        assert_eq!(camera_transform(&transform, &controller, None), eye);
+        assert_eq!(camera_transform(&transform, &controller, Some(&ThirdPerson::default())), eye);
+
+        // Looking down -z, so right is +x and behind is +z
+        let mut third_person = ThirdPerson { enabled: true, distance: 3.0, shoulder_offset: 0.75, is_right_shoulder: true };
+        let right = camera_transform(&transform, &controller, Some(&third_person));
+        assert!(right.translation.abs_diff_eq(eye.translation + Vec3::new(0.75, 0.0, 3.0), 1e-5));
+        third_person.is_right_shoulder = false;
+        let left = camera_transform(&transform, &controller, Some(&third_person));
+        assert!(left.translation.abs_diff_eq(eye.translation + Vec3::new(-0.75, 0.0, 3.0), 1e-5));
+    }
+
+    #[test]
+    fn aim_converges_on_crosshair() {
+        let physics_context = RapierContext::default();
+        let transform = Transform::default();
+        let controller = PlayerController::default();
+        let eye = eye_transform(&transform, &controller);
+        let player = Entity::from_raw(0);
+        assert!(aim_direction(&physics_context, player, &eye, &eye, 100.0).abs_diff_eq(eye.forward(), 1e-5));
+
+        // From behind the right shoulder the eye has to aim slightly right to hit where the camera looks
+        let third_person = ThirdPerson { enabled: true, ..default() };
+        let camera = camera_transform(&transform, &controller, Some(&third_person));
+        let aim = aim_direction(&physics_context, player, &eye, &camera, 100.0);
+        let target = camera.translation + camera.forward() * 100.0;
+        assert!(aim.x > 0.0);
+        assert!((eye.translation + aim * eye.translation.distance(target)).abs_diff_eq(target, 1e-3));
+    }
+
+    #[test]
+    fn sprint_requires_forward_movement() {
+        let mut controller = PlayerController::default();
+        let sprint = |movement: Vec3| PlayerInput { movement, flags: PlayerInputFlags::Sprint.into(), ..default() };
+        assert!(controller.is_sprinting(&sprint(Vec3::Z)));
+        assert!(!controller.is_sprinting(&sprint(Vec3::X)));
+        assert!(!controller.is_sprinting(&sprint(-Vec3::Z)));
+        assert!(!controller.is_sprinting(&PlayerInput { movement: Vec3::Z, ..default() }));
+
+        controller.sprint_requires_forward = false;
+        assert!(controller.is_sprinting(&sprint(Vec3::X)));
+        assert!(controller.is_sprinting(&sprint(Vec3::ZERO)));
+    }
+
+    #[test]
+    fn stamina_drains_and_regenerates_after_delay() {
+        let mut stamina = Stamina::new(10.0, 5.0, 2.0, Duration::from_secs(1));
+        assert!(stamina.tick(true, Duration::from_secs(1)));
+        assert_eq!(stamina.current, 5.0);
+
+        // Nothing back until the delay has passed
+        assert!(!stamina.tick(false, Duration::from_millis(500)));
+        assert_eq!(stamina.current, 5.0);
+        assert!(!stamina.tick(false, Duration::from_millis(500)));
+        assert_eq!(stamina.current, 6.0);
+        for _ in 0..10 {
+            stamina.tick(false, Duration::from_secs(1));
+        }
+        assert_eq!(stamina.current, stamina.max);
+    }
+
+    #[test]
+    fn exhausted_sprint_stays_off_until_released() {
+        let mut stamina = Stamina::new(1.0, 2.0, 4.0, Duration::ZERO);
+        assert!(stamina.tick(true, Duration::from_millis(500)));
+        assert_eq!(stamina.current, 0.0);
+
+        // Still holding sprint, regenerating a sliver must not let it flicker back on
+        for _ in 0..4 {
+            assert!(!stamina.tick(true, Duration::from_millis(100)));
+            assert!(stamina.is_exhausted);
+        }
+        assert!(stamina.current > 0.0);
+
+        assert!(!stamina.tick(false, Duration::from_millis(100)));
+        assert!(stamina.tick(true, Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn crouch_height_eases_and_snaps() {
+        let mut controller = PlayerController::default();
+        assert!(!controller.ease_height(controller.stand_height, 0.1));
+
+        let crouch_height = controller.crouch_height;
+        assert!(controller.ease_height(crouch_height, 1.0 / 60.0));
+        assert!(controller.height < controller.stand_height && controller.height > crouch_height);
+
+        let mut previous = controller.height;
+        for _ in 0..120 {
+            controller.ease_height(crouch_height, 1.0 / 60.0);
+            assert!(controller.height <= previous);
+            previous = controller.height;
+        }
+        assert_eq!(controller.height, crouch_height);
+        assert!(!controller.ease_height(crouch_height, 1.0 / 60.0));
+    }
+
+    #[test]
+    fn analytic_terrain_keeps_player_above_surface() {
+        // Rises half a unit for every unit along x
+        let mut heightmap = TerrainHeightmap::default();
+        heightmap.insert_chunk(IVec2::ZERO, (0..CHUNK_SZ_2).map(|index| 4.0 + 0.5 * (index % CHUNK_SZ) as f32).collect());
+        let mut app = move_app(heightmap);
+        let controller = PlayerController {
+            move_mode: MoveMode::Ground,
+            ground_collision: GroundCollision::AnalyticTerrain,
+            velocity: Vec3::new(0.0, -20.0, 0.0),
+            ..default()
+        };
+        assert!(!controller.collision_groups().filters.contains(TERRAIN_GROUP));
+        // Walking uphill along x, starting below the surface
+        let input = PlayerInput { movement: Vec3::Z, yaw: -FRAC_PI_2, ..default() };
+        let player = spawn_mover(&mut app, controller, input, Vec3::new(8.0, 2.0, 8.0));
+
+        for _ in 0..32 {
+            tick(&mut app, player);
+            let feet = app.world.get::<Transform>(player).unwrap().translation;
+            let surface = app.world.resource::<TerrainHeightmap>().sample(feet.x, feet.z).unwrap();
+            // Checked before the next tick resolves it, so allow for one step of climbing
+            assert!(feet.y >= surface - 0.5 * 10.0 / MOVE_TICK_RATE as f32 - 1e-4, "{} below {}", feet.y, surface);
+        }
+        assert!(app.world.get::<Transform>(player).unwrap().translation.x > 9.0);
+    }
+
+    #[test]
+    fn shipped_movement_tuning_parses() {
+        let tuning: MovementTuning = ron::from_str(include_str!("../../assets/default.movement.ron")).unwrap();
+        assert_eq!(tuning.ground_collision, GroundCollision::Rapier);
+    }
+
+    #[test]
+    fn half_time_scale_halves_distance_per_second() {
+        let distance_over_second = |scale: f32| {
+            let mut app = move_app(TerrainHeightmap::default());
+            app.world.resource_mut::<GameTime>().scale = scale;
+            let controller = PlayerController { move_mode: MoveMode::Fly, ..default() };
+            let player = spawn_mover(&mut app, controller, PlayerInput { movement: Vec3::NEG_Z, ..default() }, Vec3::ZERO);
+            for _ in 0..MOVE_TICK_RATE as usize {
+                tick(&mut app, player);
+            }
+            app.world.get::<Transform>(player).unwrap().translation.length()
+        };
+        let full = distance_over_second(1.0);
+        assert!(full > 1.0);
+        assert!((distance_over_second(0.5) - full * 0.5).abs() < 1e-3);
+    }
+}
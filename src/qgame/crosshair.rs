@@ -0,0 +1,301 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::{ColorTheme, Console, GameState, HitEvent, LogicalPlayer, PlayerInput, PlayerInputFlags, ThirdPerson};
+
+const HITMARKER_DURATION: Duration = Duration::from_millis(200);
+
+/// Appearance of the crosshair, changes are applied to the existing UI
+#[derive(Resource)]
+pub struct CrosshairSettings {
+    pub hide_while_sprinting: bool,
+    /// Length of each line in pixels
+    pub size: f32,
+    pub thickness: f32,
+    /// Colors are overwritten by [`ColorTheme`] whenever it changes
+    pub color: Color,
+    pub hit_color: Color,
+    pub kill_color: Color,
+}
+
+/// Whether the crosshair should be drawn this frame, only written by [`resolve_crosshair_visibility_sys`]
+#[derive(Resource)]
+pub struct CrosshairVisibility {
+    pub visible: bool,
+}
+
+/// Root UI node of the crosshair at the center of the screen
+#[derive(Component)]
+pub struct Crosshair;
+
+#[derive(Component)]
+pub enum CrosshairLine {
+    Horizontal,
+    Vertical,
+}
+
+/// Flashed over the crosshair when the local player damages something
+#[derive(Component)]
+pub struct Hitmarker {
+    pub age: Duration,
+    pub color: Color,
+}
+
+pub struct CrosshairPlugin;
+
+impl Plugin for CrosshairPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<CrosshairSettings>()
+            .init_resource::<CrosshairVisibility>()
+            .add_systems(Startup, spawn_crosshair_sys)
+            .add_systems(Update, (
+                (resolve_crosshair_visibility_sys, update_crosshair_sys).chain(),
+                (show_hitmarker_sys, fade_hitmarker_sys).chain(),
+            ));
+    }
+}
+
+impl Default for CrosshairSettings {
+    fn default() -> Self {
+        let theme = ColorTheme::default();
+        Self {
+            hide_while_sprinting: true,
+            size: 12.0,
+            thickness: 2.0,
+            color: theme.crosshair,
+            hit_color: theme.hit,
+            kill_color: theme.kill,
+        }
+    }
+}
+
+impl Default for CrosshairVisibility {
+    fn default() -> Self {
+        Self { visible: true }
+    }
+}
+
+/// Single place that decides crosshair visibility from everything that can hide it
+pub fn resolve_crosshair_visibility_sys(
+    state: Res<State<GameState>>,
+    console: Res<Console>,
+    settings: Res<CrosshairSettings>,
+    mut visibility: ResMut<CrosshairVisibility>,
+    player_query: Query<(&PlayerInput, &LogicalPlayer, Option<&ThirdPerson>)>,
+) {
+    let player = player_query.iter().find(|(_, player, _)| player.0 == 0);
+    let is_sprinting = player
+        .is_some_and(|(input, _, _)| input.flags.contains(PlayerInputFlags::Sprint) && input.movement != Vec3::ZERO);
+    // Third person only shows the crosshair while aiming
+    let is_third_person_hip = player.is_some_and(|(input, _, third_person)| {
+        third_person.is_some_and(|third_person| third_person.enabled) && !input.flags.contains(PlayerInputFlags::Aim)
+    });
+    let visible = *state.get() == GameState::Playing && !console.open && !(settings.hide_while_sprinting && is_sprinting) && !is_third_person_hip;
+    // Avoid triggering change detection every frame
+    if visibility.visible != visible {
+        visibility.visible = visible;
+    }
+}
+
+impl CrosshairLine {
+    fn style(&self, settings: &CrosshairSettings) -> Style {
+        // Lines cross at the center of the root node, which is `size` square
+        let offset = Val::Px((settings.size - settings.thickness) * 0.5);
+        let (width, height, left, top) = match self {
+            CrosshairLine::Horizontal => (settings.size, settings.thickness, Val::Px(0.0), offset),
+            CrosshairLine::Vertical => (settings.thickness, settings.size, offset, Val::Px(0.0)),
+        };
+        Style {
+            position_type: PositionType::Absolute,
+            width: Val::Px(width),
+            height: Val::Px(height),
+            left,
+            top,
+            ..default()
+        }
+    }
+}
+
+pub fn spawn_crosshair_sys(mut commands: Commands, settings: Res<CrosshairSettings>) {
+    commands.spawn(NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        ..default()
+    }).with_children(|parent| {
+        parent.spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Px(settings.size),
+                    height: Val::Px(settings.size),
+                    ..default()
+                },
+                ..default()
+            },
+            Crosshair,
+        )).with_children(|parent| {
+            for line in [CrosshairLine::Horizontal, CrosshairLine::Vertical] {
+                parent.spawn((
+                    NodeBundle {
+                        style: line.style(&settings),
+                        background_color: settings.color.into(),
+                        ..default()
+                    },
+                    line,
+                ));
+            }
+        });
+    });
+}
+
+pub fn update_crosshair_sys(
+    settings: Res<CrosshairSettings>,
+    visibility: Res<CrosshairVisibility>,
+    mut crosshair_query: Query<(&mut Style, &mut Visibility), (With<Crosshair>, Without<CrosshairLine>)>,
+    mut line_query: Query<(&CrosshairLine, &mut Style, &mut BackgroundColor), Without<Crosshair>>,
+) {
+    if visibility.is_changed() {
+        for (_, mut crosshair_visibility) in crosshair_query.iter_mut() {
+            *crosshair_visibility = if visibility.visible { Visibility::Inherited } else { Visibility::Hidden };
+        }
+    }
+    if settings.is_changed() {
+        for (mut style, _) in crosshair_query.iter_mut() {
+            style.width = Val::Px(settings.size);
+            style.height = Val::Px(settings.size);
+        }
+        for (line, mut style, mut color) in line_query.iter_mut() {
+            *style = line.style(&settings);
+            *color = settings.color.into();
+        }
+    }
+}
+
+pub fn show_hitmarker_sys(
+    mut commands: Commands,
+    settings: Res<CrosshairSettings>,
+    mut hit_events: EventReader<HitEvent>,
+    player_query: Query<&LogicalPlayer>,
+    crosshair_query: Query<Entity, With<Crosshair>>,
+    hitmarker_query: Query<Entity, With<Hitmarker>>,
+) {
+    // Kills take priority over plain hits in the same frame
+    let mut is_kill = None;
+    for hit in hit_events.read() {
+        let is_local = player_query.get(hit.attacker).is_ok_and(|player| player.0 == 0);
+        if is_local && hit.target != hit.attacker {
+            is_kill = Some(is_kill.unwrap_or(false) || hit.is_kill);
+        }
+    }
+    let Some(is_kill) = is_kill else { return; };
+
+    for hitmarker_ent in hitmarker_query.iter() {
+        commands.entity(hitmarker_ent).despawn_recursive();
+    }
+    let color = if is_kill { settings.kill_color } else { settings.hit_color };
+    for crosshair_ent in crosshair_query.iter() {
+        commands.entity(crosshair_ent).with_children(|parent| {
+            parent.spawn((
+                TextBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        // Roughly centered on the crosshair for the font size below
+                        left: Val::Px(settings.size * 0.5 - 6.0),
+                        top: Val::Px(settings.size * 0.5 - 12.0),
+                        ..default()
+                    },
+                    text: Text::from_section("X", TextStyle { font_size: 20.0, color, ..default() }),
+                    ..default()
+                },
+                Hitmarker { age: Duration::ZERO, color },
+            ));
+        });
+    }
+}
+
+pub fn fade_hitmarker_sys(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut hitmarker_query: Query<(Entity, &mut Hitmarker, &mut Text)>,
+) {
+    for (hitmarker_ent, mut hitmarker, mut text) in hitmarker_query.iter_mut() {
+        hitmarker.age += time.delta();
+        if hitmarker.age >= HITMARKER_DURATION {
+            commands.entity(hitmarker_ent).despawn_recursive();
+            continue;
+        }
+        let fade = 1.0 - hitmarker.age.as_secs_f32() / HITMARKER_DURATION.as_secs_f32();
+        text.sections[0].style.color = hitmarker.color.with_a(hitmarker.color.a() * fade);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn visibility_app() -> (App, Entity) {
+        let mut app = App::new();
+        app
+            .init_resource::<Console>()
+            .init_resource::<CrosshairSettings>()
+            .init_resource::<CrosshairVisibility>()
+            .add_state::<GameState>()
+            .add_systems(Update, resolve_crosshair_visibility_sys);
+        app.world.resource_mut::<NextState<GameState>>().set(GameState::Playing);
+        let player = app.world.spawn((PlayerInput::default(), LogicalPlayer(0))).id();
+        (app, player)
+    }
+
+    fn is_visible(app: &mut App) -> bool {
+        app.update();
+        app.world.resource::<CrosshairVisibility>().visible
+    }
+
+    #[test]
+    fn hidden_while_sprinting_and_moving() {
+        let (mut app, player) = visibility_app();
+        app.world.get_mut::<PlayerInput>(player).unwrap().flags = PlayerInputFlags::Sprint.into();
+        assert!(is_visible(&mut app));
+
+        app.world.get_mut::<PlayerInput>(player).unwrap().movement = Vec3::NEG_Z;
+        assert!(!is_visible(&mut app));
+
+        app.world.resource_mut::<CrosshairSettings>().hide_while_sprinting = false;
+        assert!(is_visible(&mut app));
+    }
+
+    #[test]
+    fn hidden_while_console_open() {
+        let (mut app, _) = visibility_app();
+        app.world.resource_mut::<Console>().open = true;
+        assert!(!is_visible(&mut app));
+        app.world.resource_mut::<Console>().open = false;
+        assert!(is_visible(&mut app));
+    }
+
+    #[test]
+    fn third_person_shows_only_while_aiming() {
+        let (mut app, player) = visibility_app();
+        app.world.entity_mut(player).insert(ThirdPerson { enabled: true, ..default() });
+        assert!(!is_visible(&mut app));
+        app.world.get_mut::<PlayerInput>(player).unwrap().flags = PlayerInputFlags::Aim.into();
+        assert!(is_visible(&mut app));
+    }
+
+    #[test]
+    fn opening_menu_hides_and_closing_restores() {
+        let (mut app, _) = visibility_app();
+        assert!(is_visible(&mut app));
+        app.world.resource_mut::<NextState<GameState>>().set(GameState::Paused);
+        assert!(!is_visible(&mut app));
+        app.world.resource_mut::<NextState<GameState>>().set(GameState::Playing);
+        assert!(is_visible(&mut app));
+    }
+}
@@ -0,0 +1,199 @@
+use std::time::Duration;
+
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+
+use crate::{ColorTheme, Health, HitEvent, LogicalPlayer, RenderPlayer};
+
+const VIGNETTE_RESOLUTION: u32 = 64;
+
+#[derive(Resource)]
+pub struct DamageOverlaySettings {
+    /// Health fraction below which the vignette starts to show
+    pub low_health_fraction: f32,
+    /// Vignette opacity at zero health
+    pub max_vignette_alpha: f32,
+    pub indicator_duration: Duration,
+    /// Distance in pixels from the center of the screen to each indicator
+    pub indicator_radius: f32,
+    /// Width and length in pixels, the length points at the source
+    pub indicator_size: Vec2,
+}
+
+/// Red screen edges for the local player at low health, tinted by [`ColorTheme::damage`]
+#[derive(Component)]
+pub struct LowHealthVignette;
+
+/// Zero-sized node at the center of the screen that indicators are placed around
+#[derive(Component)]
+pub struct DamageIndicatorAnchor;
+
+/// Points from the center of the screen towards where a hit on the local player came from
+#[derive(Component)]
+pub struct DamageIndicator {
+    pub age: Duration,
+    pub source: Vec3,
+}
+
+pub struct DamageOverlayPlugin;
+
+impl Plugin for DamageOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<DamageOverlaySettings>()
+            .add_systems(Startup, spawn_damage_overlay_sys)
+            .add_systems(Update, (
+                low_health_vignette_sys,
+                (spawn_damage_indicator_sys, update_damage_indicator_sys).chain(),
+            ));
+    }
+}
+
+impl Default for DamageOverlaySettings {
+    fn default() -> Self {
+        Self {
+            low_health_fraction: 0.35,
+            max_vignette_alpha: 0.7,
+            indicator_duration: Duration::from_secs(1),
+            indicator_radius: 120.0,
+            indicator_size: Vec2::new(6.0, 28.0),
+        }
+    }
+}
+
+/// White with alpha rising towards the edges, tinted through the background color of the node
+fn vignette_image() -> Image {
+    let resolution = VIGNETTE_RESOLUTION as usize;
+    let mut data = Vec::with_capacity(resolution * resolution * 4);
+    for row in 0..resolution {
+        for col in 0..resolution {
+            let uv = Vec2::new(col as f32, row as f32) / (resolution - 1) as f32 * 2.0 - 1.0;
+            let edge = ((uv.length() - 0.6) / 0.6).clamp(0.0, 1.0);
+            data.extend_from_slice(&[255, 255, 255, (edge * edge * 255.0) as u8]);
+        }
+    }
+    Image::new(
+        Extent3d { width: VIGNETTE_RESOLUTION, height: VIGNETTE_RESOLUTION, depth_or_array_layers: 1 },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+pub fn spawn_damage_overlay_sys(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    commands.spawn(NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        ..default()
+    }).with_children(|parent| {
+        parent.spawn((
+            ImageBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    ..default()
+                },
+                image: UiImage::new(images.add(vignette_image())),
+                background_color: Color::NONE.into(),
+                ..default()
+            },
+            LowHealthVignette,
+        ));
+        parent.spawn((NodeBundle::default(), DamageIndicatorAnchor));
+    });
+}
+
+pub fn low_health_vignette_sys(
+    settings: Res<DamageOverlaySettings>,
+    theme: Res<ColorTheme>,
+    player_query: Query<(&LogicalPlayer, &Health)>,
+    mut vignette_query: Query<&mut BackgroundColor, With<LowHealthVignette>>,
+) {
+    let health_fraction = player_query.iter()
+        .find(|(player, _)| player.0 == 0)
+        .map_or(1.0, |(_, health)| health.current / health.max);
+    let intensity = (1.0 - health_fraction / settings.low_health_fraction).clamp(0.0, 1.0);
+    let color = theme.damage.with_a(intensity * settings.max_vignette_alpha);
+    for mut background in vignette_query.iter_mut() {
+        // Avoid triggering change detection every frame
+        if background.0 != color {
+            background.0 = color;
+        }
+    }
+}
+
+pub fn spawn_damage_indicator_sys(
+    mut commands: Commands,
+    settings: Res<DamageOverlaySettings>,
+    theme: Res<ColorTheme>,
+    mut hit_events: EventReader<HitEvent>,
+    player_query: Query<&LogicalPlayer>,
+    anchor_query: Query<Entity, With<DamageIndicatorAnchor>>,
+) {
+    for hit in hit_events.read() {
+        if !player_query.get(hit.target).is_ok_and(|player| player.0 == 0) { continue; }
+        for anchor_ent in anchor_query.iter() {
+            commands.entity(anchor_ent).with_children(|parent| {
+                parent.spawn((
+                    NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            width: Val::Px(settings.indicator_size.x),
+                            height: Val::Px(settings.indicator_size.y),
+                            ..default()
+                        },
+                        background_color: theme.damage.into(),
+                        // Placed by the update system before it is first drawn
+                        visibility: Visibility::Hidden,
+                        ..default()
+                    },
+                    DamageIndicator { age: Duration::ZERO, source: hit.source },
+                ));
+            });
+        }
+    }
+}
+
+/// Keep each indicator pointing at its source as the camera turns, fading it out over its lifetime
+pub fn update_damage_indicator_sys(
+    time: Res<Time>,
+    mut commands: Commands,
+    settings: Res<DamageOverlaySettings>,
+    theme: Res<ColorTheme>,
+    camera_query: Query<(&RenderPlayer, &GlobalTransform)>,
+    mut indicator_query: Query<(Entity, &mut DamageIndicator, &mut Style, &mut Transform, &mut BackgroundColor, &mut Visibility)>,
+) {
+    let camera = camera_query.iter().find(|(render_player, _)| render_player.0 == 0).map(|(_, transform)| transform);
+    for (indicator_ent, mut indicator, mut style, mut transform, mut background, mut visibility) in indicator_query.iter_mut() {
+        indicator.age += time.delta();
+        if indicator.age >= settings.indicator_duration {
+            commands.entity(indicator_ent).despawn_recursive();
+            continue;
+        }
+        let Some(camera) = camera else { continue; };
+
+        // Angle clockwise from straight ahead on the horizontal plane, straight ahead is the top of the screen
+        let to_source = (indicator.source - camera.translation()).xz();
+        let forward = camera.forward().xz().normalize_or_zero();
+        let right = camera.right().xz().normalize_or_zero();
+        let angle = f32::atan2(to_source.dot(right), to_source.dot(forward));
+        let center = Vec2::new(angle.sin(), -angle.cos()) * settings.indicator_radius;
+        style.left = Val::Px(center.x - settings.indicator_size.x * 0.5);
+        style.top = Val::Px(center.y - settings.indicator_size.y * 0.5);
+        // Layout only writes the translation, so the rotation sticks
+        transform.rotation = Quat::from_rotation_z(angle);
+
+        let fade = 1.0 - indicator.age.as_secs_f32() / settings.indicator_duration.as_secs_f32();
+        background.0 = theme.damage.with_a(fade);
+        *visibility = Visibility::Inherited;
+    }
+}
@@ -0,0 +1,220 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use flagset::FlagSet;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{ConsoleCommandEvent, LogicalPlayer, MoveMode, player_move_sys, PlayerController, PlayerInput};
+
+const DEMOS_DIR: &str = "demos";
+
+/// State of the local player when recording started, playback puts the player back here first
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DemoStart {
+    pub translation: Vec3,
+    pub move_mode: MoveMode,
+    pub velocity: Vec3,
+    pub pitch: f32,
+    pub yaw: f32,
+}
+
+/// Input of the local player during one movement tick
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DemoTick {
+    pub movement: Vec3,
+    pub flags: u32,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub wanted_item_slot: Option<u8>,
+    pub wanted_move_mode: Option<MoveMode>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Demo {
+    pub start: DemoStart,
+    pub ticks: Vec<DemoTick>,
+}
+
+#[derive(Debug, Error)]
+pub enum DemoError {
+    #[error("nothing was recorded")]
+    Empty,
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    RonSpannedError(#[from] ron::error::SpannedError),
+    #[error(transparent)]
+    RonError(#[from] ron::Error),
+}
+
+/// Exists while recording, written out when recording stops
+#[derive(Resource)]
+pub struct DemoRecording {
+    pub path: PathBuf,
+    /// Captured on the first tick
+    start: Option<DemoStart>,
+    ticks: Vec<DemoTick>,
+}
+
+/// Exists while playing back, live input is ignored meanwhile
+#[derive(Resource)]
+pub struct DemoPlayback {
+    demo: Demo,
+    tick: usize,
+}
+
+pub struct DemoPlugin;
+
+impl Plugin for DemoPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(FixedUpdate, (
+                playback_demo_sys.run_if(resource_exists::<DemoPlayback>()),
+                record_demo_sys.run_if(resource_exists::<DemoRecording>()),
+            ).chain().before(player_move_sys))
+            .add_systems(Update, demo_command_sys);
+    }
+}
+
+pub fn demo_path(name: &str) -> PathBuf {
+    Path::new(DEMOS_DIR).join(format!("{}.demo.ron", name))
+}
+
+pub fn is_playing_demo(playback: Option<Res<DemoPlayback>>) -> bool {
+    playback.is_some()
+}
+
+/// Record local player input every movement tick until [`stop_recording`]
+pub fn start_recording(commands: &mut Commands, path: impl Into<PathBuf>) {
+    commands.insert_resource(DemoRecording { path: path.into(), start: None, ticks: Vec::new() });
+}
+
+/// Write out the recording to its path
+pub fn stop_recording(commands: &mut Commands, recording: &DemoRecording) -> Result<(), DemoError> {
+    commands.remove_resource::<DemoRecording>();
+    recording.save()
+}
+
+/// Replace live input with the ticks recorded at `path`
+pub fn play_demo(commands: &mut Commands, path: &Path) -> Result<(), DemoError> {
+    let bytes = fs::read(path)?;
+    let demo: Demo = ron::de::from_bytes(&bytes)?;
+    commands.insert_resource(DemoPlayback { demo, tick: 0 });
+    Ok(())
+}
+
+impl DemoRecording {
+    pub fn save(&self) -> Result<(), DemoError> {
+        let Some(start) = &self.start else { return Err(DemoError::Empty); };
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Serialize through a borrowed view so the recording does not have to be cloned
+        #[derive(Serialize)]
+        struct DemoRef<'a> {
+            start: &'a DemoStart,
+            ticks: &'a [DemoTick],
+        }
+        let text = ron::to_string(&DemoRef { start, ticks: &self.ticks })?;
+        fs::write(&self.path, text)?;
+        Ok(())
+    }
+}
+
+impl From<&PlayerInput> for DemoTick {
+    fn from(input: &PlayerInput) -> Self {
+        Self {
+            movement: input.movement,
+            flags: input.flags.bits(),
+            yaw: input.yaw,
+            pitch: input.pitch,
+            wanted_item_slot: input.wanted_item_slot,
+            wanted_move_mode: input.wanted_move_mode,
+        }
+    }
+}
+
+impl DemoTick {
+    pub fn apply(&self, input: &mut PlayerInput) {
+        input.movement = self.movement;
+        input.flags = FlagSet::new_truncated(self.flags);
+        input.yaw = self.yaw;
+        input.pitch = self.pitch;
+        input.wanted_item_slot = self.wanted_item_slot;
+        input.wanted_move_mode = self.wanted_move_mode;
+    }
+}
+
+pub fn record_demo_sys(
+    mut recording: ResMut<DemoRecording>,
+    player_query: Query<(&LogicalPlayer, &Transform, &PlayerController, &PlayerInput)>,
+) {
+    let Some((_, transform, controller, input)) = player_query.iter().find(|(player, ..)| player.0 == 0) else { return; };
+    if recording.start.is_none() {
+        recording.start = Some(DemoStart {
+            translation: transform.translation,
+            move_mode: controller.move_mode,
+            velocity: controller.velocity,
+            pitch: input.pitch,
+            yaw: input.yaw,
+        });
+    }
+    recording.ticks.push(DemoTick::from(input));
+}
+
+pub fn playback_demo_sys(
+    mut commands: Commands,
+    mut playback: ResMut<DemoPlayback>,
+    mut player_query: Query<(&LogicalPlayer, &mut Transform, &mut PlayerController, &mut PlayerInput)>,
+) {
+    let Some((_, mut transform, mut controller, mut input)) = player_query.iter_mut().find(|(player, ..)| player.0 == 0) else { return; };
+    if playback.tick == 0 {
+        let start = &playback.demo.start;
+        transform.translation = start.translation;
+        controller.move_mode = start.move_mode;
+        controller.velocity = start.velocity;
+        input.pitch = start.pitch;
+        input.yaw = start.yaw;
+    }
+    let Some(tick) = playback.demo.ticks.get(playback.tick) else {
+        info!("Demo finished after {} ticks", playback.tick);
+        *input = PlayerInput { pitch: input.pitch, yaw: input.yaw, ..default() };
+        commands.remove_resource::<DemoPlayback>();
+        return;
+    };
+    tick.apply(&mut input);
+    playback.tick += 1;
+}
+
+pub fn demo_command_sys(
+    mut commands: Commands,
+    mut command_events: EventReader<ConsoleCommandEvent>,
+    recording: Option<Res<DemoRecording>>,
+) {
+    for command in command_events.read() {
+        match command.name.as_str() {
+            "demo_record" => match command.args.first() {
+                Some(name) => start_recording(&mut commands, demo_path(name)),
+                None => warn!("Usage: demo_record <name>"),
+            },
+            "demo_stop" => match &recording {
+                Some(recording) => match stop_recording(&mut commands, recording) {
+                    Ok(()) => info!("Saved demo to {}", recording.path.display()),
+                    Err(err) => error!("Failed to save demo to {}: {}", recording.path.display(), err),
+                },
+                None => warn!("Not recording a demo"),
+            },
+            "demo_play" => match command.args.first() {
+                Some(name) => if let Err(err) = play_demo(&mut commands, &demo_path(name)) {
+                    error!("Failed to play demo {}: {}", name, err);
+                },
+                None => warn!("Usage: demo_play <name>"),
+            },
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,270 @@
+use std::{
+    collections::VecDeque,
+    time::Duration,
+};
+
+use bevy::{
+    pbr::NotShadowCaster,
+    prelude::*,
+    prelude::shape::{Cube, Quad, UVSphere},
+};
+
+use crate::{FIRE_STATE, GunProps, Inventory, Item, ItemStateEvent, LogicalPlayer, ShotEvent};
+
+const MUZZLE_FLASH_DURATION: Duration = Duration::from_millis(50);
+/// Keeps decals from fighting with the surface they are on
+const DECAL_SURFACE_OFFSET: f32 = 0.01;
+
+#[derive(Resource)]
+pub struct EffectSettings {
+    pub tracer_duration: Duration,
+    pub tracer_width: f32,
+    pub decal_size: f32,
+    pub decal_lifetime: Duration,
+    /// Oldest decals are removed first once there are this many
+    pub max_decals: usize,
+}
+
+/// Meshes and materials shared by every effect instance
+#[derive(Resource)]
+pub struct EffectAssets {
+    pub flash_mesh: Handle<Mesh>,
+    /// Unit cube, stretched along Z between the ends of a tracer
+    pub tracer_mesh: Handle<Mesh>,
+    /// Unit quad facing Z
+    pub decal_mesh: Handle<Mesh>,
+    pub decal_material: Handle<StandardMaterial>,
+}
+
+/// Live decals from oldest to newest
+#[derive(Resource, Default)]
+pub struct Decals(VecDeque<Entity>);
+
+/// Light and glow at the muzzle of a view model, despawned after [`MUZZLE_FLASH_DURATION`]
+#[derive(Component, Default)]
+pub struct MuzzleFlash {
+    pub age: Duration,
+}
+
+/// Streak along the path of a hitscan shot, fading out over [`EffectSettings::tracer_duration`]
+#[derive(Component, Default)]
+pub struct Tracer {
+    pub age: Duration,
+}
+
+/// Mark where a shot hit a surface
+#[derive(Component, Default)]
+pub struct Decal {
+    pub age: Duration,
+}
+
+pub struct EffectsPlugin;
+
+impl Plugin for EffectsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<EffectSettings>()
+            .init_resource::<EffectAssets>()
+            .init_resource::<Decals>()
+            .add_systems(Update, (
+                fade_muzzle_flash_sys,
+                (spawn_shot_effects_sys, fade_tracer_sys, expire_decal_sys).chain(),
+            ));
+    }
+}
+
+impl Default for EffectSettings {
+    fn default() -> Self {
+        Self {
+            tracer_duration: Duration::from_millis(60),
+            tracer_width: 0.02,
+            decal_size: 0.15,
+            decal_lifetime: Duration::from_secs(10),
+            max_decals: 64,
+        }
+    }
+}
+
+impl FromWorld for EffectAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes = world.resource_mut::<Assets<Mesh>>();
+        let flash_mesh = meshes.add(Mesh::from(UVSphere { radius: 0.05, sectors: 8, stacks: 4 }));
+        let tracer_mesh = meshes.add(Mesh::from(Cube { size: 1.0 }));
+        let decal_mesh = meshes.add(Mesh::from(Quad::new(Vec2::ONE)));
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+        let decal_material = materials.add(StandardMaterial {
+            base_color: Color::rgba(0.05, 0.05, 0.05, 0.9),
+            alpha_mode: AlphaMode::Blend,
+            ..default()
+        });
+        Self { flash_mesh, tracer_mesh, decal_mesh, decal_material }
+    }
+}
+
+/// Flash at the muzzle of equipped guns as they start firing, as a child of the view model so it follows it
+pub fn muzzle_flash_sys(
+    mut commands: Commands,
+    gun_props: Res<Assets<GunProps>>,
+    effect_assets: Res<EffectAssets>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut state_events: EventReader<ItemStateEvent>,
+    item_query: Query<&Item>,
+    inv_query: Query<&Inventory>,
+) {
+    for event in state_events.read() {
+        if event.state_name != FIRE_STATE { continue; }
+        let Ok(item) = item_query.get(event.item_ent) else { continue; };
+        let Ok(inv) = inv_query.get(event.inv_ent) else { continue; };
+        if inv.equipped_slot != Some(item.inv_slot) { continue; }
+        let Some(flash) = gun_props.get(&item.props).and_then(|props| props.muzzle_flash.as_ref()) else { continue; };
+
+        commands.entity(event.item_ent).with_children(|parent| {
+            parent.spawn((
+                PointLightBundle {
+                    point_light: PointLight {
+                        color: flash.color,
+                        intensity: flash.intensity,
+                        range: flash.range,
+                        shadows_enabled: false,
+                        ..default()
+                    },
+                    transform: Transform::from_translation(flash.offset),
+                    ..default()
+                },
+                MuzzleFlash::default(),
+            )).with_children(|parent| {
+                parent.spawn((
+                    PbrBundle {
+                        mesh: effect_assets.flash_mesh.clone(),
+                        material: materials.add(StandardMaterial {
+                            base_color: flash.color,
+                            unlit: true,
+                            alpha_mode: AlphaMode::Add,
+                            ..default()
+                        }),
+                        ..default()
+                    },
+                    NotShadowCaster,
+                ));
+            });
+        });
+    }
+}
+
+pub fn fade_muzzle_flash_sys(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut flash_query: Query<(Entity, &mut MuzzleFlash)>,
+) {
+    for (flash_ent, mut flash) in flash_query.iter_mut() {
+        flash.age += time.delta();
+        if flash.age >= MUZZLE_FLASH_DURATION {
+            commands.entity(flash_ent).despawn_recursive();
+        }
+    }
+}
+
+/// Where the shots of the local player appear to leave from, the muzzle of the view model when it has one
+fn local_muzzle(
+    shooter: Entity,
+    gun_props: &Assets<GunProps>,
+    player_query: &Query<(&LogicalPlayer, &Inventory)>,
+    item_query: &Query<(&Item, &GlobalTransform)>,
+) -> Option<Vec3> {
+    let (player, inv) = player_query.get(shooter).ok()?;
+    if player.0 != 0 { return None; }
+    let (item, item_transform) = item_query.get(inv.equipped_item_ent()?).ok()?;
+    let flash = gun_props.get(&item.props)?.muzzle_flash.as_ref()?;
+    Some(item_transform.transform_point(flash.offset))
+}
+
+pub fn spawn_shot_effects_sys(
+    mut commands: Commands,
+    settings: Res<EffectSettings>,
+    effect_assets: Res<EffectAssets>,
+    gun_props: Res<Assets<GunProps>>,
+    mut decals: ResMut<Decals>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut shot_events: EventReader<ShotEvent>,
+    player_query: Query<(&LogicalPlayer, &Inventory)>,
+    item_query: Query<(&Item, &GlobalTransform)>,
+) {
+    for shot in shot_events.read() {
+        let origin = local_muzzle(shot.shooter, &gun_props, &player_query, &item_query).unwrap_or(shot.origin);
+        let path = shot.end - origin;
+        if let Some(dir) = path.try_normalize() {
+            commands.spawn((
+                PbrBundle {
+                    mesh: effect_assets.tracer_mesh.clone(),
+                    // Each tracer fades on its own
+                    material: materials.add(StandardMaterial {
+                        base_color: Color::rgba(1.0, 0.9, 0.6, 1.0),
+                        unlit: true,
+                        alpha_mode: AlphaMode::Add,
+                        ..default()
+                    }),
+                    transform: Transform::from_translation(origin + path * 0.5)
+                        .with_rotation(Quat::from_rotation_arc(Vec3::Z, dir))
+                        .with_scale(Vec3::new(settings.tracer_width, settings.tracer_width, path.length())),
+                    ..default()
+                },
+                NotShadowCaster,
+                Tracer::default(),
+            ));
+        }
+
+        let Some(normal) = shot.normal else { continue; };
+        let decal_ent = commands.spawn((
+            PbrBundle {
+                mesh: effect_assets.decal_mesh.clone(),
+                material: effect_assets.decal_material.clone(),
+                transform: Transform::from_translation(shot.end + normal * DECAL_SURFACE_OFFSET)
+                    .with_rotation(Quat::from_rotation_arc(Vec3::Z, normal))
+                    .with_scale(Vec3::splat(settings.decal_size)),
+                ..default()
+            },
+            NotShadowCaster,
+            Decal::default(),
+        )).id();
+        decals.0.push_back(decal_ent);
+        while decals.0.len() > settings.max_decals {
+            let Some(oldest) = decals.0.pop_front() else { break; };
+            commands.entity(oldest).despawn_recursive();
+        }
+    }
+}
+
+pub fn fade_tracer_sys(
+    time: Res<Time>,
+    mut commands: Commands,
+    settings: Res<EffectSettings>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut tracer_query: Query<(Entity, &mut Tracer, &Handle<StandardMaterial>)>,
+) {
+    for (tracer_ent, mut tracer, material) in tracer_query.iter_mut() {
+        tracer.age += time.delta();
+        if tracer.age >= settings.tracer_duration {
+            commands.entity(tracer_ent).despawn_recursive();
+            continue;
+        }
+        let Some(material) = materials.get_mut(material) else { continue; };
+        let fade = 1.0 - tracer.age.as_secs_f32() / settings.tracer_duration.as_secs_f32();
+        material.base_color.set_a(fade);
+    }
+}
+
+pub fn expire_decal_sys(
+    time: Res<Time>,
+    mut commands: Commands,
+    settings: Res<EffectSettings>,
+    mut decals: ResMut<Decals>,
+    mut decal_query: Query<(Entity, &mut Decal)>,
+) {
+    for (decal_ent, mut decal) in decal_query.iter_mut() {
+        decal.age += time.delta();
+        if decal.age >= settings.decal_lifetime {
+            decals.0.retain(|&live_ent| live_ent != decal_ent);
+            commands.entity(decal_ent).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,418 @@
+use std::fmt::Write;
+
+use bevy::{
+    diagnostic::DiagnosticsStore,
+    diagnostic::FrameTimeDiagnosticsPlugin,
+    prelude::*,
+    prelude::shape::Cube,
+};
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    apply_input_accumulator_sys, apply_movement_tuning_sys, BiomePlugin, CameraFeel, ColorTheme, CombatPlugin, Config,
+    config_fallback_sys, ConfigAssetLoader, ConfigState, ConsoleCommandEvent, ConsolePlugin, CrosshairPlugin,
+    cursor_grab_sys, DamageOverlayPlugin, DemoPlugin, drop_item_sys, EffectsPlugin, fire_sys, FootstepEvent,
+    GameState, GameTimePlugin, GraphicsPlugin, grapple_sys, GrapplePlugin, Gun, has_local_player, HazardPlugin,
+    Headless, Health, init_gun_sys, InputSource, interact_prompt_sys, InteractPlugin, InteractPromptText,
+    Inventory, InventoryPlugin, is_authoritative, is_playing_demo, Item, item_animation_sys, item_pickup_animate_sys,
+    item_pickup_sys, item_sound_sys, ItemName, ItemPickup, ItemPickupVisual, knockback_sys, LocalPlayers, LogicalPlayer,
+    Map, MenuPlugin, MinimapPlugin, modify_equip_state_sys, modify_item_sys, MOVE_TICK_RATE, MovementTuning,
+    MovementTuningAssetLoader, MovementTuningState, muzzle_flash_sys, NetMode, NetPlugin, pickup_interactable,
+    playback_demo_sys, player_collision_groups_sys, player_input_system, player_look_sys, player_move_sys, PlayerInput, projectile_sys,
+    render_inventory_sys, render_player_camera_sys, RenderPlayer, reset_config_command_sys, RespawnPlugin, Shield,
+    shield_regen_sys, SkyPlugin, SoundPlugin, spawn_chunk, spawn_logical_player, SpawnPoint,
+    SplitScreenPlugin, Stamina, StructurePlugin, Sun, sync_player_hitboxes_sys, terrain_material, TerrainMaterial, ThemedText, ThemePlugin,
+    ThemeRole, third_person_toggle_sys, TriplanarPlugin, USER_CONFIG_PATH, VoxelsPlugin, WaypointPlugin, WorldPlugin
+};
+
+#[derive(Component)]
+pub struct TopRightText;
+
+#[derive(Component)]
+pub struct PlayerHudText;
+
+/// Show raw player state under the HUD, toggled with the `debug_hud` console command
+#[derive(Resource, Default)]
+pub struct DebugHud {
+    pub enabled: bool,
+}
+
+/// Player systems that simulate, and those that draw the local player
+#[derive(Clone, Hash, Debug, PartialEq, Eq, SystemSet)]
+pub enum PlayerSet {
+    Logic,
+    Render,
+}
+
+/// Everything the game needs on top of `DefaultPlugins`, or [`HeadlessPlugin`] when `headless` is set.
+/// Rendering wireframes additionally needs the `WireframePlugin` and the `POLYGON_MODE_LINE` GPU feature.
+#[derive(Default)]
+pub struct QGamePlugin {
+    /// Leaves out rendering, audio, input and UI
+    pub headless: bool,
+}
+
+impl Plugin for QGamePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(Time::<Fixed>::from_hz(MOVE_TICK_RATE))
+            .insert_resource(RapierConfiguration {
+                // Physics steps once per movement tick, in the fixed schedule after movement, so results do not depend on frame rate
+                timestep_mode: TimestepMode::Fixed {
+                    dt: (1.0 / MOVE_TICK_RATE) as f32,
+                    substeps: 1,
+                },
+                ..default()
+            });
+
+        if !self.headless {
+            if !app.is_plugin_added::<FrameTimeDiagnosticsPlugin>() {
+                app.add_plugins(FrameTimeDiagnosticsPlugin::default());
+            }
+            app
+                .add_plugins((
+                    VoxelsPlugin,
+                    ConsolePlugin,
+                    SoundPlugin,
+                    WaypointPlugin,
+                    CrosshairPlugin,
+                    SkyPlugin,
+                    MinimapPlugin,
+                    MenuPlugin,
+                    TriplanarPlugin,
+                    GraphicsPlugin,
+                    ThemePlugin,
+                    DamageOverlayPlugin,
+                    EffectsPlugin,
+                ))
+                .add_systems(Startup, spawn_ui_sys)
+                .add_systems(PreUpdate, player_input_system
+                    .after(config_fallback_sys)
+                    .run_if(has_local_player)
+                    .run_if(not(is_playing_demo))
+                    .run_if(in_state(GameState::Playing)))
+                .add_systems(Update, (
+                    (cursor_grab_sys, update_fps_text_sys, toggle_debug_hud_sys, third_person_toggle_sys),
+                    (item_pickup_animate_sys, render_player_camera_sys, render_inventory_sys, item_sound_sys, item_animation_sys, muzzle_flash_sys, update_hud_system, interact_prompt_sys).chain().in_set(PlayerSet::Render),
+                ));
+        }
+
+        app
+            .add_plugins((
+                RapierPhysicsPlugin::<NoUserData>::default().in_fixed_schedule(),
+                (
+                    CombatPlugin,
+                    InventoryPlugin,
+                    GameTimePlugin,
+                    StructurePlugin,
+                    WorldPlugin,
+                    NetPlugin,
+                    DemoPlugin,
+                    RespawnPlugin,
+                    BiomePlugin,
+                    GrapplePlugin,
+                    HazardPlugin,
+                    InteractPlugin,
+                    SplitScreenPlugin,
+                ),
+            ))
+            .register_asset_loader(ConfigAssetLoader)
+            .init_asset::<Config>()
+            .register_asset_loader(MovementTuningAssetLoader)
+            .init_asset::<MovementTuning>()
+            .add_event::<FootstepEvent>()
+            .init_resource::<DebugHud>()
+            .init_resource::<CameraFeel>()
+            .configure_sets(Update, (
+                PlayerSet::Logic.run_if(in_state(GameState::Playing)),
+                PlayerSet::Render.run_if(has_local_player),
+            ))
+            .add_systems(Startup, setup_sys)
+            .add_systems(OnExit(GameState::MainMenu), (spawn_voxel_sys, spawn_player_sys))
+            .add_systems(PreUpdate, config_fallback_sys)
+            .add_systems(FixedUpdate, (
+                apply_input_accumulator_sys.before(playback_demo_sys).before(grapple_sys).before(player_move_sys),
+                player_move_sys.before(PhysicsSet::SyncBackend).run_if(is_authoritative).run_if(in_state(GameState::Playing)),
+            ))
+            .add_systems(Update, (
+                (reset_config_command_sys, (apply_movement_tuning_sys, player_collision_groups_sys).chain()),
+                (
+                    player_look_sys,
+                    // Clients only look around locally, the server simulates everything else
+                    (init_gun_sys, modify_equip_state_sys, modify_item_sys, sync_player_hitboxes_sys, fire_sys, projectile_sys, knockback_sys, shield_regen_sys, drop_item_sys, item_pickup_sys).chain().run_if(is_authoritative),
+                ).chain().in_set(PlayerSet::Logic),
+            ));
+    }
+}
+
+pub fn setup_sys(
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut configs: ResMut<Assets<Config>>,
+) {
+    // println!("{}", toml::to_string(&Config::default()).unwrap());
+
+    // Prefer the user's saved config, the shipped one stays untouched in assets
+    let user_config = std::fs::read_to_string(USER_CONFIG_PATH).ok().and_then(|text| {
+        ron::from_str::<Config>(&text)
+            .map_err(|err| warn!("Failed to parse {}, using shipped config: {}", USER_CONFIG_PATH, err))
+            .ok()
+    });
+    let config: Handle<Config> = match user_config {
+        Some(config) => configs.add(config),
+        None => asset_server.load("default.config.ron"),
+    };
+    commands.insert_resource(ConfigState { handle: config, user_path: Some(USER_CONFIG_PATH.into()) });
+    commands.insert_resource(MovementTuningState { handle: asset_server.load("default.movement.ron") });
+
+    commands.spawn((
+        DirectionalLightBundle {
+            directional_light: DirectionalLight {
+                illuminance: 2000.0,
+                shadows_enabled: true,
+                ..default()
+            },
+            transform: Transform::from_xyz(-38.0, 40.0, 34.0),
+            ..default()
+        },
+        Sun,
+    ));
+
+    {
+        let mesh = meshes.add(Mesh::from(Cube { size: 1.0 }));
+        let material = materials.add(StandardMaterial {
+            base_color: Color::PINK,
+            ..default()
+        });
+        commands.spawn((
+            PbrBundle {
+                mesh: mesh.clone(),
+                material: material.clone(),
+                // transform: Transform::from_xyz(-18.0, 32.0, -18.0),
+                transform: Transform::from_xyz(0.0, 0.0, 0.0),
+                ..default()
+            },
+            Collider::cuboid(0.5, 0.5, 0.5),
+        ));
+    }
+
+    for spawn_point in [Vec3::new(4.0, 18.0, 4.0), Vec3::new(-12.0, 18.0, 20.0), Vec3::new(20.0, 18.0, -12.0)] {
+        commands.spawn((TransformBundle::from(Transform::from_translation(spawn_point)), SpawnPoint));
+    }
+
+    commands.spawn(
+        (
+            Transform::from_xyz(8.0, 16.0, 8.0),
+            GlobalTransform::default(),
+            Collider::ball(0.5),
+            Sensor,
+            VisibilityBundle::default(),
+            ItemPickup { item_name: ItemName::from("rifle"), amount: 1, gun: None },
+            pickup_interactable(&ItemName::from("rifle")),
+        )
+    ).with_children(|parent| {
+        parent.spawn((
+            SceneBundle {
+                scene: asset_server.load("models/rifle.glb#Scene0"),
+                ..default()
+            },
+            ItemPickupVisual::default(),
+        ));
+    });
+}
+
+pub fn spawn_ui_sys(mut commands: Commands, theme: Res<ColorTheme>) {
+    commands.spawn((
+        TextBundle {
+            style: Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+                top: Val::Px(5.0),
+                left: Val::Px(5.0),
+                ..default()
+            },
+            text: Text {
+                sections: vec![
+                    TextSection {
+                        value: "".to_string(),
+                        style: TextStyle { font_size: 16.0, color: theme.hud_text, ..default() },
+                    },
+                ],
+                ..default()
+            },
+            ..default()
+        },
+        TopRightText,
+        ThemedText(vec![ThemeRole::HudText]),
+    ));
+
+    commands.spawn((
+        TextBundle {
+            style: Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(5.0),
+                left: Val::Px(5.0),
+                ..default()
+            },
+            text: Text {
+                sections: vec![
+                    TextSection {
+                        value: "".to_string(),
+                        style: TextStyle { font_size: 24.0, color: theme.hud_text, ..default() },
+                    },
+                    TextSection {
+                        value: "".to_string(),
+                        style: TextStyle { font_size: 12.0, color: theme.hud_secondary_text, ..default() },
+                    },
+                ],
+                ..default()
+            },
+            ..default()
+        },
+        PlayerHudText,
+        ThemedText(vec![ThemeRole::HudText, ThemeRole::HudSecondaryText]),
+    ));
+
+    // Just below the crosshair
+    commands.spawn(NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            top: Val::Percent(55.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        ..default()
+    }).with_children(|parent| {
+        parent.spawn((
+            TextBundle::from_section("", TextStyle { font_size: 20.0, color: theme.hud_text, ..default() }),
+            InteractPromptText,
+            ThemedText(vec![ThemeRole::HudText]),
+        ));
+    });
+}
+
+pub fn spawn_voxel_sys(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<TerrainMaterial>>,
+) {
+    // Color comes from the terrain color ramp vertex colors
+    let ground_mat_handle = materials.add(terrain_material(StandardMaterial {
+        base_color: Color::WHITE,
+        ..default()
+    }));
+    commands.spawn(Map::default());
+    spawn_chunk(&mut commands, &mut meshes, ground_mat_handle, IVec3::ZERO);
+}
+
+pub fn spawn_player_sys(
+    mut commands: Commands,
+    net_mode: Res<NetMode>,
+    local_players: Res<LocalPlayers>,
+    headless: Option<Res<Headless>>,
+) {
+    // Clients only control the one player the server gives them
+    let player_count = if *net_mode == NetMode::Singleplayer { local_players.count } else { 1 };
+    for player_id in 0..player_count {
+        // Dedicated servers only simulate the players of connected clients
+        if *net_mode != NetMode::Server {
+            let transform = Transform::from_xyz(4.0 + 2.0 * player_id as f32, 18.0, 4.0);
+            let player_ent = spawn_logical_player(&mut commands, player_id, transform);
+            commands.entity(player_ent).insert(InputSource::for_player(player_id));
+        }
+
+        if headless.is_some() { continue; }
+        let camera_ent = commands.spawn((
+            Camera3dBundle {
+                camera: Camera { order: player_id as isize, ..default() },
+                ..default()
+            },
+            RenderPlayer(player_id),
+        )).id();
+        if player_id == 0 {
+            commands.entity(camera_ent).insert(SpatialListener::new(0.3));
+        } else {
+            // The HUD only follows the first player, so it is drawn once in their view
+            commands.entity(camera_ent).insert(UiCameraConfig { show_ui: false });
+        }
+    }
+}
+
+pub fn update_fps_text_sys(
+    diagnostics: Res<DiagnosticsStore>,
+    mut query: Query<&mut Text, With<TopRightText>>,
+) {
+    for mut text in query.iter_mut() {
+        let mut fps = 0.0;
+        if let Some(fps_diagnostic) = diagnostics.get(FrameTimeDiagnosticsPlugin::FPS) {
+            if let Some(fps_avg) = fps_diagnostic.average() {
+                fps = fps_avg;
+            }
+        }
+
+        let mut frame_time = 0.0f64;
+        if let Some(frame_time_diagnostic) = diagnostics.get(FrameTimeDiagnosticsPlugin::FRAME_TIME) {
+            if let Some(frame_time_avg) = frame_time_diagnostic.average() {
+                frame_time = frame_time_avg;
+            }
+        }
+
+        let text = &mut text.sections[0].value;
+        text.clear();
+        write!(text, "{:.1} fps, {:.3} ms/frame", fps, frame_time).unwrap();
+    }
+}
+
+pub fn toggle_debug_hud_sys(
+    mut command_events: EventReader<ConsoleCommandEvent>,
+    mut debug_hud: ResMut<DebugHud>,
+) {
+    for command in command_events.read() {
+        if command.name == "debug_hud" {
+            debug_hud.enabled = !debug_hud.enabled;
+        }
+    }
+}
+
+pub fn update_hud_system(
+    debug_hud: Res<DebugHud>,
+    mut text_query: Query<&mut Text, With<PlayerHudText>>,
+    camera_query: Query<&Transform, With<Projection>>,
+    item_query: Query<(&Item, Option<&Gun>)>,
+    player_query: Query<(&Inventory, &PlayerInput, &Health, Option<&Shield>, Option<&Stamina>, &LogicalPlayer)>,
+) {
+    let Some((inv, input, health, shield, stamina, _)) = player_query.iter().find(|(.., player)| player.0 == 0) else { return; };
+    for mut text in text_query.iter_mut() {
+        let hud = &mut text.sections[0].value;
+        hud.clear();
+        if let Some((_, Some(gun))) = inv.equipped_item_ent().and_then(|item_ent| item_query.get(item_ent).ok()) {
+            writeln!(hud, "Ammo {} / {}", gun.ammo, gun.ammo_in_reserve).unwrap();
+        }
+        if let Some(shield) = shield {
+            writeln!(hud, "Shield {:.0} / {:.0}", shield.current, shield.max).unwrap();
+        }
+        if let Some(stamina) = stamina {
+            writeln!(hud, "Stamina {:.0} / {:.0}", stamina.current, stamina.max).unwrap();
+        }
+        write!(hud, "Health {:.0} / {:.0}", health.current, health.max).unwrap();
+
+        let debug = &mut text.sections[1].value;
+        debug.clear();
+        if !debug_hud.enabled { continue; }
+        for transform in camera_query.iter() {
+            let p = transform.translation;
+            write!(debug, "\nPosition {{ {:.2}, {:.2}, {:.2} }}", p.x, p.y, p.z).unwrap();
+        }
+        write!(debug, "\n{:?}", input).unwrap();
+        write!(debug, "\n{:?}", inv).unwrap();
+        for &item_ent in inv.item_ents.0.iter().flatten() {
+            if let Ok((item, _)) = item_query.get(item_ent) {
+                write!(debug, "\n{:?}", item).unwrap();
+            }
+        }
+    }
+}
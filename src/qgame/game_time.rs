@@ -0,0 +1,152 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{ConsoleCommandEvent, MOVE_TICK_RATE};
+
+/// Scaled clock for gameplay systems, rendering and UI keep using [`Time`]
+#[derive(Resource)]
+pub struct GameTime {
+    pub scale: f32,
+    /// Time stands still regardless of scale
+    pub paused: bool,
+    delta: Duration,
+    elapsed: Duration,
+}
+
+pub struct GameTimePlugin;
+
+impl Plugin for GameTimePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<GameTime>()
+            .add_systems(First, update_game_time_sys)
+            .add_systems(Update, time_scale_command_sys);
+    }
+}
+
+impl Default for GameTime {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            paused: false,
+            delta: Duration::ZERO,
+            elapsed: Duration::ZERO,
+        }
+    }
+}
+
+impl GameTime {
+    #[inline]
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    #[inline]
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta.as_secs_f32()
+    }
+
+    #[inline]
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Advance by a real time step
+    pub fn advance(&mut self, real_delta: Duration) {
+        let scale = if self.paused { 0.0 } else { self.scale };
+        self.delta = real_delta.mul_f32(scale);
+        self.elapsed += self.delta;
+    }
+}
+
+pub fn update_game_time_sys(time: Res<Time>, mut game_time: ResMut<GameTime>) {
+    game_time.advance(time.delta());
+}
+
+pub fn time_scale_command_sys(
+    mut command_events: EventReader<ConsoleCommandEvent>,
+    mut game_time: ResMut<GameTime>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+) {
+    for command in command_events.read() {
+        if command.name != "timescale" { continue; }
+
+        match command.arg::<f32>(0) {
+            Some(scale) if scale >= 0.0 => {
+                game_time.scale = scale;
+                // Physics needs to slow down with gameplay, otherwise projectiles would be unaffected
+                match &mut rapier_config.timestep_mode {
+                    TimestepMode::Variable { time_scale, .. } | TimestepMode::Interpolated { time_scale, .. } => *time_scale = scale,
+                    TimestepMode::Fixed { dt, .. } => *dt = scale / MOVE_TICK_RATE as f32,
+                }
+            }
+            _ => warn!("Usage: timescale <non-negative scale>"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn advance_scales_real_delta() {
+        let mut game_time = GameTime { scale: 0.5, ..default() };
+        game_time.advance(Duration::from_millis(100));
+        game_time.advance(Duration::from_millis(100));
+        assert_eq!(game_time.delta(), Duration::from_millis(50));
+        assert_eq!(game_time.elapsed(), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn paused_stands_still() {
+        let mut game_time = GameTime { scale: 2.0, paused: true, ..default() };
+        game_time.advance(Duration::from_millis(100));
+        assert_eq!(game_time.delta(), Duration::ZERO);
+        assert_eq!(game_time.elapsed(), Duration::ZERO);
+    }
+
+    #[test]
+    fn timescale_command_sets_scale() {
+        let mut app = App::new();
+        app
+            .add_event::<ConsoleCommandEvent>()
+            .init_resource::<GameTime>()
+            .insert_resource(RapierConfiguration {
+                timestep_mode: TimestepMode::Variable { max_dt: 1.0 / 60.0, time_scale: 1.0, substeps: 1 },
+                ..default()
+            })
+            .add_systems(Update, time_scale_command_sys);
+
+        app.world.send_event(ConsoleCommandEvent::parse("timescale 0.25").unwrap());
+        app.update();
+        assert_eq!(app.world.resource::<GameTime>().scale, 0.25);
+        let TimestepMode::Variable { time_scale, .. } = app.world.resource::<RapierConfiguration>().timestep_mode else { panic!() };
+        assert_eq!(time_scale, 0.25);
+
+        // Negative scales are rejected
+        app.world.send_event(ConsoleCommandEvent::parse("timescale -1").unwrap());
+        app.update();
+        assert_eq!(app.world.resource::<GameTime>().scale, 0.25);
+    }
+
+    #[test]
+    fn timescale_command_scales_fixed_step() {
+        let mut app = App::new();
+        app
+            .add_event::<ConsoleCommandEvent>()
+            .init_resource::<GameTime>()
+            .insert_resource(RapierConfiguration {
+                timestep_mode: TimestepMode::Fixed { dt: 1.0 / MOVE_TICK_RATE as f32, substeps: 1 },
+                ..default()
+            })
+            .add_systems(Update, time_scale_command_sys);
+
+        app.world.send_event(ConsoleCommandEvent::parse("timescale 0.5").unwrap());
+        app.update();
+        let TimestepMode::Fixed { dt, .. } = app.world.resource::<RapierConfiguration>().timestep_mode else { panic!() };
+        assert_eq!(dt, 0.5 / MOVE_TICK_RATE as f32);
+    }
+}
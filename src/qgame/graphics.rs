@@ -0,0 +1,161 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use bevy::{
+    core_pipeline::clear_color::ClearColorConfig,
+    pbr::DirectionalLightShadowMap,
+    prelude::*,
+    render::{
+        camera::RenderTarget,
+        render_resource::{Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages},
+    },
+    window::{PrimaryWindow, WindowRef},
+};
+
+use crate::{Config, ConfigState, RenderPlayer};
+
+/// The player view is rendered to an image at a fraction of the window resolution, then stretched over the window
+#[derive(Resource)]
+pub struct ScaledRender {
+    image: Handle<Image>,
+    /// Draws the image and the UI to the window
+    window_camera: Entity,
+    node: Entity,
+}
+
+pub struct GraphicsPlugin;
+
+impl Plugin for GraphicsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Update, (present_mode_sys, graphics_settings_sys))
+            // Last so the sleep covers everything else the frame did
+            .add_systems(Last, frame_limiter_sys);
+    }
+}
+
+pub fn present_mode_sys(
+    configs: Res<Assets<Config>>,
+    config_state: Option<Res<ConfigState>>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let Some(config) = config_state.and_then(|state| configs.get(&state.handle)) else { return; };
+    for mut window in window_query.iter_mut() {
+        if window.present_mode != config.present_mode {
+            window.present_mode = config.present_mode;
+        }
+    }
+}
+
+/// Sleep away what is left of the frame when the config caps the frame rate
+pub fn frame_limiter_sys(
+    configs: Res<Assets<Config>>,
+    config_state: Option<Res<ConfigState>>,
+    mut last_frame_end: Local<Option<Instant>>,
+) {
+    let fps_cap = config_state
+        .and_then(|state| configs.get(&state.handle))
+        .and_then(|config| config.fps_cap)
+        .filter(|&fps_cap| fps_cap > 0);
+    if let (Some(fps_cap), Some(last_frame_end)) = (fps_cap, *last_frame_end) {
+        let frame_time = Duration::from_secs_f64(1.0 / fps_cap as f64);
+        let elapsed = last_frame_end.elapsed();
+        if elapsed < frame_time {
+            thread::sleep(frame_time - elapsed);
+        }
+    }
+    *last_frame_end = Some(Instant::now());
+}
+
+/// Apply shadow and render resolution settings from the config to the lights and the player camera
+pub fn graphics_settings_sys(
+    mut commands: Commands,
+    configs: Res<Assets<Config>>,
+    config_state: Option<Res<ConfigState>>,
+    scaled_render: Option<Res<ScaledRender>>,
+    mut shadow_map: ResMut<DirectionalLightShadowMap>,
+    mut images: ResMut<Assets<Image>>,
+    mut light_query: Query<&mut DirectionalLight>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut camera_query: Query<(Entity, &mut Camera), With<RenderPlayer>>,
+) {
+    let Some(config) = config_state.and_then(|state| configs.get(&state.handle)) else { return; };
+    for mut light in light_query.iter_mut() {
+        if light.shadows_enabled != config.shadows {
+            light.shadows_enabled = config.shadows;
+        }
+    }
+    let shadow_map_size = config.shadow_map_size.clamp(256, 8192);
+    if shadow_map.size != shadow_map_size {
+        shadow_map.size = shadow_map_size;
+    }
+
+    let Ok(window) = window_query.get_single() else { return; };
+    let is_split_screen = camera_query.iter().count() > 1;
+    let Some((camera_ent, mut camera)) = camera_query.iter_mut().next() else { return; };
+    // Split-screen viewports render straight to the window
+    let render_scale = if is_split_screen { 1.0 } else { config.render_scale.clamp(0.25, 1.0) };
+    if render_scale >= 1.0 {
+        if let Some(scaled_render) = scaled_render {
+            camera.target = RenderTarget::Window(WindowRef::Primary);
+            commands.entity(camera_ent).remove::<UiCameraConfig>();
+            commands.entity(scaled_render.window_camera).despawn_recursive();
+            commands.entity(scaled_render.node).despawn_recursive();
+            commands.remove_resource::<ScaledRender>();
+        }
+        return;
+    }
+
+    let size = Extent3d {
+        width: u32::max((window.physical_width() as f32 * render_scale) as u32, 1),
+        height: u32::max((window.physical_height() as f32 * render_scale) as u32, 1),
+        depth_or_array_layers: 1,
+    };
+    if let Some(scaled_render) = scaled_render {
+        if let Some(image) = images.get_mut(&scaled_render.image) {
+            if image.texture_descriptor.size != size {
+                image.resize(size);
+            }
+        }
+        return;
+    }
+
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: Some("scaled render image"),
+            size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Bgra8UnormSrgb,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST | TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        },
+        ..default()
+    };
+    image.resize(size);
+    let image = images.add(image);
+    camera.target = RenderTarget::Image(image.clone());
+    // UI stays at full resolution, drawn by the window camera instead
+    commands.entity(camera_ent).insert(UiCameraConfig { show_ui: false });
+    let window_camera = commands.spawn(Camera2dBundle {
+        camera: Camera { order: 1, ..default() },
+        camera_2d: Camera2d { clear_color: ClearColorConfig::None },
+        ..default()
+    }).id();
+    let node = commands.spawn(ImageBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            ..default()
+        },
+        image: UiImage::new(image.clone()),
+        // Behind every other node
+        z_index: ZIndex::Global(-1),
+        ..default()
+    }).id();
+    commands.insert_resource(ScaledRender { image, window_camera, node });
+}
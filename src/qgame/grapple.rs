@@ -0,0 +1,100 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{eye_transform, GameState, GameTime, is_authoritative, MoveMode, player_move_sys, PlayerController, PlayerInput, PlayerInputFlags, Respawning};
+
+#[derive(Resource)]
+pub struct GrappleSettings {
+    /// Longest rope, both for attaching and before it snaps
+    pub max_length: f32,
+    /// Released once the eye gets this close to the anchor
+    pub min_distance: f32,
+    /// Pull acceleration per meter of rope
+    pub stiffness: f32,
+    pub max_pull_accel: f32,
+}
+
+/// Rope from the eye of a player to a point on a surface
+#[derive(Component, Default)]
+pub struct Grapple {
+    pub anchor: Option<Vec3>,
+    /// Attaching needs a fresh press, holding the key only keeps the rope
+    was_held: bool,
+}
+
+pub struct GrapplePlugin;
+
+impl Plugin for GrapplePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<GrappleSettings>()
+            .add_systems(FixedUpdate, grapple_sys
+                .before(player_move_sys)
+                .run_if(is_authoritative)
+                .run_if(in_state(GameState::Playing)));
+    }
+}
+
+impl Default for GrappleSettings {
+    fn default() -> Self {
+        Self {
+            max_length: 40.0,
+            min_distance: 2.0,
+            stiffness: 4.0,
+            max_pull_accel: 60.0,
+        }
+    }
+}
+
+impl Grapple {
+    #[inline]
+    pub fn is_attached(&self) -> bool {
+        self.anchor.is_some()
+    }
+}
+
+/// Pulls the controller velocity towards the anchor each tick, ahead of movement
+pub fn grapple_sys(
+    time: Res<Time>,
+    game_time: Res<GameTime>,
+    settings: Res<GrappleSettings>,
+    physics_context: Res<RapierContext>,
+    mut query: Query<(Entity, &PlayerInput, &Transform, &mut PlayerController, &mut Grapple), Without<Respawning>>,
+) {
+    let dt = time.delta_seconds() * game_time.scale;
+
+    for (entity, input, transform, mut controller, mut grapple) in query.iter_mut() {
+        let is_held = input.flags.contains(PlayerInputFlags::Grapple) && controller.move_mode == MoveMode::Ground;
+        let is_pressed = is_held && !grapple.was_held;
+        grapple.was_held = is_held;
+        if !is_held {
+            grapple.anchor = None;
+            continue;
+        }
+
+        let eye = eye_transform(transform, &controller);
+        // Pickups and hazards are sensors, neither should hold or cut the rope
+        let filter = QueryFilter::default().exclude_rigid_body(entity).exclude_sensors();
+        if is_pressed {
+            grapple.anchor = physics_context.cast_ray(eye.translation, eye.forward(), settings.max_length, true, filter)
+                .map(|(_, toi)| eye.translation + eye.forward() * toi);
+        }
+        let Some(anchor) = grapple.anchor else { continue; };
+
+        let to_anchor = anchor - eye.translation;
+        let distance = to_anchor.length();
+        if distance < settings.min_distance || distance > settings.max_length {
+            grapple.anchor = None;
+            continue;
+        }
+        let dir = to_anchor / distance;
+        // Anything between us and the anchor cuts the rope, stop a little short so the anchor surface itself does not count
+        if physics_context.cast_ray(eye.translation, dir, distance - 0.1, true, filter).is_some() {
+            grapple.anchor = None;
+            continue;
+        }
+
+        let pull_accel = f32::min(settings.stiffness * distance, settings.max_pull_accel);
+        controller.velocity += dir * pull_accel * dt;
+    }
+}
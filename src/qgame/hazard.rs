@@ -0,0 +1,125 @@
+use std::time::Duration;
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use bevy_rapier3d::prelude::*;
+
+use crate::{apply_hit, Chunk, DamageType, DeathEvent, final_damage, GameState, GameTime, Health, HitEvent, is_authoritative, Resistances, Shield};
+
+/// Material of the lava biome, see [`crate::BiomeMap`]
+pub const LAVA_MATERIAL: u32 = 3;
+
+/// Damages anything with [`Health`] overlapping the collider of this entity, usually a sensor volume
+#[derive(Component, Clone, Debug)]
+pub struct Hazard {
+    pub damage_per_second: f32,
+    pub damage_type: DamageType,
+}
+
+/// Terrain that hurts to stand on, keyed by [`crate::Voxel::material`]
+#[derive(Resource)]
+pub struct HazardMaterials(pub HashMap<u32, Hazard>);
+
+#[derive(Resource)]
+pub struct HazardSettings {
+    /// Damage is dealt in steps this far apart instead of every frame
+    pub tick_interval: Duration,
+}
+
+/// Hurt by a hazard recently, no hazard damages this entity again until it runs out
+#[derive(Component)]
+pub struct HazardCooldown(pub Duration);
+
+pub struct HazardPlugin;
+
+impl Plugin for HazardPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<HazardMaterials>()
+            .init_resource::<HazardSettings>()
+            .add_systems(Update, hazard_sys
+                .run_if(is_authoritative)
+                .run_if(in_state(GameState::Playing)));
+    }
+}
+
+impl Default for HazardMaterials {
+    fn default() -> Self {
+        Self(HashMap::from([
+            (LAVA_MATERIAL, Hazard { damage_per_second: 20.0, damage_type: DamageType::Fire }),
+        ]))
+    }
+}
+
+impl Default for HazardSettings {
+    fn default() -> Self {
+        Self { tick_interval: Duration::from_millis(500) }
+    }
+}
+
+pub fn hazard_sys(
+    time: Res<GameTime>,
+    mut commands: Commands,
+    phys_ctx: Res<RapierContext>,
+    settings: Res<HazardSettings>,
+    hazard_materials: Res<HazardMaterials>,
+    mut hit_events: EventWriter<HitEvent>,
+    mut death_events: EventWriter<DeathEvent>,
+    hazard_query: Query<(&Hazard, Option<&GlobalTransform>)>,
+    chunk_query: Query<(Entity, &Chunk)>,
+    mut health_query: Query<(Entity, &Transform, &mut Health, Option<&Resistances>, Option<&mut Shield>), Without<HazardCooldown>>,
+    mut cooldown_query: Query<(Entity, &mut HazardCooldown)>,
+) {
+    for (ent, mut cooldown) in cooldown_query.iter_mut() {
+        cooldown.0 = cooldown.0.saturating_sub(time.delta());
+        if cooldown.0.is_zero() {
+            commands.entity(ent).remove::<HazardCooldown>();
+        }
+    }
+
+    // (hazard, victim, where the damage comes from), each victim is only hurt by one hazard per tick
+    let mut exposures: Vec<(Entity, Entity, &Hazard, Vec3)> = Vec::new();
+    let mut exposed: HashSet<Entity> = HashSet::new();
+    for (ent1, ent2, is_intersecting) in phys_ctx.intersection_pairs() {
+        if !is_intersecting { continue; }
+        // Hitboxes are children of the body with the health
+        let ent1 = phys_ctx.collider_parent(ent1).unwrap_or(ent1);
+        let ent2 = phys_ctx.collider_parent(ent2).unwrap_or(ent2);
+        for (hazard_ent, victim_ent) in [(ent1, ent2), (ent2, ent1)] {
+            let Ok((hazard, hazard_transform)) = hazard_query.get(hazard_ent) else { continue; };
+            let Ok((_, victim_transform, ..)) = health_query.get(victim_ent) else { continue; };
+            if exposed.insert(victim_ent) {
+                let source = hazard_transform.map_or(victim_transform.translation, |transform| transform.translation());
+                exposures.push((hazard_ent, victim_ent, hazard, source));
+            }
+        }
+    }
+
+    if !hazard_materials.0.is_empty() {
+        for (victim_ent, transform, ..) in health_query.iter() {
+            if exposed.contains(&victim_ent) { continue; }
+            // Solid voxel right under the feet
+            let below = transform.translation - Vec3::Y * 0.5;
+            let standing_on = chunk_query.iter().find_map(|(chunk_ent, chunk)| {
+                chunk.voxel_at(below).map(|voxel| (chunk_ent, voxel))
+            });
+            let Some((chunk_ent, voxel)) = standing_on else { continue; };
+            if voxel.density < 0.5 { continue; }
+            let Some(hazard) = hazard_materials.0.get(&voxel.material) else { continue; };
+            exposed.insert(victim_ent);
+            exposures.push((chunk_ent, victim_ent, hazard, below));
+        }
+    }
+
+    let tick_secs = settings.tick_interval.as_secs_f32();
+    for (hazard_ent, victim_ent, hazard, source) in exposures {
+        let Ok((_, _, mut health, resistances, shield)) = health_query.get_mut(victim_ent) else { continue; };
+        if health.is_dead() { continue; }
+        let damage = final_damage(hazard.damage_per_second * tick_secs, hazard.damage_type, resistances);
+        let hit = HitEvent { attacker: hazard_ent, target: victim_ent, damage, source, knockback: Vec3::ZERO, is_kill: false };
+        apply_hit(&mut hit_events, &mut death_events, hit, &mut health, shield);
+        commands.entity(victim_ent).insert(HazardCooldown(settings.tick_interval));
+    }
+}
@@ -0,0 +1,68 @@
+use std::time::Duration;
+
+use bevy::{
+    app::ScheduleRunnerPlugin,
+    prelude::*,
+    scene::ScenePlugin,
+};
+
+use crate::{
+    apply_chunk_collider_sys, ChunkGenerationHooks, ConsoleCommandEvent, cpu_polygonize_sys, GameState, MOVE_TICK_RATE,
+    PlaySoundEvent, TerrainDensity, TerrainHeightmap, TerrainMaterial,
+};
+
+/// Present when running without a window or GPU
+#[derive(Resource)]
+pub struct Headless;
+
+/// Stands in for `DefaultPlugins` and every rendering, audio and UI plugin.
+/// Chunks are meshed on the CPU for their colliders only and the game starts in [`GameState::Playing`].
+pub struct HeadlessPlugin;
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugins((
+                MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(1.0 / MOVE_TICK_RATE))),
+                AssetPlugin::default(),
+                TransformPlugin,
+                HierarchyPlugin,
+                ScenePlugin,
+            ))
+            .insert_resource(Headless)
+            // Normally initialized by the voxels plugin, which needs a GPU
+            .init_resource::<TerrainHeightmap>()
+            .init_resource::<TerrainDensity>()
+            .init_resource::<ChunkGenerationHooks>()
+            // Spawning the world still creates meshes and materials, they are just never rendered
+            .init_asset::<Mesh>()
+            .init_asset::<StandardMaterial>()
+            .init_asset::<TerrainMaterial>()
+            // Normally added by the console and sound plugins, gameplay systems still read and send them
+            .add_event::<ConsoleCommandEvent>()
+            .add_event::<PlaySoundEvent>()
+            .add_state::<GameState>()
+            .add_systems(Startup, start_playing_sys)
+            .add_systems(PreUpdate, (cpu_polygonize_sys, apply_chunk_collider_sys));
+    }
+}
+
+/// There is no main menu to leave
+fn start_playing_sys(mut next_state: ResMut<NextState<GameState>>) {
+    next_state.set(GameState::Playing);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boots_straight_into_playing() {
+        let mut app = App::new();
+        app.add_plugins(HeadlessPlugin);
+        app.update();
+        app.update();
+        assert!(app.world.contains_resource::<Headless>());
+        assert_eq!(*app.world.resource::<State<GameState>>().get(), GameState::Playing);
+    }
+}
@@ -1,4 +1,7 @@
-use std::f32::consts::FRAC_PI_2;
+use std::{
+    f32::consts::{FRAC_PI_2, PI, TAU},
+    path::PathBuf,
+};
 
 use bevy::{
     asset::{
@@ -6,17 +9,18 @@ use bevy::{
         AsyncReadExt,
         io::Reader,
         LoadContext,
+        LoadState,
     },
     input::mouse::MouseMotion,
     prelude::*,
     reflect::TypePath,
     utils::BoxedFuture,
-    window::CursorGrabMode,
+    window::{CursorGrabMode, PresentMode},
 };
 use flagset::{flags, FlagSet};
 use serde::{Deserialize, Serialize};
 
-use crate::RonLoaderError;
+use crate::{ColorThemePreset, Console, ConsoleCommandEvent, GameState, GunProps, Inventory, Item, MoveMode, RonLoaderError};
 
 flags! {
     pub enum PlayerInputFlags: u32 {
@@ -24,10 +28,45 @@ flags! {
         Sprint,
         Fly,
         Fire,
-        Reload
+        Reload,
+        Drop,
+        Aim,
+        CycleFireMode,
+        Noclip,
+        Grapple,
+        Use,
+        Crouch
     }
 }
 
+/// Toggles that stay set until a movement tick consumes them, frames can run without any tick
+pub fn latched_input_flags() -> FlagSet<PlayerInputFlags> {
+    PlayerInputFlags::Fly | PlayerInputFlags::Noclip
+}
+
+/// Held inputs read by movement ticks, a press between two ticks is kept until the next one even if released
+pub fn tick_input_flags() -> FlagSet<PlayerInputFlags> {
+    PlayerInputFlags::Jump | PlayerInputFlags::Crouch | PlayerInputFlags::Sprint | PlayerInputFlags::Grapple
+}
+
+/// Input polled every frame since the last movement tick, folded into [`PlayerInput`] once per tick
+/// by [`apply_input_accumulator_sys`] so that nothing is lost or counted twice at any frame rate
+#[derive(Component, Default, Debug)]
+pub struct InputAccumulator {
+    /// Mouse motion already scaled by sensitivity, subtracted from pitch and yaw
+    pub look_delta: Vec2,
+    /// Any of [`tick_input_flags`] held at some point since the last tick
+    pub pressed: FlagSet<PlayerInputFlags>,
+}
+
+/// Where a local player reads input from, see [`crate::LocalPlayers`]
+#[derive(Component, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum InputSource {
+    KeyboardMouse,
+    /// Index into the connected gamepads sorted by id
+    Gamepad(usize),
+}
+
 #[derive(Component, Default, Debug)]
 pub struct PlayerInput {
     pub movement: Vec3,
@@ -35,11 +74,36 @@ pub struct PlayerInput {
     pub yaw: f32,
     pub pitch: f32,
     pub wanted_item_slot: Option<u8>,
+    /// Switch straight to a mode, takes priority over the fly toggle
+    pub wanted_move_mode: Option<MoveMode>,
+    /// Keep walking forward without holding the key, cancelled by any other movement key
+    pub is_auto_walking: bool,
 }
 
 #[derive(Asset, Copy, Clone, Debug, PartialEq, Serialize, Deserialize, TypePath)]
 pub struct Config {
     pub sensitivity: f32,
+    /// Weight in [0, 1) of the previous mouse motion in a moving average with the latest, zero for raw input
+    pub mouse_smoothing: f32,
+    /// Extra sensitivity per count per millisecond of mouse speed, zero for none
+    pub mouse_accel: f32,
+    /// Look speed in radians per second with the right stick all the way over
+    pub gamepad_sensitivity: f32,
+    /// Camera far plane distance
+    pub view_distance: f32,
+    /// `AutoVsync` and `Fifo` wait for the display, `AutoNoVsync` and `Immediate` do not
+    pub present_mode: PresentMode,
+    /// Most frames per second to render, none for no limit beyond the present mode
+    pub fps_cap: Option<u32>,
+    /// Directional lights cast shadows
+    pub shadows: bool,
+    /// Side length in texels of each directional shadow map cascade
+    pub shadow_map_size: usize,
+    /// Fraction of the window resolution the world is rendered at, the UI is always at full resolution.
+    /// Split-screen always renders at full resolution.
+    pub render_scale: f32,
+    /// Colors of the HUD and hit feedback, see [`crate::ColorTheme`]
+    pub color_theme: ColorThemePreset,
     pub key_forward: KeyCode,
     pub key_back: KeyCode,
     pub key_left: KeyCode,
@@ -49,36 +113,57 @@ pub struct Config {
     pub key_sprint: KeyCode,
     pub key_jump: KeyCode,
     pub key_fly: KeyCode,
+    pub key_walk: KeyCode,
+    pub key_spectate: KeyCode,
+    pub key_noclip: KeyCode,
     pub key_crouch: KeyCode,
     pub key_fire: KeyCode,
     pub key_reload: KeyCode,
+    pub key_debug_chunks: KeyCode,
+    pub key_console: KeyCode,
+    pub key_drop: KeyCode,
+    pub key_waypoint: KeyCode,
+    pub key_wireframe: KeyCode,
+    pub key_third_person: KeyCode,
+    pub key_swap_shoulder: KeyCode,
+    pub key_auto_walk: KeyCode,
+    pub key_fire_mode: KeyCode,
+    pub key_grapple: KeyCode,
+    pub key_use: KeyCode,
 }
 
+const SHIPPED_CONFIG: &str = include_str!("../../assets/default.config.ron");
+
+/// Written by `reset_config` and loaded instead of the shipped config when present
+pub const USER_CONFIG_PATH: &str = "user.config.ron";
+
 #[derive(Resource)]
 pub struct ConfigState {
     pub(crate) handle: Handle<Config>,
+    /// Where config changes are saved, none to keep them in memory
+    pub(crate) user_path: Option<PathBuf>,
 }
 
+/// Shown when the config asset failed to load and defaults are in use
+#[derive(Component)]
+pub struct ConfigWarningText;
+
 impl Default for Config {
+    /// The shipped `default.config.ron`, so resetting or falling back never drifts from it
     fn default() -> Self {
-        Self {
-            key_forward: KeyCode::W,
-            key_back: KeyCode::S,
-            key_left: KeyCode::A,
-            key_right: KeyCode::D,
-            key_up: KeyCode::Q,
-            key_down: KeyCode::E,
-            key_sprint: KeyCode::ShiftLeft,
-            key_jump: KeyCode::Space,
-            key_fly: KeyCode::F,
-            key_crouch: KeyCode::ControlLeft,
-            key_fire: KeyCode::Q,
-            sensitivity: 0.5,
-            key_reload: KeyCode::R,
-        }
+        ron::from_str(SHIPPED_CONFIG).expect("Shipped config should parse")
     }
 }
 
+pub fn clamp_pitch(pitch: f32) -> f32 {
+    pitch.clamp(-FRAC_PI_2 + 0.001953125, FRAC_PI_2 - 0.001953125)
+}
+
+/// Wrap an angle to [-PI, PI], keeps yaw from growing without bound and losing precision
+pub fn wrap_angle(angle: f32) -> f32 {
+    (angle + PI).rem_euclid(TAU) - PI
+}
+
 fn get_pressed(key_input: &Res<Input<KeyCode>>, key: KeyCode) -> f32 {
     if key_input.pressed(key) {
         1.0
@@ -91,61 +176,240 @@ fn get_axis(key_input: &Res<Input<KeyCode>>, key_pos: KeyCode, key_neg: KeyCode)
     get_pressed(key_input, key_pos) - get_pressed(key_input, key_neg)
 }
 
+impl InputSource {
+    /// The first player uses the keyboard and mouse, the rest take the gamepads in order
+    pub fn for_player(player_id: u8) -> Self {
+        match player_id {
+            0 => InputSource::KeyboardMouse,
+            player_id => InputSource::Gamepad(player_id as usize - 1),
+        }
+    }
+}
+
+/// Escape releases the cursor through the pause menu, see [`crate::pause_toggle_sys`]
 pub fn cursor_grab_sys(
     mut windows: Query<&mut Window>,
     btn: Res<Input<MouseButton>>,
-    key: Res<Input<KeyCode>>,
+    state: Res<State<GameState>>,
 ) {
     let mut window = windows.single_mut();
-    if btn.just_pressed(MouseButton::Left) {
+    if btn.just_pressed(MouseButton::Left) && *state.get() == GameState::Playing {
         window.cursor.grab_mode = CursorGrabMode::Locked;
         window.cursor.visible = false;
     }
-    if key.just_pressed(KeyCode::Escape) {
-        window.cursor.grab_mode = CursorGrabMode::None;
-        window.cursor.visible = true;
+}
+
+/// Look sensitivity multiplier of the equipped weapon, one when not aiming
+fn aim_sensitivity_scale(
+    is_aiming: bool, inv: Option<&Inventory>, item_query: &Query<&Item>, gun_props: &Assets<GunProps>,
+) -> f32 {
+    if !is_aiming { return 1.0; }
+    inv.and_then(Inventory::equipped_item_ent)
+        .and_then(|item_ent| item_query.get(item_ent).ok())
+        .and_then(|item| gun_props.get(&item.props))
+        .map_or(1.0, |props| props.weapon_props.aim_sensitivity_scale)
+}
+
+/// Smooth and accelerate one frame of mouse motion, unchanged when both settings are zero
+fn shape_mouse_delta(config: &Config, raw: Vec2, dt: f32, smoothed: &mut Vec2) -> Vec2 {
+    let smoothing = config.mouse_smoothing.clamp(0.0, 0.99);
+    *smoothed = *smoothed * smoothing + raw * (1.0 - smoothing);
+    if config.mouse_accel == 0.0 || dt <= 0.0 {
+        return *smoothed;
     }
+    let speed = smoothed.length() / (dt * 1000.0);
+    *smoothed * (1.0 + config.mouse_accel * speed)
 }
 
 pub fn player_input_system(
+    time: Res<Time>,
     key_input: Res<Input<KeyCode>>,
+    mouse_input: Res<Input<MouseButton>>,
+    gamepads: Res<Gamepads>,
+    button_input: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
     config: Res<Assets<Config>>,
     config_state: Res<ConfigState>,
+    console: Res<Console>,
+    gun_props: Res<Assets<GunProps>>,
+    item_query: Query<&Item>,
     mut window: Query<&mut Window>,
     mut mouse_events: EventReader<MouseMotion>,
-    mut query: Query<&mut PlayerInput>)
+    mut smoothed_mouse_delta: Local<Vec2>,
+    mut query: Query<(&mut PlayerInput, &mut InputAccumulator, &InputSource, Option<&Inventory>)>)
 {
-    if let Some(config) = config.get(&config_state.handle) {
-        for mut player_input in query.iter_mut() {
-            let window = window.single_mut();
-            if window.focused {
-                let mut mouse_delta = Vec2::ZERO;
-                for mouse_event in mouse_events.read() {
-                    mouse_delta += mouse_event.delta;
+    let Some(config) = config.get(&config_state.handle) else { return; };
+    let mut connected_gamepads: Vec<Gamepad> = gamepads.iter().collect();
+    connected_gamepads.sort_by_key(|gamepad| gamepad.id);
+    for (mut player_input, mut accumulator, source, inv) in query.iter_mut() {
+        // Keys typed into the console should not move the player
+        if console.open {
+            player_input.movement = Vec3::ZERO;
+            player_input.flags.clear();
+            player_input.wanted_move_mode = None;
+            *accumulator = InputAccumulator::default();
+            mouse_events.clear();
+            continue;
+        }
+
+        let latched = player_input.flags & latched_input_flags();
+        player_input.flags.clear();
+        player_input.flags |= latched;
+        match *source {
+            InputSource::KeyboardMouse => {
+                let window = window.single_mut();
+                if window.focused {
+                    let mut mouse_delta = Vec2::ZERO;
+                    for mouse_event in mouse_events.read() {
+                        mouse_delta += mouse_event.delta;
+                    }
+                    mouse_delta = shape_mouse_delta(config, mouse_delta, time.delta_seconds(), &mut smoothed_mouse_delta);
+                    let is_aiming = mouse_input.pressed(MouseButton::Right);
+                    mouse_delta *= config.sensitivity * aim_sensitivity_scale(is_aiming, inv, &item_query, &gun_props);
+                    accumulator.look_delta += mouse_delta;
                 }
-                mouse_delta *= config.sensitivity;
 
-                player_input.pitch = (player_input.pitch - mouse_delta.y).clamp(
-                    -FRAC_PI_2 + 0.001953125,
-                    FRAC_PI_2 - 0.001953125,
+                player_input.movement = Vec3::new(
+                    get_axis(&key_input, config.key_right, config.key_left),
+                    get_axis(&key_input, config.key_up, config.key_down),
+                    get_axis(&key_input, config.key_forward, config.key_back),
                 );
-                player_input.yaw = player_input.yaw - mouse_delta.x;
+                if key_input.just_pressed(config.key_auto_walk) {
+                    player_input.is_auto_walking = !player_input.is_auto_walking;
+                } else if key_input.any_just_pressed([config.key_forward, config.key_back, config.key_left, config.key_right]) {
+                    player_input.is_auto_walking = false;
+                }
+                if player_input.is_auto_walking {
+                    player_input.movement.z = 1.0;
+                }
+                if key_input.pressed(config.key_sprint) { player_input.flags |= PlayerInputFlags::Sprint; }
+                if key_input.pressed(config.key_crouch) { player_input.flags |= PlayerInputFlags::Crouch; }
+                if key_input.pressed(config.key_jump) { player_input.flags |= PlayerInputFlags::Jump; }
+                if key_input.pressed(config.key_fire) { player_input.flags |= PlayerInputFlags::Fire; }
+                if key_input.pressed(config.key_reload) { player_input.flags |= PlayerInputFlags::Reload; }
+                if mouse_input.pressed(MouseButton::Right) { player_input.flags |= PlayerInputFlags::Aim; }
+                if key_input.pressed(config.key_grapple) { player_input.flags |= PlayerInputFlags::Grapple; }
+                if key_input.pressed(config.key_use) { player_input.flags |= PlayerInputFlags::Use; }
+                if key_input.just_pressed(config.key_fly) { player_input.flags |= PlayerInputFlags::Fly; }
+                if key_input.just_pressed(config.key_noclip) { player_input.flags |= PlayerInputFlags::Noclip; }
+                if key_input.just_pressed(config.key_spectate) {
+                    player_input.wanted_move_mode = Some(MoveMode::Spectate);
+                } else if key_input.just_pressed(config.key_walk) {
+                    player_input.wanted_move_mode = Some(MoveMode::Ground);
+                }
+                if key_input.just_pressed(config.key_drop) { player_input.flags |= PlayerInputFlags::Drop; }
+                if key_input.just_pressed(config.key_fire_mode) { player_input.flags |= PlayerInputFlags::CycleFireMode; }
+                if key_input.pressed(KeyCode::Key1) { player_input.wanted_item_slot = Some(0); }
+                if key_input.pressed(KeyCode::Key2) { player_input.wanted_item_slot = Some(1); }
+                if key_input.pressed(KeyCode::Key3) { player_input.wanted_item_slot = Some(2); }
             }
+            InputSource::Gamepad(index) => {
+                // Stand still until a gamepad is connected for this player
+                let Some(&gamepad) = connected_gamepads.get(index) else {
+                    player_input.movement = Vec3::ZERO;
+                    continue;
+                };
+                let axis = |axis_type| axes.get(GamepadAxis::new(gamepad, axis_type)).unwrap_or(0.0);
+                let pressed = |button_type| button_input.pressed(GamepadButton::new(gamepad, button_type));
+                let just_pressed = |button_type| button_input.just_pressed(GamepadButton::new(gamepad, button_type));
+                let button_axis = |pos, neg| f32::from(u8::from(pressed(pos))) - f32::from(u8::from(pressed(neg)));
 
-            player_input.movement = Vec3::new(
-                get_axis(&key_input, config.key_right, config.key_left),
-                get_axis(&key_input, config.key_up, config.key_down),
-                get_axis(&key_input, config.key_forward, config.key_back),
-            );
-            player_input.flags.clear();
-            if key_input.pressed(config.key_sprint) { player_input.flags |= PlayerInputFlags::Sprint; }
-            if key_input.pressed(config.key_jump) { player_input.flags |= PlayerInputFlags::Jump; }
-            if key_input.pressed(config.key_fire) { player_input.flags |= PlayerInputFlags::Fire; }
-            if key_input.pressed(config.key_reload) { player_input.flags |= PlayerInputFlags::Reload; }
-            if key_input.just_pressed(config.key_fly) { player_input.flags |= PlayerInputFlags::Fly; }
-            if key_input.pressed(KeyCode::Key1) { player_input.wanted_item_slot = Some(0); }
-            if key_input.pressed(KeyCode::Key2) { player_input.wanted_item_slot = Some(1); }
-            if key_input.pressed(KeyCode::Key3) { player_input.wanted_item_slot = Some(2); }
+                // Stick up is positive, unlike mouse motion
+                let stick = Vec2::new(axis(GamepadAxisType::RightStickX), -axis(GamepadAxisType::RightStickY));
+                let is_aiming = pressed(GamepadButtonType::LeftTrigger2);
+                accumulator.look_delta += stick * config.gamepad_sensitivity * time.delta_seconds()
+                    * aim_sensitivity_scale(is_aiming, inv, &item_query, &gun_props);
+
+                player_input.movement = Vec3::new(
+                    axis(GamepadAxisType::LeftStickX),
+                    button_axis(GamepadButtonType::South, GamepadButtonType::East),
+                    axis(GamepadAxisType::LeftStickY),
+                );
+                player_input.is_auto_walking = false;
+                if pressed(GamepadButtonType::LeftThumb) { player_input.flags |= PlayerInputFlags::Sprint; }
+                if pressed(GamepadButtonType::East) { player_input.flags |= PlayerInputFlags::Crouch; }
+                if pressed(GamepadButtonType::South) { player_input.flags |= PlayerInputFlags::Jump; }
+                if pressed(GamepadButtonType::RightTrigger2) { player_input.flags |= PlayerInputFlags::Fire; }
+                if pressed(GamepadButtonType::West) { player_input.flags |= PlayerInputFlags::Reload; }
+                if is_aiming { player_input.flags |= PlayerInputFlags::Aim; }
+                if pressed(GamepadButtonType::LeftTrigger) { player_input.flags |= PlayerInputFlags::Grapple; }
+                if pressed(GamepadButtonType::North) { player_input.flags |= PlayerInputFlags::Use; }
+                if just_pressed(GamepadButtonType::DPadDown) { player_input.flags |= PlayerInputFlags::Drop; }
+                if just_pressed(GamepadButtonType::Select) { player_input.flags |= PlayerInputFlags::CycleFireMode; }
+                if pressed(GamepadButtonType::DPadLeft) { player_input.wanted_item_slot = Some(0); }
+                if pressed(GamepadButtonType::DPadUp) { player_input.wanted_item_slot = Some(1); }
+                if pressed(GamepadButtonType::DPadRight) { player_input.wanted_item_slot = Some(2); }
+            }
+        }
+        accumulator.pressed |= player_input.flags & tick_input_flags();
+    }
+}
+
+/// Hand everything polled since the last tick to the tick, first thing in [`FixedUpdate`]
+pub fn apply_input_accumulator_sys(mut query: Query<(&mut PlayerInput, &mut InputAccumulator)>) {
+    for (mut input, mut accumulator) in query.iter_mut() {
+        let accumulator = std::mem::take(&mut *accumulator);
+        input.pitch = clamp_pitch(input.pitch - accumulator.look_delta.y);
+        input.yaw = wrap_angle(input.yaw - accumulator.look_delta.x);
+        input.flags |= accumulator.pressed;
+    }
+}
+
+/// Stand in the embedded shipped config under the config handle if it failed to load,
+/// so everything reading the config keeps working without knowing about the failure
+pub fn config_fallback_sys(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    config_state: Res<ConfigState>,
+    mut configs: ResMut<Assets<Config>>,
+) {
+    if asset_server.get_load_state(&config_state.handle) != Some(LoadState::Failed) { return; }
+    if configs.contains(&config_state.handle) { return; }
+
+    let path = config_state.handle.path().map_or_else(|| "config".to_string(), |path| path.to_string());
+    warn!("Failed to load {}, using default bindings", path);
+    configs.insert(&config_state.handle, Config::default());
+    commands.spawn((
+        TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(5.0),
+                right: Val::Px(5.0),
+                ..default()
+            },
+            text: Text::from_section(
+                format!("Failed to load {}, using default bindings", path),
+                TextStyle { font_size: 16.0, color: Color::ORANGE_RED, ..default() },
+            ),
+            ..default()
+        },
+        ConfigWarningText,
+    ));
+}
+
+/// `reset_config confirm` restores [`Config::default`] and writes it to the user config file
+pub fn reset_config_command_sys(
+    config_state: Res<ConfigState>,
+    mut configs: ResMut<Assets<Config>>,
+    mut command_events: EventReader<ConsoleCommandEvent>,
+) {
+    for command in command_events.read() {
+        if command.name != "reset_config" { continue; }
+        if command.args.first().map(String::as_str) != Some("confirm") {
+            warn!("This overwrites all bindings, run `reset_config confirm` to continue");
+            continue;
+        }
+
+        let config = Config::default();
+        // Takes effect immediately, input reads the config through this handle every frame
+        configs.insert(&config_state.handle, config);
+        let Some(file_path) = &config_state.user_path else { continue; };
+        let result = ron::ser::to_string_pretty(&config, ron::ser::PrettyConfig::default())
+            .map_err(|err| err.to_string())
+            .and_then(|text| std::fs::write(&file_path, text).map_err(|err| err.to_string()));
+        match result {
+            Ok(()) => info!("Reset config and saved it to {}", file_path.display()),
+            Err(err) => error!("Reset config but failed to save it to {}: {}", file_path.display(), err),
         }
     }
 }
@@ -175,4 +439,216 @@ impl AssetLoader for ConfigAssetLoader {
     fn extensions(&self) -> &[&str] {
         &["config.ron"]
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::ecs::system::SystemState;
+
+    use crate::{IDLE_STATE, ItemStateName, look_quat};
+
+    use super::*;
+
+    #[test]
+    fn shipped_config_parses() {
+        ron::from_str::<Config>(include_str!("../../assets/default.config.ron")).unwrap();
+    }
+
+    #[test]
+    fn missing_config_falls_back_to_defaults() {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<Config>()
+            .register_asset_loader(ConfigAssetLoader)
+            .add_systems(Update, config_fallback_sys);
+        let handle = app.world.resource::<AssetServer>().load("missing.config.ron");
+        app.insert_resource(ConfigState { handle: handle.clone(), user_path: None });
+
+        // Loading happens on other threads
+        for _ in 0..200 {
+            app.update();
+            if app.world.resource::<Assets<Config>>().contains(&handle) { break; }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let config = app.world.resource::<Assets<Config>>().get(&handle).unwrap();
+        assert_eq!(config, &ron::from_str::<Config>(SHIPPED_CONFIG).unwrap());
+        assert_eq!(config.sensitivity, 0.001);
+        assert_eq!((config.key_forward, config.key_back, config.key_left, config.key_right), (KeyCode::W, KeyCode::S, KeyCode::A, KeyCode::D));
+        // Every action stays reachable, jump and up share a key on purpose as do crouch and down
+        assert_ne!(config.key_fire, config.key_up);
+        assert_ne!(config.key_fire, config.key_jump);
+        assert_eq!(app.world.query::<&ConfigWarningText>().iter(&app.world).count(), 1);
+    }
+
+    #[test]
+    fn aiming_scales_by_equipped_weapon() {
+        let rifle: GunProps = ron::from_str(include_str!("../../assets/items/rifle.item.ron")).unwrap();
+        // Same rifle with a higher zoom optic
+        let mut scoped: GunProps = ron::from_str(include_str!("../../assets/items/rifle.item.ron")).unwrap();
+        scoped.weapon_props.aim_sensitivity_scale = 0.3;
+        let mut gun_props = Assets::<GunProps>::default();
+        let props = [gun_props.add(rifle), gun_props.add(scoped)];
+
+        let mut world = World::new();
+        world.insert_resource(gun_props);
+        let player = world.spawn_empty().id();
+        let mut inv = Inventory::default();
+        for (slot, props) in props.into_iter().enumerate() {
+            let item_ent = world.spawn(Item {
+                name: "rifle".into(),
+                amount: 1,
+                state_name: ItemStateName::from(IDLE_STATE),
+                state_dur: Duration::ZERO,
+                state_dur_scale: 1.0,
+                fire_mode_index: 0,
+                is_fire_press_pending: false,
+                is_fire_held: false,
+                burst_remaining: 0,
+                inv_ent: player,
+                inv_slot: slot as u8,
+                props,
+            }).id();
+            inv.item_ents.0[slot] = Some(item_ent);
+        }
+
+        let mut state: SystemState<(Query<&Item>, Res<Assets<GunProps>>)> = SystemState::new(&mut world);
+        let (item_query, gun_props) = state.get(&world);
+        // Nothing is equipped yet
+        assert_eq!(aim_sensitivity_scale(true, Some(&inv), &item_query, &gun_props), 1.0);
+        inv.equipped_slot = Some(0);
+        let rifle_scale = aim_sensitivity_scale(true, Some(&inv), &item_query, &gun_props);
+        assert_eq!(aim_sensitivity_scale(false, Some(&inv), &item_query, &gun_props), 1.0);
+        inv.equipped_slot = Some(1);
+        let scoped_scale = aim_sensitivity_scale(true, Some(&inv), &item_query, &gun_props);
+        assert_eq!(aim_sensitivity_scale(false, Some(&inv), &item_query, &gun_props), 1.0);
+        // Higher zoom slows looking down more
+        assert!(rifle_scale < 1.0);
+        assert!(scoped_scale < rifle_scale);
+    }
+
+    #[test]
+    fn reset_config_needs_confirmation() {
+        let mut configs = Assets::<Config>::default();
+        let handle = configs.add(Config { sensitivity: 2.0, key_forward: KeyCode::K, ..default() });
+        let mut app = App::new();
+        app
+            .add_event::<ConsoleCommandEvent>()
+            .insert_resource(configs)
+            // No user path, so nothing is written to disk
+            .insert_resource(ConfigState { handle: handle.clone(), user_path: None })
+            .add_systems(Update, reset_config_command_sys);
+
+        app.world.send_event(ConsoleCommandEvent::parse("reset_config").unwrap());
+        app.update();
+        assert_eq!(app.world.resource::<Assets<Config>>().get(&handle).unwrap().key_forward, KeyCode::K);
+
+        app.world.send_event(ConsoleCommandEvent::parse("reset_config confirm").unwrap());
+        app.update();
+        let config = app.world.resource::<Assets<Config>>().get(&handle).unwrap();
+        assert_eq!(config, &ron::from_str::<Config>(SHIPPED_CONFIG).unwrap());
+        assert_eq!(config.key_forward, KeyCode::W);
+        assert_ne!(config.key_up, config.key_fire);
+    }
+
+    #[test]
+    fn wrap_angle_stays_in_range() {
+        assert!((wrap_angle(TAU + 0.5) - 0.5).abs() < 1e-5);
+        assert!((wrap_angle(-TAU - 0.5) + 0.5).abs() < 1e-5);
+        assert!((wrap_angle(PI + 0.25) - (0.25 - PI)).abs() < 1e-5);
+        assert_eq!(wrap_angle(1.0), 1.0);
+    }
+
+    #[test]
+    fn pitch_stops_short_of_straight_up() {
+        assert!(clamp_pitch(10.0) < FRAC_PI_2);
+        assert!(clamp_pitch(-10.0) > -FRAC_PI_2);
+        assert_eq!(clamp_pitch(0.3), 0.3);
+    }
+
+    #[test]
+    fn accumulated_look_is_wrapped_and_clamped() {
+        let mut app = App::new();
+        app.add_systems(Update, apply_input_accumulator_sys);
+        let player = app.world.spawn((
+            PlayerInput { yaw: 3.0, ..default() },
+            InputAccumulator { look_delta: Vec2::new(-1.0, -5.0), ..default() },
+        )).id();
+        app.update();
+        let input = app.world.get::<PlayerInput>(player).unwrap();
+        assert!((input.yaw - (4.0 - TAU)).abs() < 1e-5);
+        assert_eq!(input.pitch, clamp_pitch(5.0));
+    }
+
+    #[test]
+    fn many_large_yaw_deltas_do_not_roll() {
+        let mut app = App::new();
+        app.add_systems(Update, apply_input_accumulator_sys);
+        let player = app.world.spawn((PlayerInput { pitch: 0.4, ..default() }, InputAccumulator::default())).id();
+        for i in 0..5000 {
+            // Alternate directions unevenly so yaw keeps wrapping both ways
+            let delta = if i % 3 == 0 { -7.3 } else { 5.9 };
+            app.world.get_mut::<InputAccumulator>(player).unwrap().look_delta = Vec2::new(delta, 0.0);
+            app.update();
+
+            let input = app.world.get::<PlayerInput>(player).unwrap();
+            assert!((-PI..=PI).contains(&input.yaw));
+            let (roll, yaw, pitch) = look_quat(input.pitch, input.yaw).to_euler(EulerRot::ZYX);
+            assert!(roll.abs() < 1e-4, "rolled by {} after {} deltas", roll, i + 1);
+            assert!((pitch - 0.4).abs() < 1e-4);
+            assert!((wrap_angle(yaw - input.yaw)).abs() < 1e-4);
+        }
+    }
+
+    fn keyboard_input_app() -> App {
+        let mut configs = Assets::<Config>::default();
+        let handle = configs.add(Config::default());
+        let mut app = App::new();
+        app
+            .init_resource::<Time>()
+            .init_resource::<Input<KeyCode>>()
+            .init_resource::<Input<MouseButton>>()
+            .init_resource::<Gamepads>()
+            .init_resource::<Input<GamepadButton>>()
+            .init_resource::<Axis<GamepadAxis>>()
+            .init_resource::<Console>()
+            .insert_resource(configs)
+            .insert_resource(ConfigState { handle, user_path: None })
+            .insert_resource(Assets::<GunProps>::default())
+            .add_event::<MouseMotion>()
+            .add_systems(Update, player_input_system);
+        app.world.spawn(Window::default());
+        app.world.spawn((PlayerInput::default(), InputAccumulator::default(), InputSource::KeyboardMouse));
+        app
+    }
+
+    /// Hold `held` for one frame, of which only `just_pressed` are new presses, and return the movement
+    fn press(app: &mut App, held: &[KeyCode], just_pressed: &[KeyCode]) -> Vec3 {
+        let mut key_input = app.world.resource_mut::<Input<KeyCode>>();
+        key_input.reset_all();
+        for &key in held {
+            key_input.press(key);
+            if !just_pressed.contains(&key) {
+                key_input.clear_just_pressed(key);
+            }
+        }
+        app.update();
+        app.world.query::<&PlayerInput>().single(&app.world).movement
+    }
+
+    #[test]
+    fn auto_walk_latches_until_movement_key() {
+        let mut app = keyboard_input_app();
+        assert_eq!(press(&mut app, &[KeyCode::Z], &[KeyCode::Z]).z, 1.0);
+        assert_eq!(press(&mut app, &[], &[]).z, 1.0);
+        // Walking back cancels it instead of adding up to standing still
+        assert_eq!(press(&mut app, &[KeyCode::S], &[KeyCode::S]).z, -1.0);
+        assert_eq!(press(&mut app, &[], &[]).z, 0.0);
+
+        // The key toggles it back off too
+        press(&mut app, &[KeyCode::Z], &[KeyCode::Z]);
+        assert_eq!(press(&mut app, &[KeyCode::Z], &[KeyCode::Z]).z, 0.0);
+    }
+}
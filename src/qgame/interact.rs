@@ -0,0 +1,107 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{eye_transform, GameState, is_authoritative, LogicalPlayer, PlayerController, PlayerInput, PlayerInputFlags};
+
+/// Something a player can use by looking at it, such as a door, button or lever
+#[derive(Component, Clone, Debug)]
+pub struct Interactable {
+    /// Shown on the HUD while the local player looks at it
+    pub prompt: String,
+}
+
+/// Sent when a player presses use while looking at an [`Interactable`]
+#[derive(Event, Debug)]
+pub struct InteractEvent {
+    pub player: Entity,
+    pub target: Entity,
+}
+
+#[derive(Resource)]
+pub struct InteractSettings {
+    /// Farthest an interactable can be from the eye
+    pub max_distance: f32,
+}
+
+/// What a player is looking at, updated every frame by [`interact_sys`]
+#[derive(Component, Default)]
+pub struct Interactor {
+    pub target: Option<Entity>,
+    /// Using needs a fresh press, holding the key does not repeat
+    was_held: bool,
+}
+
+/// HUD text showing the prompt of what the local player is looking at
+#[derive(Component)]
+pub struct InteractPromptText;
+
+pub struct InteractPlugin;
+
+impl Plugin for InteractPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<InteractSettings>()
+            .add_event::<InteractEvent>()
+            .add_systems(Update, interact_sys
+                .run_if(is_authoritative)
+                .run_if(in_state(GameState::Playing)));
+    }
+}
+
+impl Default for InteractSettings {
+    fn default() -> Self {
+        Self { max_distance: 3.0 }
+    }
+}
+
+/// Find the interactable under the crosshair of each player and send an event on a use press.
+/// The ray stops at the first solid thing, so interactables behind walls are out of sight.
+pub fn interact_sys(
+    settings: Res<InteractSettings>,
+    physics_context: Res<RapierContext>,
+    mut interact_events: EventWriter<InteractEvent>,
+    interactable_query: Query<(), With<Interactable>>,
+    sensor_query: Query<(), With<Sensor>>,
+    mut player_query: Query<(Entity, &PlayerInput, &Transform, &PlayerController, &mut Interactor)>,
+) {
+    // Colliders may be children of the body that carries the component
+    let interactable_of = |collider_ent: Entity| {
+        let ent = physics_context.collider_parent(collider_ent).unwrap_or(collider_ent);
+        [collider_ent, ent].into_iter().find(|&ent| interactable_query.contains(ent))
+    };
+    // Sensors only count when they are interactable, otherwise they would block the view of everything inside them
+    let is_visible = |collider_ent: Entity| !sensor_query.contains(collider_ent) || interactable_of(collider_ent).is_some();
+
+    for (player_ent, input, transform, controller, mut interactor) in player_query.iter_mut() {
+        let eye = eye_transform(transform, controller);
+        let filter = QueryFilter::default().exclude_rigid_body(player_ent).predicate(&is_visible);
+        interactor.target = physics_context.cast_ray(eye.translation, eye.forward(), settings.max_distance, true, filter)
+            .and_then(|(hit_ent, _)| interactable_of(hit_ent));
+
+        let is_held = input.flags.contains(PlayerInputFlags::Use);
+        let is_pressed = is_held && !interactor.was_held;
+        interactor.was_held = is_held;
+        let Some(target) = interactor.target else { continue; };
+        if is_pressed {
+            interact_events.send(InteractEvent { player: player_ent, target });
+        }
+    }
+}
+
+pub fn interact_prompt_sys(
+    player_query: Query<(&LogicalPlayer, &Interactor)>,
+    interactable_query: Query<&Interactable>,
+    mut text_query: Query<&mut Text, With<InteractPromptText>>,
+) {
+    let prompt = player_query.iter()
+        .find(|(player, _)| player.0 == 0)
+        .and_then(|(_, interactor)| interactor.target)
+        .and_then(|target| interactable_query.get(target).ok())
+        .map_or("", |interactable| interactable.prompt.as_str());
+    for mut text in text_query.iter_mut() {
+        // Avoid triggering change detection every frame
+        if text.sections[0].value != prompt {
+            text.sections[0].value = prompt.to_string();
+        }
+    }
+}
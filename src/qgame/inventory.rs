@@ -11,6 +11,9 @@ use bevy::{
         io::Reader,
         LoadContext,
     },
+    gltf::Gltf,
+    hierarchy::HierarchyQueryExt,
+    math::Vec3Swizzles,
     prelude::*,
     reflect::TypePath,
     utils::{BoxedFuture, HashMap},
@@ -18,20 +21,24 @@ use bevy::{
 use bevy_rapier3d::prelude::*;
 use serde::{Deserialize, Serialize};
 use smartstring::alias::String;
+use thiserror::Error;
 
-use crate::{PlayerInput, PlayerInputFlags, RonLoaderError};
+use crate::{ConsoleCommandEvent, DamageType, eye_transform, GameRng, GameTime, Interactable, InteractEvent, LogicalPlayer, MoveFactor, MoveMode, PlayerController, PlayerInput, PlayerInputFlags, PlaySoundEvent, Recoil, RenderPlayer, Respawning, RonLoaderError, SoundCategory, wrap_angle};
 
-const EQUIPPING_STATE: &str = "equipping";
-const EQUIPPED_STATE: &str = "equipped";
-const UNEQUIPPING_STATE: &str = "unequipping";
-const UNEQUIPPED_STATE: &str = "unequipped";
-const IDLE_STATE: &str = "idle";
-const RELOAD_STATE: &str = "reload";
-const FIRE_STATE: &str = "fire";
+pub const EQUIPPING_STATE: &str = "equipping";
+pub const EQUIPPED_STATE: &str = "equipped";
+pub const UNEQUIPPING_STATE: &str = "unequipping";
+pub const UNEQUIPPED_STATE: &str = "unequipped";
+pub const IDLE_STATE: &str = "idle";
+pub const RELOAD_STATE: &str = "reload";
+pub const FIRE_STATE: &str = "fire";
+
+const DROP_THROW_SPEED: f32 = 6.0;
+const DROP_PICKUP_COOLDOWN: Duration = Duration::from_secs(1);
 
 pub type ItemName = String;
-type ItemStateName = String;
-type EquipStateName = String;
+pub type ItemStateName = String;
+pub type EquipStateName = String;
 
 #[derive(Serialize, Deserialize)]
 pub struct ItemStateProps {
@@ -39,47 +46,229 @@ pub struct ItemStateProps {
     pub is_persistent: bool,
 }
 
+/// Player actions that are rate limited by an [`ActionCooldowns`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    Switch,
+    Fire,
+}
+
+/// Sound played in the world at the position of whatever caused it
+#[derive(Clone, Serialize, Deserialize, TypePath)]
+pub struct ItemSound {
+    /// Relative to the assets folder
+    pub path: std::string::String,
+    pub volume: f32,
+    /// Multiplier on the distance to the listener, higher fades out sooner
+    pub falloff: f32,
+}
+
 #[derive(Serialize, Deserialize, TypePath)]
 pub struct ItemProps {
     pub name: ItemName,
     pub move_factor: f32,
+    /// How many fit in one inventory slot, one means not stackable
+    pub max_stack: u16,
     pub states: HashMap<ItemStateName, ItemStateProps>,
     pub equip_states: HashMap<EquipStateName, ItemStateProps>,
+    /// Minimum time between repeats of an action while this item is equipped, missing actions are not limited
+    pub cooldowns: HashMap<Action, Duration>,
+    /// Played when the item or its equip state enters the state of the same name
+    pub sounds: HashMap<ItemStateName, ItemSound>,
+    /// Name of the clip in the glTF model to play when the item or its equip state enters the state of the same name.
+    /// Clips are stretched to the duration of the state, persistent states loop.
+    pub animations: HashMap<ItemStateName, std::string::String>,
+}
+
+#[derive(Serialize, Deserialize, TypePath)]
+pub struct ProjectileProps {
+    pub speed: f32,
+    pub gravity_scale: f32,
+    pub explosion_radius: f32,
+    pub damage: f32,
+}
+
+/// Light flashed at the muzzle of the view model on each shot
+#[derive(Serialize, Deserialize, TypePath)]
+pub struct MuzzleFlashProps {
+    pub color: Color,
+    /// Point light intensity in lumens
+    pub intensity: f32,
+    pub range: f32,
+    /// Position of the muzzle relative to the view model
+    pub offset: Vec3,
+}
+
+#[derive(Serialize, Deserialize, TypePath)]
+pub struct MeleeProps {
+    pub range: f32,
+    pub damage: f32,
+    pub arc_degrees: f32,
+}
+
+/// What happens when a weapon fires
+#[derive(Serialize, Deserialize, TypePath)]
+pub enum AttackKind {
+    /// Instant ray along the aim direction
+    Hitscan,
+    /// Spawns a physical projectile
+    Projectile(ProjectileProps),
+    /// Swings at whatever is in front, never uses ammo
+    Melee(MeleeProps),
 }
 
 #[derive(Serialize, Deserialize, TypePath)]
 pub struct WeaponProps {
     pub damage: u16,
+    pub damage_type: DamageType,
     pub headshot_factor: f32,
+    /// Speed added to whatever is hit along the hit direction, explosions fall off with distance
+    pub knockback: f32,
+    /// Look sensitivity multiplier while aiming, lower for higher zoom optics
+    pub aim_sensitivity_scale: f32,
+    /// Fraction that each fire interval and fire sound pitch randomly varies by, zero for none
+    pub fire_jitter: f32,
+    /// Played where a shot lands, a projectile explodes, or a swing connects
+    pub impact_sound: Option<ItemSound>,
+    pub attack: AttackKind,
     pub item_props: ItemProps,
 }
 
+/// How holding fire turns into shots
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FireMode {
+    /// One shot per press
+    Semi,
+    /// Fixed number of shots per press
+    Burst(u8),
+    /// Keeps firing while held
+    Auto,
+}
+
+/// Top level of an `items/<name>.item.ron` asset. Props nest from most to least specific:
+/// `GunProps { weapon_props: WeaponProps { item_props: ItemProps } }`, so every item file is authored
+/// as a gun. Melee weapons have no magazine, whatever their `mag_size`.
 #[derive(Asset, Serialize, Deserialize, TypePath)]
 pub struct GunProps {
     pub mag_size: u16,
     pub starting_ammo_in_reserve: u16,
+    /// View kick in radians applied to pitch each shot
+    pub recoil: f32,
+    /// Speed added to the shooter opposite the aim direction, enough of it allows rocket jumping
+    pub recoil_impulse: Option<f32>,
+    /// Modes the fire mode key cycles through, the first is the default and none means full-auto
+    pub fire_modes: Vec<FireMode>,
+    /// Half angle in radians of the cone shots randomly go in when standing still
+    pub spread: f32,
+    /// Spread multiplier while moving or in the air
+    pub moving_spread_multiplier: f32,
+    /// Spread multiplier while aiming down sights
+    pub aim_spread_multiplier: f32,
+    pub muzzle_flash: Option<MuzzleFlashProps>,
     pub weapon_props: WeaponProps,
 }
 
+impl GunProps {
+    /// Whether firing spends rounds from a magazine that needs reloading
+    pub fn uses_ammo(&self) -> bool {
+        !matches!(self.weapon_props.attack, AttackKind::Melee(_)) && self.mag_size > 0
+    }
+}
+
 #[derive(Component, Debug)]
 pub struct Item {
     pub name: ItemName,
     pub amount: u16,
     pub state_name: ItemStateName,
     pub state_dur: Duration,
+    /// Multiplier on the configured duration of the current state
+    pub state_dur_scale: f32,
+    /// Index into the fire modes of the props
+    pub fire_mode_index: usize,
+    /// Fire was pressed and has not turned into a shot yet, semi-auto and burst only fire on a press
+    pub is_fire_press_pending: bool,
+    pub is_fire_held: bool,
+    /// Shots left in the current burst
+    pub burst_remaining: u8,
     pub inv_ent: Entity,
     pub inv_slot: u8,
+    pub props: Handle<GunProps>,
+}
+
+/// Sent whenever an item starts a new state
+#[derive(Event)]
+pub struct ItemStateEvent {
+    pub item_ent: Entity,
+    pub inv_ent: Entity,
+    pub state_name: ItemStateName,
+}
+
+/// Sent whenever an inventory starts a new equip state
+#[derive(Event)]
+pub struct EquipStateEvent {
+    pub inv_ent: Entity,
+    /// Item in the equipped slot as the state starts
+    pub item_ent: Entity,
+    pub state_name: EquipStateName,
+}
+
+/// Everything the item state machine needs while modifying an equipped item
+struct ItemContext<'a, 'w> {
+    item_ent: Entity,
+    inv: &'a Inventory,
+    input: &'a PlayerInput,
+    time: &'a GameTime,
+    props: Option<&'a GunProps>,
+    recoil: &'a mut Recoil,
+    cooldowns: &'a mut ActionCooldowns,
+    /// Missing until props load, or for items without a magazine
+    gun: Option<&'a mut Gun>,
+    rng: &'a mut GameRng,
+    state_events: &'a mut EventWriter<'w, ItemStateEvent>,
+}
+
+/// Time left before each action can be taken again
+#[derive(Component, Default, Debug)]
+pub struct ActionCooldowns(HashMap<Action, Duration>);
+
+/// Why [`Inventory::push_item`] left a pickup where it was
+#[derive(Debug, Error)]
+pub enum PushItemError {
+    /// Stacking depends on the props, so the push has to wait for them
+    #[error("props of {0} have not loaded yet")]
+    PropsNotLoaded(ItemName),
+    #[error("inventory is full")]
+    Full,
 }
 
+/// An item lying in the world, with what it had when it was dropped
 #[derive(Component)]
 pub struct ItemPickup {
     pub item_name: ItemName,
+    pub amount: u16,
+    /// Ammo the item had, none for fresh items which start with a full magazine
+    pub gun: Option<Gun>,
 }
 
 #[derive(Component, Default)]
 pub struct ItemPickupVisual;
 
+/// How players take pickups, looking at one and pressing use always works
+#[derive(Resource, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PickupMode {
+    /// Also picked up just by touching them
+    #[default]
+    Auto,
+    /// Only through use, so walking over items does not replace the loadout
+    Manual,
+}
+
+/// Pickup can not be picked up until this runs out, stops dropped items from being instantly picked back up
 #[derive(Component)]
+pub struct PickupCooldown(pub Duration);
+
+/// Ammo of an item whose props have a magazine, added once its props load
+#[derive(Component, Copy, Clone, Debug)]
 pub struct Gun {
     pub ammo: u16,
     pub ammo_in_reserve: u16,
@@ -91,6 +280,53 @@ pub struct Items(pub [Option<Entity>; 10]);
 #[derive(Component)]
 pub struct ItemVisual;
 
+/// Keeps the equipped view model from poking through walls the player is facing
+#[derive(Resource)]
+pub struct ViewModelPullback {
+    /// How far in front of the camera to look for walls
+    pub cast_distance: f32,
+    /// Distance the view model moves back when touching a wall
+    pub max_pullback: f32,
+}
+
+/// Where the equipped view model sits relative to the camera
+#[derive(Resource)]
+pub struct ViewModelPose {
+    pub hip_offset: Vec3,
+    /// Centered under the crosshair
+    pub aim_offset: Vec3,
+    /// How quickly the view model eases between poses, higher is snappier
+    pub aim_ease_rate: f32,
+}
+
+/// Procedural view model motion, both are mostly suppressed while aiming
+#[derive(Resource)]
+pub struct ViewModelFeel {
+    /// Offset per radian of look movement, the view model lags behind where the camera turns
+    pub sway_amount: f32,
+    pub max_sway: f32,
+    /// How quickly sway settles back to center, higher is snappier
+    pub sway_return_rate: f32,
+    /// Offset at walk speed
+    pub bob_amplitude: f32,
+    /// Bob cycles per second
+    pub bob_frequency: f32,
+}
+
+/// Per player view model state driven by [`ViewModelFeel`]
+#[derive(Component, Default)]
+pub struct ViewModelMotion {
+    /// Screen space offset, x right and y up
+    pub sway: Vec2,
+    /// Pitch and yaw last frame
+    pub prev_look: Option<Vec2>,
+    pub bob_phase: f32,
+}
+
+/// How far the view model has moved from the hip towards the aim pose, from zero to one
+#[derive(Component, Default)]
+pub struct AimProgress(pub f32);
+
 #[derive(Component, Debug)]
 pub struct Inventory {
     pub equipped_slot: Option<u8>,
@@ -102,8 +338,105 @@ pub struct Inventory {
 
 pub struct InventoryPlugin;
 
+pub fn item_props_path(item_name: &ItemName) -> std::string::String {
+    format!("items/{}.item.ron", item_name)
+}
+
+/// Goes on every [`ItemPickup`] so it can be taken through use
+pub fn pickup_interactable(item_name: &ItemName) -> Interactable {
+    Interactable { prompt: format!("Pick up {}", item_name) }
+}
+
+/// Duration and persistence of a state, states without props finish immediately
+fn state_timing(states: Option<&HashMap<ItemStateName, ItemStateProps>>, state_name: &str) -> (Duration, bool) {
+    states
+        .and_then(|states| states.get(state_name))
+        .map_or((Duration::ZERO, false), |state_props| (state_props.duration, state_props.is_persistent))
+}
+
 impl Plugin for InventoryPlugin {
-    fn build(&self, _app: &mut App) {}
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<ItemStateEvent>()
+            .add_event::<EquipStateEvent>()
+            .init_resource::<GameRng>()
+            .init_resource::<ViewModelPullback>()
+            .init_resource::<ViewModelPose>()
+            .init_resource::<ViewModelFeel>()
+            .init_resource::<PickupMode>()
+            .init_asset::<GunProps>()
+            .register_asset_loader(GunPropsAssetLoader)
+            .add_systems(Update, pickup_mode_command_sys);
+    }
+}
+
+impl Default for ViewModelPullback {
+    fn default() -> Self {
+        Self { cast_distance: 1.2, max_pullback: 0.6 }
+    }
+}
+
+impl Default for ViewModelPose {
+    fn default() -> Self {
+        Self {
+            hip_offset: Vec3::new(0.4, -0.3, -1.0),
+            aim_offset: Vec3::new(0.0, -0.2, -0.8),
+            aim_ease_rate: 12.0,
+        }
+    }
+}
+
+impl Default for ViewModelFeel {
+    fn default() -> Self {
+        Self {
+            sway_amount: 0.15,
+            max_sway: 0.06,
+            sway_return_rate: 6.0,
+            bob_amplitude: 0.025,
+            bob_frequency: 1.8,
+        }
+    }
+}
+
+impl ViewModelMotion {
+    /// Advance sway from look movement and bob from ground speed, returning the offset to apply to the view model
+    pub fn update(&mut self, feel: &ViewModelFeel, input: &PlayerInput, controller: &PlayerController, dt: f32) -> Vec3 {
+        let look = Vec2::new(input.pitch, input.yaw);
+        if let Some(prev_look) = self.prev_look {
+            let delta_pitch = look.x - prev_look.x;
+            let delta_yaw = wrap_angle(look.y - prev_look.y);
+            // Turning left leaves the view model behind on the right, looking up leaves it below
+            self.sway += Vec2::new(delta_yaw, -delta_pitch) * feel.sway_amount;
+            self.sway = self.sway.clamp_length_max(feel.max_sway);
+        }
+        self.prev_look = Some(look);
+        self.sway *= (-feel.sway_return_rate * dt).exp();
+
+        let is_grounded = controller.move_mode == MoveMode::Ground && controller.ground_tick > 0;
+        let speed_ratio = if is_grounded {
+            (controller.velocity.xz().length() / controller.walk_speed).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        if speed_ratio > 0.0 {
+            self.bob_phase = (self.bob_phase + TAU * feel.bob_frequency * dt) % TAU;
+        }
+        let bob_amplitude = feel.bob_amplitude * speed_ratio;
+        // Figure eight, side to side once per two vertical dips
+        let bob = Vec2::new(self.bob_phase.cos() * 0.5, -self.bob_phase.sin().abs()) * bob_amplitude;
+
+        (self.sway + bob).extend(0.0)
+    }
+}
+
+impl ViewModelPullback {
+    /// Offset towards the camera given the distance to the closest wall in front, if any
+    pub fn pullback(&self, wall_dist: Option<f32>) -> f32 {
+        wall_dist.map_or(0.0, |wall_dist| {
+            let closeness = 1.0 - (wall_dist / self.cast_distance).clamp(0.0, 1.0);
+            closeness * self.max_pullback
+        })
+    }
 }
 
 #[derive(Default)]
@@ -129,7 +462,7 @@ impl AssetLoader for GunPropsAssetLoader {
     }
 
     fn extensions(&self) -> &[&str] {
-        &["config.ron"]
+        &["item.ron"]
     }
 }
 
@@ -141,40 +474,63 @@ impl AssetLoader for GunPropsAssetLoader {
 // ╚══════╝ ╚═════╝  ╚═════╝ ╚═╝ ╚═════╝
 
 pub fn modify_equip_state_sys(
-    time: Res<Time>,
-    _asset_server: Res<AssetServer>,
-    mut inv_query: Query<(&PlayerInput, &mut Inventory)>,
+    time: Res<GameTime>,
+    gun_props: Res<Assets<GunProps>>,
+    mut equip_events: EventWriter<EquipStateEvent>,
+    mut inv_query: Query<(Entity, &PlayerInput, &mut Inventory, &mut ActionCooldowns, Option<&mut MoveFactor>)>,
     mut item_query: Query<&mut Item>,
 ) {
-    for (input, mut inv) in inv_query.iter_mut() {
+    let mut send_equip_state = |inv_ent: Entity, inv: &Inventory| {
+        if let Some(item_ent) = inv.equipped_item_ent() {
+            equip_events.send(EquipStateEvent { inv_ent, item_ent, state_name: inv.equip_state_name.clone() });
+        }
+    };
+    for (inv_ent, input, mut inv, mut cooldowns, move_factor) in inv_query.iter_mut() {
+        cooldowns.tick(time.delta());
+
+        if let Some(mut move_factor) = move_factor {
+            // Only a fully equipped item weighs the player down
+            let equipped_props = inv.equipped_props(&item_query, &gun_props)
+                .filter(|_| inv.equip_state_name == EQUIPPED_STATE);
+            move_factor.0 = equipped_props.map_or(1.0, |props| props.weapon_props.item_props.move_factor);
+        }
+
         let has_valid_wanted = input.wanted_item_slot.is_some()
             && inv.item_ents.0[input.wanted_item_slot.unwrap() as usize].is_some();
 
         // Handle unequipping current item
         let is_alr_unequipping = inv.equip_state_name == UNEQUIPPING_STATE;
-        if has_valid_wanted && input.wanted_item_slot != inv.equipped_slot && !is_alr_unequipping {
+        if has_valid_wanted && input.wanted_item_slot != inv.equipped_slot && !is_alr_unequipping
+            && cooldowns.is_ready(Action::Switch) {
             inv.equip_state_name = EquipStateName::from(UNEQUIPPING_STATE);
             inv.equip_state_dur = Duration::ZERO;
+            send_equip_state(inv_ent, &inv);
+            // Wanted slot stays set, so a switch requested during the cooldown happens once it runs out
+            if let Some(props) = inv.equipped_props(&item_query, &gun_props) {
+                cooldowns.trigger(Action::Switch, &props.weapon_props.item_props.cooldowns);
+            }
         }
-        if inv.equipped_slot.is_none() { return; }
-        if inv.equipped_slot.is_none() { return; }
+        if inv.equipped_slot.is_none() { continue; }
 
-        // Handle finishing equip state
+        // Handle finishing equip state, timings come from whichever item is in the equipped slot
+        let equip_states = inv.equipped_props(&item_query, &gun_props)
+            .map(|props| &props.weapon_props.item_props.equip_states);
         inv.equip_state_dur = inv.equip_state_dur.saturating_add(time.delta());
-        while inv.equip_state_dur > Duration::from_millis(2000) {
-            match inv.equip_state_name.as_str() {
-                EQUIPPING_STATE => {
-                    inv.equip_state_name = EquipStateName::from(EQUIPPED_STATE);
-                }
-                UNEQUIPPING_STATE => {
-                    inv.equip_state_name = EquipStateName::from(UNEQUIPPED_STATE);
-                }
-                _ => {}
-            }
-            inv.equip_state_dur = inv.equip_state_dur.saturating_sub(Duration::from_millis(2000));
+        loop {
+            let (duration, _) = state_timing(equip_states, &inv.equip_state_name);
+            if inv.equip_state_dur < duration { break; }
+            let next_state = match inv.equip_state_name.as_str() {
+                EQUIPPING_STATE => EQUIPPED_STATE,
+                UNEQUIPPING_STATE => UNEQUIPPED_STATE,
+                // Equipped and unequipped last until something else changes them
+                _ => break,
+            };
+            inv.equip_state_name = EquipStateName::from(next_state);
+            inv.equip_state_dur -= duration;
+            send_equip_state(inv_ent, &inv);
         }
 
-        if inv.equip_state_name != UNEQUIPPED_STATE { return; }
+        if inv.equip_state_name != UNEQUIPPED_STATE { continue; }
 
         // We have unequipped the last slot, so we need to starting equipping the new slot
         if has_valid_wanted {
@@ -184,60 +540,174 @@ pub fn modify_equip_state_sys(
             inv.equipped_slot = inv.find_replacement(&mut item_query);
         }
         inv.equip_state_name = EquipStateName::from(EQUIPPING_STATE);
+        send_equip_state(inv_ent, &inv);
     }
 }
 
 pub fn modify_item_sys(
-    time: Res<Time>,
-    mut item_query: Query<&mut Item>,
-    player_query: Query<(&PlayerInput, &Inventory)>,
+    time: Res<GameTime>,
+    gun_props: Res<Assets<GunProps>>,
+    mut rng: ResMut<GameRng>,
+    mut state_events: EventWriter<ItemStateEvent>,
+    mut item_query: Query<(Entity, &mut Item, Option<&mut Gun>)>,
+    mut player_query: Query<(&PlayerInput, &Inventory, &mut Recoil, &mut ActionCooldowns), Without<Respawning>>,
 ) {
-    for mut item in item_query.iter_mut() {
-        let (input, inv): (&PlayerInput, &Inventory) = player_query.get(item.inv_ent).unwrap();
+    for (item_ent, mut item, mut gun) in item_query.iter_mut() {
+        let Ok((input, inv, mut recoil, mut cooldowns)) = player_query.get_mut(item.inv_ent) else { continue; };
         let is_equipped = inv.equipped_slot == Some(item.inv_slot);
         if is_equipped {
-            item.modify(inv, input, &time);
-            while item.state_dur > Duration::from_millis(2000) {
-                match item.state_name.as_str() {
-                    IDLE_STATE | RELOAD_STATE | FIRE_STATE => {
-                        item.state_name = ItemStateName::from(IDLE_STATE);
-                    }
-                    _ => unimplemented!()
-                }
-                item.state_dur = item.state_dur.saturating_sub(Duration::from_millis(2000));
-            }
+            let mut ctx = ItemContext {
+                item_ent,
+                inv,
+                input,
+                time: &time,
+                props: gun_props.get(&item.props),
+                recoil: &mut recoil,
+                cooldowns: &mut cooldowns,
+                gun: gun.as_deref_mut(),
+                rng: &mut rng,
+                state_events: &mut state_events,
+            };
+            item.modify(&mut ctx);
         }
     }
 }
 
+pub fn init_gun_sys(
+    mut commands: Commands,
+    gun_props: Res<Assets<GunProps>>,
+    item_query: Query<(Entity, &Item), Without<Gun>>,
+) {
+    for (item_ent, item) in item_query.iter() {
+        let Some(props) = gun_props.get(&item.props) else { continue; };
+        if !props.uses_ammo() { continue; }
+        commands.entity(item_ent).insert(Gun {
+            ammo: props.mag_size,
+            ammo_in_reserve: props.starting_ammo_in_reserve,
+        });
+    }
+}
+
 pub fn item_pickup_sys(
+    time: Res<GameTime>,
     phys_ctx: Res<RapierContext>,
+    asset_server: Res<AssetServer>,
+    gun_props: Res<Assets<GunProps>>,
+    pickup_mode: Res<PickupMode>,
     mut commands: Commands,
-    mut inv_query: Query<&mut Inventory>,
+    mut interact_events: EventReader<InteractEvent>,
+    mut inv_query: Query<&mut Inventory, Without<Respawning>>,
     mut item_query: Query<&mut Item>,
-    mut pickup_query: Query<&mut ItemPickup>,
+    pickup_query: Query<&ItemPickup, Without<PickupCooldown>>,
+    mut cooldown_query: Query<(Entity, &mut PickupCooldown)>,
 ) {
-    for (ent1, ent2, _inter) in phys_ctx.intersection_pairs() {
-        let mut pickup_ent: Option<Entity> = None;
-        let mut player_ent: Option<Entity> = None;
-        if pickup_query.get(ent1).is_ok() && inv_query.get(ent2).is_ok() {
-            pickup_ent = Some(ent1);
-            player_ent = Some(ent2);
-        } else if pickup_query.get(ent2).is_ok() && inv_query.get(ent1).is_ok() {
-            pickup_ent = Some(ent2);
-            player_ent = Some(ent1);
-        }
-        if let Some(pickup_ent) = pickup_ent {
-            if let Some(player_ent) = player_ent {
-                let pickup = pickup_query.get_mut(pickup_ent).unwrap();
-                let mut inv = inv_query.get_mut(player_ent).unwrap();
-                inv.push_item(player_ent, &mut commands, &mut item_query, &pickup.item_name);
-                commands.entity(pickup_ent).despawn_recursive();
-            }
+    for (pickup_ent, mut cooldown) in cooldown_query.iter_mut() {
+        cooldown.0 = cooldown.0.saturating_sub(time.delta());
+        if cooldown.0.is_zero() {
+            commands.entity(pickup_ent).remove::<PickupCooldown>();
+        }
+    }
+
+    // (pickup, player)
+    let mut pickups: Vec<(Entity, Entity)> = interact_events.read()
+        .map(|event| (event.target, event.player))
+        .collect();
+    if *pickup_mode == PickupMode::Auto {
+        for (ent1, ent2, _inter) in phys_ctx.intersection_pairs() {
+            // Sensors of thrown pickups are children of their rigid body
+            let ent1 = phys_ctx.collider_parent(ent1).unwrap_or(ent1);
+            let ent2 = phys_ctx.collider_parent(ent2).unwrap_or(ent2);
+            pickups.push((ent1, ent2));
+            pickups.push((ent2, ent1));
+        }
+    }
+
+    let mut taken: Vec<Entity> = Vec::new();
+    for (pickup_ent, player_ent) in pickups {
+        if taken.contains(&pickup_ent) { continue; }
+        let Ok(pickup) = pickup_query.get(pickup_ent) else { continue; };
+        let Ok(mut inv) = inv_query.get_mut(player_ent) else { continue; };
+        // Left in the world to be tried again, pushing never partially succeeds
+        if let Err(err) = inv.push_item(player_ent, &mut commands, &asset_server, &gun_props, &mut item_query, pickup) {
+            debug!("Not picking up {}: {}", pickup.item_name, err);
+            continue;
+        }
+        commands.entity(pickup_ent).despawn_recursive();
+        taken.push(pickup_ent);
+    }
+}
+
+pub fn pickup_mode_command_sys(
+    mut command_events: EventReader<ConsoleCommandEvent>,
+    mut pickup_mode: ResMut<PickupMode>,
+) {
+    for command in command_events.read() {
+        if command.name != "pickup_mode" { continue; }
+        match command.args.first().map(|arg| arg.as_str()) {
+            Some("auto") => *pickup_mode = PickupMode::Auto,
+            Some("manual") => *pickup_mode = PickupMode::Manual,
+            _ => warn!("Usage: pickup_mode <auto|manual>"),
         }
     }
 }
 
+pub fn drop_item_sys(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut inv_query: Query<(&PlayerInput, &Transform, &PlayerController, &mut Inventory)>,
+    mut item_query: Query<&mut Item>,
+    gun_query: Query<&Gun>,
+) {
+    for (input, transform, controller, mut inv) in inv_query.iter_mut() {
+        if !input.flags.contains(PlayerInputFlags::Drop) { continue; }
+        let Some(slot) = inv.equipped_slot else { continue; };
+        let Some(item_ent) = inv.item_ents.0[slot as usize] else { continue; };
+        let Ok(item) = item_query.get(item_ent) else { continue; };
+        let item_name = item.name.clone();
+        let pickup = ItemPickup { item_name: item_name.clone(), amount: item.amount, gun: gun_query.get(item_ent).ok().copied() };
+
+        inv.item_ents.0[slot as usize] = None;
+        commands.entity(item_ent).despawn_recursive();
+        if inv.prev_equipped_slot == Some(slot) {
+            inv.prev_equipped_slot = None;
+        }
+        inv.equipped_slot = inv.find_slot(&mut item_query, |item| item.is_some());
+        inv.equip_state_dur = Duration::ZERO;
+        inv.equip_state_name = EquipStateName::from(if inv.equipped_slot.is_some() {
+            EQUIPPING_STATE
+        } else {
+            UNEQUIPPED_STATE
+        });
+
+        let eye = eye_transform(transform, controller);
+        let fwd = eye.forward();
+        commands.spawn((
+            TransformBundle::from(Transform::from_translation(eye.translation + fwd)),
+            VisibilityBundle::default(),
+            RigidBody::Dynamic,
+            Collider::ball(0.15),
+            LockedAxes::ROTATION_LOCKED,
+            Velocity::linear(fwd * DROP_THROW_SPEED + Vec3::Y * 2.0),
+            pickup,
+            pickup_interactable(&item_name),
+            PickupCooldown(DROP_PICKUP_COOLDOWN),
+        )).with_children(|parent| {
+            parent.spawn((
+                TransformBundle::default(),
+                Collider::ball(0.5),
+                Sensor,
+            ));
+            parent.spawn((
+                SceneBundle {
+                    scene: asset_server.load(format!("models/{}.glb#Scene0", item_name)),
+                    ..default()
+                },
+                ItemPickupVisual,
+            ));
+        });
+    }
+}
+
 impl Default for Inventory {
     fn default() -> Self {
         Self {
@@ -251,55 +721,183 @@ impl Default for Inventory {
 }
 
 impl Item {
-    fn start_state(&mut self, _inv: &Inventory, state: ItemStateName, dur: Duration) {
+    fn start_state(&mut self, ctx: &mut ItemContext, state: ItemStateName, dur: Duration) {
         self.state_name = state;
         self.state_dur = dur;
+        self.state_dur_scale = 1.0;
         match self.state_name.as_str() {
             FIRE_STATE => {
-                println!("Boom!");
+                self.is_fire_press_pending = false;
+                if let FireMode::Burst(burst_size) = self.fire_mode(ctx.props) {
+                    if self.burst_remaining == 0 {
+                        self.burst_remaining = burst_size;
+                    }
+                    self.burst_remaining = self.burst_remaining.saturating_sub(1);
+                }
+                if let Some(gun) = ctx.gun.as_deref_mut() {
+                    gun.ammo = gun.ammo.saturating_sub(1);
+                }
+                if let Some(props) = ctx.props {
+                    // Symmetric so sustained fire still averages the configured rate
+                    let jitter = props.weapon_props.fire_jitter;
+                    self.state_dur_scale = 1.0 + ctx.rng.range(-jitter, jitter);
+                    ctx.cooldowns.trigger(Action::Fire, &props.weapon_props.item_props.cooldowns);
+                    ctx.recoil.impulse.x += props.recoil;
+                    ctx.recoil.impulse.y += props.recoil * ctx.rng.range(-0.25, 0.25);
+                }
             }
             _ => {}
         }
+        ctx.state_events.send(ItemStateEvent {
+            item_ent: ctx.item_ent,
+            inv_ent: self.inv_ent,
+            state_name: self.state_name.clone(),
+        });
+    }
+
+    pub fn fire_mode(&self, props: Option<&GunProps>) -> FireMode {
+        props.and_then(|props| props.fire_modes.get(self.fire_mode_index)).copied().unwrap_or(FireMode::Auto)
+    }
+
+    /// Whether input asks for a shot, according to the fire mode
+    fn wants_fire(&self, props: Option<&GunProps>) -> bool {
+        match self.fire_mode(props) {
+            FireMode::Semi => self.is_fire_press_pending,
+            FireMode::Burst(_) => self.is_fire_press_pending || self.burst_remaining > 0,
+            FireMode::Auto => self.is_fire_held,
+        }
     }
 
-    fn can_fire(&mut self, inv: &Inventory, at_state_end: bool) -> bool {
+    fn can_fire(&mut self, inv: &Inventory, cooldowns: &ActionCooldowns, gun: Option<&Gun>, props: Option<&GunProps>, at_state_end: bool) -> bool {
+        if !cooldowns.is_ready(Action::Fire) { return false; }
+        // Items without a magazine never run dry
+        let uses_ammo = props.is_some_and(GunProps::uses_ammo);
+        if uses_ammo && gun.map_or(true, |gun| gun.ammo == 0) { return false; }
         match (inv.equip_state_name.as_str(), self.state_name.as_str(), at_state_end) {
             (EQUIPPED_STATE, FIRE_STATE, true) | (EQUIPPED_STATE, IDLE_STATE, _) => true,
             _ => false,
         }
     }
 
-    fn modify_status(&mut self, inv: &Inventory, input: &PlayerInput, time: &Res<Time>) {
-        while self.state_dur > Duration::from_millis(2000) {
+    fn modify_status(&mut self, ctx: &mut ItemContext) {
+        self.state_dur = self.state_dur.saturating_add(ctx.time.delta());
+        let states = ctx.props.map(|props| &props.weapon_props.item_props.states);
+        loop {
+            let (duration, is_persistent) = state_timing(states, &self.state_name);
+            let duration = duration.mul_f32(self.state_dur_scale);
+            if self.state_dur < duration { break; }
+            if is_persistent {
+                // Persistent states loop or hold, only input moves us out of them
+                if !duration.is_zero() {
+                    self.state_dur = Duration::from_nanos((self.state_dur.as_nanos() % duration.as_nanos()) as u64);
+                }
+                break;
+            }
             // We have just finished a state
-            self.end_status(inv, input, time);
-            let next_state = self.next_state(inv, input);
-            self.start_state(inv, next_state, self.state_dur - Duration::from_millis(2000));
+            self.end_status(ctx);
+            let next_state = self.next_state(ctx.inv, ctx.cooldowns, ctx.gun.as_deref(), ctx.props);
+            self.start_state(ctx, next_state, self.state_dur - duration);
+            // Instant states would otherwise transition forever
+            if duration.is_zero() { break; }
         }
-        self.state_dur = self.state_dur.saturating_add(time.delta());
     }
 
-    fn next_state(&mut self, inv: &Inventory, input: &PlayerInput) -> ItemStateName {
-        let do_fire = input.flags.contains(PlayerInputFlags::Fire) && self.can_fire(inv, true);
+    fn next_state(&mut self, inv: &Inventory, cooldowns: &ActionCooldowns, gun: Option<&Gun>, props: Option<&GunProps>) -> ItemStateName {
+        let do_fire = self.wants_fire(props) && self.can_fire(inv, cooldowns, gun, props, true);
         match (self.state_name.as_str(), do_fire) {
             (FIRE_STATE, true) => ItemStateName::from(FIRE_STATE),
             _ => ItemStateName::from(IDLE_STATE)
         }
     }
 
-    fn end_status(&mut self, _inv: &Inventory, _input: &PlayerInput, _time: &Res<Time>) {}
+    fn end_status(&mut self, ctx: &mut ItemContext) {
+        if self.state_name != RELOAD_STATE { return; }
+        // Only a reload that runs to completion fills the magazine
+        if let (Some(gun), Some(props)) = (ctx.gun.as_deref_mut(), ctx.props) {
+            let moved = props.mag_size.saturating_sub(gun.ammo).min(gun.ammo_in_reserve);
+            gun.ammo += moved;
+            gun.ammo_in_reserve -= moved;
+        }
+    }
+
+    fn modify(&mut self, ctx: &mut ItemContext) {
+        if ctx.input.flags.contains(PlayerInputFlags::CycleFireMode) {
+            let mode_count = ctx.props.map_or(0, |props| props.fire_modes.len());
+            if mode_count > 0 {
+                self.fire_mode_index = (self.fire_mode_index + 1) % mode_count;
+            }
+            self.is_fire_press_pending = false;
+            self.burst_remaining = 0;
+        }
+        let is_fire_held = ctx.input.flags.contains(PlayerInputFlags::Fire);
+        if is_fire_held && !self.is_fire_held {
+            self.is_fire_press_pending = true;
+        }
+        self.is_fire_held = is_fire_held;
+
+        if self.state_name == RELOAD_STATE {
+            let wants_fire = ctx.input.flags.contains(PlayerInputFlags::Fire) && ctx.cooldowns.is_ready(Action::Fire);
+            let is_switching = ctx.inv.equip_state_name == UNEQUIPPING_STATE;
+            if wants_fire || is_switching {
+                // Skips end_status, so no ammo is credited
+                self.start_state(ctx, ItemStateName::from(IDLE_STATE), Duration::ZERO);
+            }
+        }
+        if self.wants_fire(ctx.props) && self.can_fire(ctx.inv, ctx.cooldowns, ctx.gun.as_deref(), ctx.props, false) {
+            self.start_state(ctx, ItemStateName::from(FIRE_STATE), Duration::ZERO);
+        } else if ctx.input.flags.contains(PlayerInputFlags::Reload)
+            && self.state_name != RELOAD_STATE && ctx.inv.equip_state_name == EQUIPPED_STATE {
+            self.start_state(ctx, ItemStateName::from(RELOAD_STATE), Duration::ZERO);
+        }
+        self.modify_status(ctx);
+    }
+}
+
+impl ItemSound {
+    pub fn event(&self, asset_server: &AssetServer, category: SoundCategory, position: Vec3) -> PlaySoundEvent {
+        PlaySoundEvent {
+            source: asset_server.load(self.path.clone()),
+            category,
+            volume: self.volume,
+            speed: 1.0,
+            position: Some(position),
+            falloff: self.falloff,
+        }
+    }
+}
+
+impl ActionCooldowns {
+    #[inline]
+    pub fn is_ready(&self, action: Action) -> bool {
+        self.0.get(&action).map_or(true, Duration::is_zero)
+    }
+
+    /// Start the cooldown for an action using the durations from item props
+    pub fn trigger(&mut self, action: Action, cooldowns: &HashMap<Action, Duration>) {
+        if let Some(&cooldown) = cooldowns.get(&action) {
+            self.0.insert(action, cooldown);
+        }
+    }
 
-    fn modify(&mut self, inv: &Inventory, input: &PlayerInput, time: &Res<Time>) {
-        if input.flags.contains(PlayerInputFlags::Fire) && self.can_fire(inv, false) {
-            self.start_state(inv, ItemStateName::from(FIRE_STATE), Duration::ZERO);
-        } else if input.flags.contains(PlayerInputFlags::Reload) {
-            self.start_state(inv, ItemStateName::from(RELOAD_STATE), Duration::ZERO);
+    pub fn tick(&mut self, delta: Duration) {
+        for remaining in self.0.values_mut() {
+            *remaining = remaining.saturating_sub(delta);
         }
-        self.modify_status(inv, input, time);
     }
 }
 
 impl Inventory {
+    pub fn equipped_item_ent(&self) -> Option<Entity> {
+        self.equipped_slot.and_then(|slot| self.item_ents.0[slot as usize])
+    }
+
+    /// Props loaded from `items/<name>.item.ron` of the item in the equipped slot
+    pub fn equipped_props<'a>(&self, item_query: &Query<&mut Item>, gun_props: &'a Assets<GunProps>) -> Option<&'a GunProps> {
+        self.equipped_item_ent()
+            .and_then(|item_ent| item_query.get(item_ent).ok())
+            .and_then(|item| gun_props.get(&item.props))
+    }
+
     fn find_replacement(&self, item_query: &mut Query<&mut Item>) -> Option<u8> {
         if self.prev_equipped_slot.is_none() {
             self.find_slot(item_query, |item| item.is_some())
@@ -328,20 +926,43 @@ impl Inventory {
         &mut self,
         inv_ent: Entity,
         commands: &mut Commands,
+        asset_server: &AssetServer,
+        gun_props: &Assets<GunProps>,
         item_query: &mut Query<&mut Item>,
-        item_name: &ItemName,
-    ) {
-        let open_slot = self.find_slot(item_query, |item| item.is_none());
-        if let Some(open_slot) = open_slot {
-            self.set_item(inv_ent, commands, item_name, open_slot);
+        pickup: &ItemPickup,
+    ) -> Result<(), PushItemError> {
+        let item_name = &pickup.item_name;
+        let props: Handle<GunProps> = asset_server.load(item_props_path(item_name));
+        let Some(props) = gun_props.get(&props) else {
+            return Err(PushItemError::PropsNotLoaded(item_name.clone()));
+        };
+        let max_stack = props.weapon_props.item_props.max_stack;
+        if max_stack > 1 {
+            for &item_ent in self.item_ents.0.iter().flatten() {
+                if let Ok(mut item) = item_query.get_mut(item_ent) {
+                    if item.name == *item_name && item.amount + pickup.amount <= max_stack {
+                        item.amount += pickup.amount;
+                        return Ok(());
+                    }
+                }
+            }
         }
+
+        let open_slot = self.find_slot(item_query, |item| item.is_none()).ok_or(PushItemError::Full)?;
+        self.set_item(inv_ent, commands, asset_server, item_name, open_slot, pickup.amount);
+        if let (Some(item_ent), Some(gun)) = (self.item_ents.0[open_slot as usize], pickup.gun) {
+            // Lands before init_gun_sys would fill the magazine
+            commands.entity(item_ent).insert(gun);
+        }
+        Ok(())
     }
 
     pub fn set_item(
         &mut self,
         inv_ent: Entity,
         commands: &mut Commands,
-        item_name: &ItemName, slot: u8,
+        asset_server: &AssetServer,
+        item_name: &ItemName, slot: u8, amount: u16,
     ) -> &mut Self {
         let existing_item_ent = self.item_ents.0[slot as usize];
         if let Some(existing_item_ent) = existing_item_ent {
@@ -349,11 +970,17 @@ impl Inventory {
         }
         let item_ent = commands.spawn(Item {
             name: item_name.clone(),
-            amount: 1,
+            amount,
             state_name: ItemStateName::from(IDLE_STATE),
             state_dur: Duration::ZERO,
+            state_dur_scale: 1.0,
+            fire_mode_index: 0,
+            is_fire_press_pending: false,
+            is_fire_held: false,
+            burst_remaining: 0,
             inv_ent,
             inv_slot: slot,
+            props: asset_server.load(item_props_path(item_name)),
         }).id();
         if self.equipped_slot.is_none() {
             self.equipped_slot = Some(slot);
@@ -374,35 +1001,142 @@ impl Inventory {
 
 pub fn render_inventory_sys(
     mut commands: Commands,
+    time: Res<Time>,
     asset_server: Res<AssetServer>,
-    item_query: Query<&mut Item>,
-    player_query: Query<&Inventory>,
-    camera_query: Query<&Transform, With<Projection>>,
+    physics_context: Res<RapierContext>,
+    pullback: Res<ViewModelPullback>,
+    pose: Res<ViewModelPose>,
+    feel: Res<ViewModelFeel>,
+    mut item_query: Query<(&Item, Option<&Handle<Scene>>, Option<&mut Transform>, Option<&mut Visibility>), Without<RenderPlayer>>,
+    mut player_query: Query<(Entity, &Inventory, &PlayerInput, &PlayerController, &LogicalPlayer, &mut AimProgress, &mut ViewModelMotion)>,
+    camera_query: Query<(&Transform, &RenderPlayer)>,
 ) {
-    for inv in player_query.iter() {
+    let dt = time.delta_seconds();
+    // Frame rate independent exponential ease
+    let ease = 1.0 - (-pose.aim_ease_rate * dt).exp();
+    for (inv_ent, inv, input, controller, player, mut aim_progress, mut motion) in player_query.iter_mut() {
+        // Only players with a camera of their own, such as local split-screen players, hold a view model
+        let camera_transform = camera_query.iter()
+            .find(|(_, render_player)| render_player.0 == player.0)
+            .map(|(transform, _)| transform);
+        let target_progress = if input.flags.contains(PlayerInputFlags::Aim) { 1.0 } else { 0.0 };
+        aim_progress.0 += (target_progress - aim_progress.0) * ease;
+        // Keep sights steady while aiming
+        let motion_offset = motion.update(&feel, input, controller, dt) * (1.0 - aim_progress.0 * 0.8);
+        let offset = pose.hip_offset.lerp(pose.aim_offset, aim_progress.0) + motion_offset;
         for item in inv.item_ents.0.iter() {
             if let Some(item_ent) = item {
-                if let Ok(item) = item_query.get(*item_ent) {
+                if let Ok((item, scene, item_transform, visibility)) = item_query.get_mut(*item_ent) {
                     let is_equipped = inv.equipped_slot == Some(item.inv_slot);
                     let mut transform = Transform::default();
                     let scene_handle = asset_server.load(format!("models/{}.glb#Scene0", item.name));
-                    if is_equipped {
-                        transform = camera_query.single().mul_transform(Transform::from_xyz(0.4, -0.3, -1.0));
+                    if let Some(camera_transform) = camera_transform.filter(|_| is_equipped) {
+                        let filter = QueryFilter::default().exclude_collider(inv_ent).exclude_sensors();
+                        let wall_dist = physics_context.cast_ray(
+                            camera_transform.translation, camera_transform.forward(), pullback.cast_distance, true, filter,
+                        ).map(|(_, toi)| toi);
+                        let pullback = pullback.pullback(wall_dist);
+                        // Lower a little too so it looks like the weapon is being held away from the wall
+                        transform = camera_transform.mul_transform(Transform::from_translation(offset + Vec3::new(0.0, -pullback * 0.25, pullback)));
                     }
-                    commands.entity(*item_ent).insert(
-                        SceneBundle {
-                            scene: scene_handle,
-                            transform,
-                            visibility: Visibility::Visible,
-                            ..default()
+                    match (scene, item_transform, visibility) {
+                        // Inserting the scene again would respawn it, along with the animation player
+                        (Some(scene), Some(mut item_transform), Some(mut visibility)) if *scene == scene_handle => {
+                            item_transform.set_if_neq(transform);
+                            visibility.set_if_neq(Visibility::Visible);
+                        }
+                        _ => {
+                            commands.entity(*item_ent).insert(
+                                SceneBundle {
+                                    scene: scene_handle,
+                                    transform,
+                                    visibility: Visibility::Visible,
+                                    ..default()
+                                }
+                            );
                         }
-                    );
+                    }
                 }
             }
         }
     }
 }
 
+/// Play the sounds of item states and equip states as they start, from the eye of the holder
+pub fn item_sound_sys(
+    asset_server: Res<AssetServer>,
+    gun_props: Res<Assets<GunProps>>,
+    mut rng: ResMut<GameRng>,
+    mut state_events: EventReader<ItemStateEvent>,
+    mut equip_events: EventReader<EquipStateEvent>,
+    mut sound_events: EventWriter<PlaySoundEvent>,
+    item_query: Query<&Item>,
+    player_query: Query<(&Transform, &PlayerController)>,
+) {
+    for event in state_events.read() {
+        let Ok(item) = item_query.get(event.item_ent) else { continue; };
+        let Some(props) = gun_props.get(&item.props) else { continue; };
+        let Some(sound) = props.weapon_props.item_props.sounds.get(&event.state_name) else { continue; };
+        let Ok((transform, controller)) = player_query.get(event.inv_ent) else { continue; };
+        let mut sound_event = sound.event(&asset_server, SoundCategory::Weapon, eye_transform(transform, controller).translation);
+        if event.state_name == FIRE_STATE {
+            let jitter = props.weapon_props.fire_jitter;
+            sound_event.speed = 1.0 + rng.range(-jitter, jitter);
+        }
+        sound_events.send(sound_event);
+    }
+
+    for event in equip_events.read() {
+        let Ok(item) = item_query.get(event.item_ent) else { continue; };
+        let Some(props) = gun_props.get(&item.props) else { continue; };
+        let Some(sound) = props.weapon_props.item_props.sounds.get(&event.state_name) else { continue; };
+        let Ok((transform, controller)) = player_query.get(event.inv_ent) else { continue; };
+        sound_events.send(sound.event(&asset_server, SoundCategory::Weapon, eye_transform(transform, controller).translation));
+    }
+}
+
+/// Play the animation clips of item states and equip states on the view model as they start
+pub fn item_animation_sys(
+    asset_server: Res<AssetServer>,
+    gun_props: Res<Assets<GunProps>>,
+    gltfs: Res<Assets<Gltf>>,
+    clips: Res<Assets<AnimationClip>>,
+    mut state_events: EventReader<ItemStateEvent>,
+    mut equip_events: EventReader<EquipStateEvent>,
+    item_query: Query<&Item>,
+    children_query: Query<&Children>,
+    mut animation_query: Query<&mut AnimationPlayer>,
+) {
+    // (item, state, whether it is an equip state)
+    let started = state_events.read()
+        .map(|event| (event.item_ent, event.state_name.clone(), false))
+        .chain(equip_events.read().map(|event| (event.item_ent, event.state_name.clone(), true)));
+
+    for (item_ent, state_name, is_equip_state) in started {
+        let Ok(item) = item_query.get(item_ent) else { continue; };
+        let Some(props) = gun_props.get(&item.props) else { continue; };
+        let item_props = &props.weapon_props.item_props;
+        let Some(clip_name) = item_props.animations.get(&state_name) else { continue; };
+        let gltf_handle: Handle<Gltf> = asset_server.load(format!("models/{}.glb", item.name));
+        let Some(clip_handle) = gltfs.get(&gltf_handle).and_then(|gltf| gltf.named_animations.get(clip_name.as_str())) else { continue; };
+        let Some(clip) = clips.get(clip_handle) else { continue; };
+
+        let states = if is_equip_state { &item_props.equip_states } else { &item_props.states };
+        let (duration, is_persistent) = state_timing(Some(states), &state_name);
+        let duration = duration.as_secs_f32() * item.state_dur_scale;
+        let speed = if duration > 0.0 { clip.duration() / duration } else { 1.0 };
+        // The scene puts the player somewhere under the item, once it has spawned
+        for ent in children_query.iter_descendants(item_ent) {
+            let Ok(mut animation_player) = animation_query.get_mut(ent) else { continue; };
+            // Start over even when the same clip is still playing, such as for consecutive shots
+            animation_player.start(clip_handle.clone()).set_speed(speed);
+            if is_persistent {
+                animation_player.repeat();
+            }
+        }
+    }
+}
+
 pub fn item_pickup_animate_sys(
     time: Res<Time>,
     mut pickup_query: Query<&mut Transform, With<ItemPickupVisual>>,
@@ -414,3 +1148,330 @@ pub fn item_pickup_animate_sys(
         transform.translation = Vec3::new(0.0, height, 0.0);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::{event::ManualEventReader, system::SystemState};
+
+    use super::*;
+
+    fn rifle_props() -> GunProps {
+        let mut props: GunProps = ron::from_str(include_str!("../../assets/items/rifle.item.ron")).unwrap();
+        // Keeps state durations exact, jitter has its own test
+        props.weapon_props.fire_jitter = 0.0;
+        props
+    }
+
+    /// Player with a rifle in each of the first slots, equipping the first one
+    struct ItemTest {
+        app: App,
+        player: Entity,
+        items: Vec<Entity>,
+    }
+
+    impl ItemTest {
+        fn new(props: GunProps, item_count: u8) -> Self {
+            let mut gun_props = Assets::<GunProps>::default();
+            let props = gun_props.add(props);
+            let mut app = App::new();
+            app
+                .add_event::<ItemStateEvent>()
+                .add_event::<EquipStateEvent>()
+                .init_resource::<GameRng>()
+                .init_resource::<GameTime>()
+                .insert_resource(gun_props)
+                .add_systems(Update, (modify_equip_state_sys, modify_item_sys, init_gun_sys).chain());
+
+            let player = app.world.spawn((PlayerInput::default(), Recoil::default(), ActionCooldowns::default())).id();
+            let mut inv = Inventory::default();
+            let items = (0..item_count).map(|slot| {
+                let item_ent = app.world.spawn(Item {
+                    name: ItemName::from("rifle"),
+                    amount: 1,
+                    state_name: ItemStateName::from(IDLE_STATE),
+                    state_dur: Duration::ZERO,
+                    state_dur_scale: 1.0,
+                    fire_mode_index: 0,
+                    is_fire_press_pending: false,
+                    is_fire_held: false,
+                    burst_remaining: 0,
+                    inv_ent: player,
+                    inv_slot: slot,
+                    props: props.clone(),
+                }).id();
+                inv.item_ents.0[slot as usize] = Some(item_ent);
+                item_ent
+            }).collect();
+            inv.equipped_slot = Some(0);
+            inv.equip_state_name = EquipStateName::from(EQUIPPING_STATE);
+            app.world.entity_mut(player).insert(inv);
+            Self { app, player, items }
+        }
+
+        /// Run the item systems once after `millis` of game time
+        fn step(&mut self, millis: u64) {
+            self.app.world.resource_mut::<GameTime>().advance(Duration::from_millis(millis));
+            self.app.update();
+        }
+
+        fn input(&mut self) -> Mut<PlayerInput> {
+            self.app.world.get_mut::<PlayerInput>(self.player).unwrap()
+        }
+
+        fn inv(&self) -> &Inventory {
+            self.app.world.get::<Inventory>(self.player).unwrap()
+        }
+
+        fn item(&self, index: usize) -> &Item {
+            self.app.world.get::<Item>(self.items[index]).unwrap()
+        }
+
+        fn gun(&self, index: usize) -> Gun {
+            *self.app.world.get::<Gun>(self.items[index]).unwrap()
+        }
+
+        /// Fire one shot and let it finish, starting from idle
+        fn fire_once(&mut self) {
+            self.input().flags = PlayerInputFlags::Fire.into();
+            self.step(0);
+            self.input().flags = Default::default();
+            self.step(100);
+        }
+    }
+
+    #[test]
+    fn equip_states_last_configured_duration() {
+        let mut test = ItemTest::new(rifle_props(), 2);
+        test.step(400);
+        assert_eq!(test.inv().equip_state_name, EQUIPPING_STATE);
+        test.step(100);
+        assert_eq!(test.inv().equip_state_name, EQUIPPED_STATE);
+
+        test.input().wanted_item_slot = Some(1);
+        test.step(0);
+        assert_eq!(test.inv().equip_state_name, UNEQUIPPING_STATE);
+        test.step(249);
+        assert_eq!(test.inv().equipped_slot, Some(0));
+        test.step(1);
+        assert_eq!(test.inv().equipped_slot, Some(1));
+        assert_eq!(test.inv().equip_state_name, EQUIPPING_STATE);
+    }
+
+    #[test]
+    fn item_states_last_configured_duration() {
+        let mut test = ItemTest::new(rifle_props(), 1);
+        test.step(500);
+        test.input().flags = PlayerInputFlags::Fire.into();
+        test.step(0);
+        assert_eq!(test.item(0).state_name, FIRE_STATE);
+
+        test.input().flags = Default::default();
+        test.step(99);
+        assert_eq!(test.item(0).state_name, FIRE_STATE);
+        test.step(1);
+        assert_eq!(test.item(0).state_name, IDLE_STATE);
+        // Idle is persistent, so it holds well past its duration
+        test.step(5000);
+        assert_eq!(test.item(0).state_name, IDLE_STATE);
+    }
+
+    #[test]
+    fn missing_state_props_finish_immediately() {
+        let mut states = HashMap::new();
+        states.insert(ItemStateName::from(IDLE_STATE), ItemStateProps { duration: Duration::from_secs(1), is_persistent: true });
+        assert_eq!(state_timing(Some(&states), IDLE_STATE), (Duration::from_secs(1), true));
+        assert_eq!(state_timing(Some(&states), FIRE_STATE), (Duration::ZERO, false));
+        assert_eq!(state_timing(None, IDLE_STATE), (Duration::ZERO, false));
+    }
+
+    #[test]
+    fn cooldowns_tick_down_to_ready() {
+        let mut cooldowns = ActionCooldowns::default();
+        let mut durations = HashMap::new();
+        durations.insert(Action::Fire, Duration::from_millis(100));
+        cooldowns.trigger(Action::Fire, &durations);
+        // Actions without a configured cooldown are never limited
+        cooldowns.trigger(Action::Switch, &durations);
+        assert!(!cooldowns.is_ready(Action::Fire));
+        assert!(cooldowns.is_ready(Action::Switch));
+        cooldowns.tick(Duration::from_millis(60));
+        assert!(!cooldowns.is_ready(Action::Fire));
+        cooldowns.tick(Duration::from_millis(60));
+        assert!(cooldowns.is_ready(Action::Fire));
+    }
+
+    #[test]
+    fn switch_waits_for_cooldown() {
+        let mut test = ItemTest::new(rifle_props(), 2);
+        test.step(500);
+        test.input().wanted_item_slot = Some(1);
+        test.step(10);
+        test.step(250);
+        assert_eq!(test.inv().equipped_slot, Some(1));
+
+        // Switching back is requested 260ms into the 300ms switch cooldown
+        test.input().wanted_item_slot = Some(0);
+        test.step(10);
+        assert_eq!(test.inv().equip_state_name, EQUIPPING_STATE);
+        test.step(30);
+        assert_eq!(test.inv().equip_state_name, UNEQUIPPING_STATE);
+    }
+
+    #[test]
+    fn pullback_grows_as_wall_gets_closer() {
+        let pullback = ViewModelPullback { cast_distance: 1.0, max_pullback: 0.6 };
+        assert_eq!(pullback.pullback(None), 0.0);
+        assert_eq!(pullback.pullback(Some(1.0)), 0.0);
+        assert!((pullback.pullback(Some(0.5)) - 0.3).abs() < 1e-6);
+        assert_eq!(pullback.pullback(Some(0.0)), 0.6);
+    }
+
+    #[test]
+    fn fire_interval_jitters_within_range() {
+        let mut props = rifle_props();
+        props.weapon_props.fire_jitter = 0.05;
+        let mut test = ItemTest::new(props, 1);
+        test.step(500);
+        test.input().flags = PlayerInputFlags::Fire.into();
+        let mut scales = Vec::new();
+        for _ in 0..2000 {
+            // Keep the magazine topped up so no reload interrupts firing
+            test.app.world.get_mut::<Gun>(test.items[0]).unwrap().ammo = 30;
+            test.step(10);
+            let item = test.item(0);
+            if item.state_name == FIRE_STATE && scales.last() != Some(&item.state_dur_scale) {
+                scales.push(item.state_dur_scale);
+            }
+        }
+        // Each shot got a different scale, otherwise they would have been merged above
+        assert!(scales.len() > 100);
+        assert!(scales.iter().all(|scale| (0.95..1.05).contains(scale)));
+        // Centered on the configured interval, so jitter does not change the fire rate on average
+        let mean = scales.iter().sum::<f32>() / scales.len() as f32;
+        assert!((mean - 1.0).abs() < 0.01, "mean scale {}", mean);
+    }
+
+    #[test]
+    fn finished_reload_fills_magazine() {
+        let mut test = ItemTest::new(rifle_props(), 1);
+        test.step(500);
+        test.fire_once();
+        assert_eq!(test.gun(0).ammo, 29);
+
+        test.input().flags = PlayerInputFlags::Reload.into();
+        test.step(0);
+        test.input().flags = Default::default();
+        assert_eq!(test.item(0).state_name, RELOAD_STATE);
+        test.step(1999);
+        assert_eq!(test.gun(0).ammo, 29);
+        test.step(1);
+        assert_eq!((test.gun(0).ammo, test.gun(0).ammo_in_reserve), (30, 89));
+        assert_eq!(test.item(0).state_name, IDLE_STATE);
+    }
+
+    #[test]
+    fn firing_cancels_reload_without_ammo() {
+        let mut test = ItemTest::new(rifle_props(), 1);
+        test.step(500);
+        test.fire_once();
+        test.input().flags = PlayerInputFlags::Reload.into();
+        test.step(1000);
+
+        test.input().flags = PlayerInputFlags::Fire.into();
+        test.step(0);
+        assert_eq!(test.item(0).state_name, FIRE_STATE);
+        assert_eq!((test.gun(0).ammo, test.gun(0).ammo_in_reserve), (28, 90));
+    }
+
+    #[test]
+    fn switching_cancels_reload_without_ammo() {
+        let mut test = ItemTest::new(rifle_props(), 2);
+        test.step(500);
+        test.fire_once();
+        test.input().flags = PlayerInputFlags::Reload.into();
+        test.step(0);
+        test.input().flags = Default::default();
+
+        test.input().wanted_item_slot = Some(1);
+        test.step(0);
+        assert_eq!(test.item(0).state_name, IDLE_STATE);
+        test.step(3000);
+        assert_eq!((test.gun(0).ammo, test.gun(0).ammo_in_reserve), (29, 90));
+    }
+
+    #[test]
+    fn empty_magazine_does_not_fire() {
+        let mut test = ItemTest::new(rifle_props(), 1);
+        test.step(500);
+        test.app.world.get_mut::<Gun>(test.items[0]).unwrap().ammo = 0;
+        test.input().flags = PlayerInputFlags::Fire.into();
+        test.step(0);
+        assert_eq!(test.item(0).state_name, IDLE_STATE);
+    }
+
+    /// Run [`Inventory::push_item`] as a system would, applying the spawned items to the world
+    fn push(world: &mut World, inv_ent: Entity, inv: &mut Inventory, pickup: &ItemPickup) -> Result<(), PushItemError> {
+        let mut state: SystemState<(Commands, Res<AssetServer>, Res<Assets<GunProps>>, Query<&mut Item>)> = SystemState::new(world);
+        let (mut commands, asset_server, gun_props, mut item_query) = state.get_mut(world);
+        let result = inv.push_item(inv_ent, &mut commands, &asset_server, &gun_props, &mut item_query, pickup);
+        state.apply(world);
+        result
+    }
+
+    #[test]
+    fn pickups_merge_into_stacks_with_room() {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<GunProps>();
+        let world = &mut app.world;
+
+        // Stored under the path push_item loads, as if it had finished loading
+        let name = ItemName::from("grenade");
+        let mut props = rifle_props();
+        props.weapon_props.item_props.max_stack = 3;
+        let handle: Handle<GunProps> = world.resource::<AssetServer>().load(item_props_path(&name));
+        world.resource_mut::<Assets<GunProps>>().insert(handle.id(), props);
+
+        let player = world.spawn_empty().id();
+        let mut inv = Inventory::default();
+        let pickup = |amount| ItemPickup { item_name: name.clone(), amount, gun: None };
+        push(world, player, &mut inv, &pickup(2)).unwrap();
+        push(world, player, &mut inv, &pickup(1)).unwrap();
+        // Would go over the max stack, so it starts a new one
+        push(world, player, &mut inv, &pickup(2)).unwrap();
+        let amounts: Vec<u16> = inv.item_ents.0.iter().flatten()
+            .map(|&item_ent| world.get::<Item>(item_ent).unwrap().amount)
+            .collect();
+        assert_eq!(amounts, [3, 2]);
+
+        for _ in 2..inv.item_ents.0.len() {
+            push(world, player, &mut inv, &pickup(3)).unwrap();
+        }
+        assert!(matches!(push(world, player, &mut inv, &pickup(3)), Err(PushItemError::Full)));
+        push(world, player, &mut inv, &pickup(1)).unwrap();
+        assert_eq!(world.get::<Item>(inv.item_ents.0[1].unwrap()).unwrap().amount, 3);
+
+        let rifle = ItemPickup { item_name: ItemName::from("rifle"), amount: 1, gun: None };
+        assert!(matches!(push(world, player, &mut inv, &rifle), Err(PushItemError::PropsNotLoaded(_))));
+    }
+
+    #[test]
+    fn equip_state_changes_send_events() {
+        let mut test = ItemTest::new(rifle_props(), 2);
+        let mut reader = ManualEventReader::<EquipStateEvent>::default();
+        let mut started = Vec::new();
+        for (millis, wanted_slot) in [(500, None), (0, Some(1)), (250, Some(1))] {
+            test.input().wanted_item_slot = wanted_slot;
+            test.step(millis);
+            let events = test.app.world.resource::<Events<EquipStateEvent>>();
+            started.extend(reader.read(events).map(|event| (event.item_ent, event.state_name.clone())));
+        }
+        assert_eq!(started, [
+            (test.items[0], EquipStateName::from(EQUIPPED_STATE)),
+            (test.items[0], EquipStateName::from(UNEQUIPPING_STATE)),
+            (test.items[0], EquipStateName::from(UNEQUIPPED_STATE)),
+            (test.items[1], EquipStateName::from(EQUIPPING_STATE)),
+        ]);
+    }
+}
@@ -0,0 +1,415 @@
+use bevy::{
+    app::AppExit,
+    prelude::*,
+    window::CursorGrabMode,
+};
+use bevy_rapier3d::prelude::*;
+
+use crate::{Config, ConfigState, GameTime, NetMode, RenderPlayer};
+
+const MENU_BACKGROUND: Color = Color::rgba(0.0, 0.0, 0.0, 0.6);
+const BUTTON_COLOR: Color = Color::rgb(0.2, 0.2, 0.2);
+const BUTTON_HOVERED_COLOR: Color = Color::rgb(0.3, 0.3, 0.3);
+const BUTTON_PRESSED_COLOR: Color = Color::rgb(0.45, 0.45, 0.45);
+const SETTINGS_BACKGROUND: Color = Color::rgb(0.08, 0.08, 0.08);
+
+/// Gameplay only runs while `Playing`, streaming and rendering keep running in every state
+#[derive(States, Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum GameState {
+    /// The world and the local player are only spawned when leaving this state
+    #[default]
+    MainMenu,
+    Playing,
+    Paused,
+}
+
+/// Root UI node of the pause menu, despawned on resume
+#[derive(Component)]
+pub struct PauseMenu;
+
+#[derive(Component)]
+pub struct MainMenu;
+
+/// Shown over the main menu until closed with its back button
+#[derive(Component)]
+pub struct SettingsMenu;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Setting {
+    Sensitivity,
+    ViewDistance,
+}
+
+/// Keys that can be rebound from the settings menu
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum KeyBind {
+    Forward,
+    Back,
+    Left,
+    Right,
+    Jump,
+    Sprint,
+    Crouch,
+    Fire,
+    Reload,
+    Drop,
+    Fly,
+    Noclip,
+}
+
+#[derive(Component, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MenuButton {
+    Play,
+    Settings,
+    Back,
+    Resume,
+    Quit,
+    /// Step a setting up or down
+    Adjust(Setting, i8),
+    /// Wait for the next key press and bind it
+    Rebind(KeyBind),
+}
+
+/// Text showing the current value of a setting or key bind
+#[derive(Component, Copy, Clone, Debug)]
+pub enum SettingText {
+    Setting(Setting),
+    KeyBind(KeyBind),
+}
+
+/// Key bind waiting for a key press
+#[derive(Resource, Default)]
+pub struct Rebinding(pub Option<KeyBind>);
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_state::<GameState>()
+            .init_resource::<Rebinding>()
+            .add_systems(Startup, skip_main_menu_sys)
+            .add_systems(OnEnter(GameState::MainMenu), (spawn_main_menu_sys, release_cursor_sys))
+            .add_systems(OnExit(GameState::MainMenu), (despawn_menu_sys::<MainMenu>, despawn_menu_sys::<SettingsMenu>, grab_cursor_sys))
+            .add_systems(OnEnter(GameState::Paused), (spawn_pause_menu_sys, pause_sys, release_cursor_sys))
+            .add_systems(OnExit(GameState::Paused), (despawn_menu_sys::<PauseMenu>, resume_sys, grab_cursor_sys))
+            .add_systems(Update, (
+                pause_toggle_sys,
+                (menu_button_color_sys, menu_button_sys, rebind_sys, settings_text_sys).chain(),
+                apply_view_distance_sys,
+            ));
+    }
+}
+
+/// Dedicated servers and connecting clients go straight into the game
+pub fn skip_main_menu_sys(net_mode: Res<NetMode>, mut next_state: ResMut<NextState<GameState>>) {
+    if *net_mode != NetMode::Singleplayer {
+        next_state.set(GameState::Playing);
+    }
+}
+
+pub fn pause_toggle_sys(
+    key_input: Res<Input<KeyCode>>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !key_input.just_pressed(KeyCode::Escape) { return; }
+    match state.get() {
+        GameState::Playing => next_state.set(GameState::Paused),
+        GameState::Paused => next_state.set(GameState::Playing),
+        GameState::MainMenu => {}
+    }
+}
+
+/// Stop gameplay time and physics, so timers and bodies pick up where they left off on resume
+pub fn pause_sys(mut game_time: ResMut<GameTime>, mut rapier_config: ResMut<RapierConfiguration>) {
+    game_time.paused = true;
+    rapier_config.physics_pipeline_active = false;
+}
+
+pub fn resume_sys(mut game_time: ResMut<GameTime>, mut rapier_config: ResMut<RapierConfiguration>) {
+    game_time.paused = false;
+    rapier_config.physics_pipeline_active = true;
+}
+
+pub fn release_cursor_sys(mut windows: Query<&mut Window>) {
+    for mut window in windows.iter_mut() {
+        window.cursor.grab_mode = CursorGrabMode::None;
+        window.cursor.visible = true;
+    }
+}
+
+pub fn grab_cursor_sys(mut windows: Query<&mut Window>) {
+    for mut window in windows.iter_mut() {
+        window.cursor.grab_mode = CursorGrabMode::Locked;
+        window.cursor.visible = false;
+    }
+}
+
+fn spawn_button(parent: &mut ChildBuilder, button: MenuButton, label: &str, width: f32) -> Entity {
+    parent.spawn((
+        ButtonBundle {
+            style: Style {
+                width: Val::Px(width),
+                height: Val::Px(width.min(48.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: BUTTON_COLOR.into(),
+            ..default()
+        },
+        button,
+    )).with_children(|parent| {
+        parent.spawn(TextBundle::from_section(label, TextStyle { font_size: 24.0, color: Color::WHITE, ..default() }));
+    }).id()
+}
+
+/// Full screen dimmed node with the given buttons stacked in the middle
+pub fn spawn_menu(commands: &mut Commands, title: &str, buttons: &[(MenuButton, &str)]) -> Entity {
+    commands.spawn(NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            row_gap: Val::Px(12.0),
+            ..default()
+        },
+        background_color: MENU_BACKGROUND.into(),
+        ..default()
+    }).with_children(|parent| {
+        parent.spawn(TextBundle::from_section(title, TextStyle { font_size: 48.0, color: Color::WHITE, ..default() }));
+        for &(button, label) in buttons {
+            spawn_button(parent, button, label, 220.0);
+        }
+    }).id()
+}
+
+pub fn spawn_main_menu_sys(mut commands: Commands) {
+    let buttons = [(MenuButton::Play, "Play"), (MenuButton::Settings, "Settings"), (MenuButton::Quit, "Quit")];
+    let menu = spawn_menu(&mut commands, "qgame", &buttons);
+    commands.entity(menu).insert(MainMenu);
+}
+
+fn spawn_setting_row(parent: &mut ChildBuilder, label: &str, text: SettingText, buttons: impl FnOnce(&mut ChildBuilder)) {
+    parent.spawn(NodeBundle {
+        style: Style {
+            width: Val::Px(420.0),
+            align_items: AlignItems::Center,
+            column_gap: Val::Px(8.0),
+            ..default()
+        },
+        ..default()
+    }).with_children(|parent| {
+        parent.spawn(TextBundle {
+            style: Style { flex_grow: 1.0, ..default() },
+            ..TextBundle::from_section(label, TextStyle { font_size: 20.0, color: Color::WHITE, ..default() })
+        });
+        buttons(parent);
+        parent.spawn((
+            TextBundle {
+                style: Style { width: Val::Px(120.0), ..default() },
+                ..TextBundle::from_section("", TextStyle { font_size: 20.0, color: Color::WHITE, ..default() })
+            },
+            text,
+        ));
+    });
+}
+
+pub fn spawn_settings_menu(commands: &mut Commands) {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(6.0),
+                ..default()
+            },
+            background_color: SETTINGS_BACKGROUND.into(),
+            ..default()
+        },
+        SettingsMenu,
+    )).with_children(|parent| {
+        parent.spawn(TextBundle::from_section("Settings", TextStyle { font_size: 40.0, color: Color::WHITE, ..default() }));
+        for (setting, label) in [(Setting::Sensitivity, "Sensitivity"), (Setting::ViewDistance, "View distance")] {
+            spawn_setting_row(parent, label, SettingText::Setting(setting), |parent| {
+                spawn_button(parent, MenuButton::Adjust(setting, -1), "-", 32.0);
+                spawn_button(parent, MenuButton::Adjust(setting, 1), "+", 32.0);
+            });
+        }
+        for bind in KeyBind::ALL {
+            spawn_setting_row(parent, bind.label(), SettingText::KeyBind(bind), |parent| {
+                spawn_button(parent, MenuButton::Rebind(bind), "Bind", 80.0);
+            });
+        }
+        spawn_button(parent, MenuButton::Back, "Back", 220.0);
+    });
+}
+
+pub fn spawn_pause_menu_sys(mut commands: Commands) {
+    let menu = spawn_menu(&mut commands, "Paused", &[(MenuButton::Resume, "Resume"), (MenuButton::Quit, "Quit")]);
+    commands.entity(menu).insert(PauseMenu);
+}
+
+pub fn despawn_menu_sys<T: Component>(mut commands: Commands, menu_query: Query<Entity, With<T>>) {
+    for menu_ent in menu_query.iter() {
+        commands.entity(menu_ent).despawn_recursive();
+    }
+}
+
+pub fn menu_button_color_sys(
+    mut button_query: Query<(&Interaction, &mut BackgroundColor), (Changed<Interaction>, With<MenuButton>)>,
+) {
+    for (interaction, mut color) in button_query.iter_mut() {
+        *color = match interaction {
+            Interaction::Pressed => BUTTON_PRESSED_COLOR,
+            Interaction::Hovered => BUTTON_HOVERED_COLOR,
+            Interaction::None => BUTTON_COLOR,
+        }.into();
+    }
+}
+
+pub fn menu_button_sys(
+    mut commands: Commands,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut exit_events: EventWriter<AppExit>,
+    mut rebinding: ResMut<Rebinding>,
+    mut configs: ResMut<Assets<Config>>,
+    config_state: Option<Res<ConfigState>>,
+    button_query: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
+    settings_query: Query<Entity, With<SettingsMenu>>,
+) {
+    for (interaction, button) in button_query.iter() {
+        if *interaction != Interaction::Pressed { continue; }
+        match *button {
+            MenuButton::Play | MenuButton::Resume => next_state.set(GameState::Playing),
+            MenuButton::Settings => spawn_settings_menu(&mut commands),
+            MenuButton::Back => {
+                rebinding.0 = None;
+                for settings_ent in settings_query.iter() {
+                    commands.entity(settings_ent).despawn_recursive();
+                }
+            }
+            MenuButton::Quit => exit_events.send(AppExit),
+            MenuButton::Adjust(setting, step) => {
+                let Some(config) = config_state.as_ref().and_then(|state| configs.get_mut(&state.handle)) else { continue; };
+                setting.adjust(config, step);
+            }
+            MenuButton::Rebind(bind) => rebinding.0 = Some(bind),
+        }
+    }
+}
+
+/// Binds the next key pressed while a key bind is waiting, escape cancels
+pub fn rebind_sys(
+    key_input: Res<Input<KeyCode>>,
+    mut rebinding: ResMut<Rebinding>,
+    mut configs: ResMut<Assets<Config>>,
+    config_state: Option<Res<ConfigState>>,
+) {
+    let Some(bind) = rebinding.0 else { return; };
+    let Some(&key) = key_input.get_just_pressed().next() else { return; };
+    rebinding.0 = None;
+    if key == KeyCode::Escape { return; }
+    let Some(config) = config_state.and_then(|state| configs.get_mut(&state.handle)) else { return; };
+    *bind.key_mut(config) = key;
+}
+
+pub fn settings_text_sys(
+    rebinding: Res<Rebinding>,
+    configs: Res<Assets<Config>>,
+    config_state: Option<Res<ConfigState>>,
+    mut text_query: Query<(&SettingText, &mut Text)>,
+) {
+    let Some(config) = config_state.and_then(|state| configs.get(&state.handle)) else { return; };
+    for (setting_text, mut text) in text_query.iter_mut() {
+        let value = match *setting_text {
+            SettingText::Setting(Setting::Sensitivity) => format!("{:.2}", config.sensitivity),
+            SettingText::Setting(Setting::ViewDistance) => format!("{:.0}", config.view_distance),
+            SettingText::KeyBind(bind) if rebinding.0 == Some(bind) => "...".to_string(),
+            SettingText::KeyBind(bind) => format!("{:?}", bind.key(config)),
+        };
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+pub fn apply_view_distance_sys(
+    configs: Res<Assets<Config>>,
+    config_state: Option<Res<ConfigState>>,
+    mut camera_query: Query<&mut Projection, With<RenderPlayer>>,
+) {
+    let Some(config) = config_state.and_then(|state| configs.get(&state.handle)) else { return; };
+    for mut projection in camera_query.iter_mut() {
+        if let Projection::Perspective(perspective) = &mut *projection {
+            if perspective.far != config.view_distance {
+                perspective.far = config.view_distance;
+            }
+        }
+    }
+}
+
+impl Setting {
+    pub fn adjust(self, config: &mut Config, step: i8) {
+        let step = step as f32;
+        match self {
+            Setting::Sensitivity => config.sensitivity = (config.sensitivity + step * 0.05).clamp(0.05, 5.0),
+            Setting::ViewDistance => config.view_distance = (config.view_distance + step * 100.0).clamp(100.0, 5000.0),
+        }
+    }
+}
+
+impl KeyBind {
+    pub const ALL: [KeyBind; 12] = [
+        KeyBind::Forward, KeyBind::Back, KeyBind::Left, KeyBind::Right,
+        KeyBind::Jump, KeyBind::Sprint, KeyBind::Crouch, KeyBind::Fire,
+        KeyBind::Reload, KeyBind::Drop, KeyBind::Fly, KeyBind::Noclip,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            KeyBind::Forward => "Forward",
+            KeyBind::Back => "Back",
+            KeyBind::Left => "Left",
+            KeyBind::Right => "Right",
+            KeyBind::Jump => "Jump",
+            KeyBind::Sprint => "Sprint",
+            KeyBind::Crouch => "Crouch",
+            KeyBind::Fire => "Fire",
+            KeyBind::Reload => "Reload",
+            KeyBind::Drop => "Drop",
+            KeyBind::Fly => "Fly",
+            KeyBind::Noclip => "Noclip",
+        }
+    }
+
+    pub fn key(self, config: &Config) -> KeyCode {
+        let mut config = *config;
+        *self.key_mut(&mut config)
+    }
+
+    pub fn key_mut(self, config: &mut Config) -> &mut KeyCode {
+        match self {
+            KeyBind::Forward => &mut config.key_forward,
+            KeyBind::Back => &mut config.key_back,
+            KeyBind::Left => &mut config.key_left,
+            KeyBind::Right => &mut config.key_right,
+            KeyBind::Jump => &mut config.key_jump,
+            KeyBind::Sprint => &mut config.key_sprint,
+            KeyBind::Crouch => &mut config.key_crouch,
+            KeyBind::Fire => &mut config.key_fire,
+            KeyBind::Reload => &mut config.key_reload,
+            KeyBind::Drop => &mut config.key_drop,
+            KeyBind::Fly => &mut config.key_fly,
+            KeyBind::Noclip => &mut config.key_noclip,
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::{
+    core::cast_slice,
+    prelude::*,
+};
+use thiserror::Error;
+
+use crate::Voxel;
+
+const CACHE_DIR: &str = "cache/chunks";
+/// Bumped whenever the file layout or the meshing shader output changes, older files are ignored
+const CACHE_VERSION: u32 = 1;
+const HEADER_LEN: usize = 4 * 4 + 8;
+
+/// Meshes straight out of the voxels compute shader, before flat shading and coloring
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChunkMeshData {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub uvs: Vec<[f32; 2]>,
+    pub indices: Vec<u32>,
+}
+
+/// Meshed chunks on disk, one file per chunk position tagged with a hash of the voxels and shaders it was meshed from.
+/// A chunk whose voxels hash differently was edited or generated differently, so its file is stale.
+#[derive(Resource)]
+pub struct ChunkMeshCache {
+    pub dir: PathBuf,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Error)]
+pub enum ChunkMeshCacheError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("Truncated or corrupt cache file")]
+    Corrupt,
+}
+
+impl Default for ChunkMeshCache {
+    fn default() -> Self {
+        Self { dir: PathBuf::from(CACHE_DIR), enabled: true }
+    }
+}
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+
+/// FNV-1a continuing from `hash`, stable across runs and builds unlike the std hasher
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+pub fn hash_voxels(voxels: &[Voxel]) -> u64 {
+    fnv1a(FNV_OFFSET, cast_slice::<Voxel, u8>(voxels))
+}
+
+/// Hash of the meshing shader sources, they can be edited while the game runs
+pub fn hash_shaders(sources: &[&str]) -> u64 {
+    sources.iter().fold(FNV_OFFSET, |hash, source| fnv1a(hash, source.as_bytes()))
+}
+
+/// Key a cached mesh is stored under, changes with either the voxels or the shaders
+pub fn mesh_cache_key(voxel_hash: u64, shader_hash: u64) -> u64 {
+    fnv1a(voxel_hash, &shader_hash.to_le_bytes())
+}
+
+impl ChunkMeshCache {
+    fn path(&self, position: IVec3) -> PathBuf {
+        self.dir.join(format!("{}_{}_{}.mesh", position.x, position.y, position.z))
+    }
+
+    /// Cached mesh of the chunk at `position`, none if there is no file for it or it was meshed from other voxels
+    pub fn load(&self, position: IVec3, voxel_hash: u64) -> Result<Option<ChunkMeshData>, ChunkMeshCacheError> {
+        let path = self.path(position);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let bytes = fs::read(&path)?;
+        let data = decode(&bytes, voxel_hash)?;
+        if data.is_none() {
+            self.invalidate(position);
+        }
+        Ok(data)
+    }
+
+    pub fn save(&self, position: IVec3, voxel_hash: u64, data: &ChunkMeshData) -> Result<(), ChunkMeshCacheError> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.path(position), encode(voxel_hash, data))?;
+        Ok(())
+    }
+
+    /// Forget the mesh of one chunk, for when its voxels are edited
+    pub fn invalidate(&self, position: IVec3) {
+        let _ = fs::remove_file(self.path(position));
+    }
+
+    /// Forget every mesh, for when the meshing itself changes
+    pub fn clear(&self) {
+        if let Err(err) = remove_dir_contents(&self.dir) {
+            warn!("Failed to clear chunk mesh cache: {}", err);
+        }
+    }
+}
+
+fn remove_dir_contents(dir: &Path) -> std::io::Result<()> {
+    if !dir.exists() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)? {
+        fs::remove_file(entry?.path())?;
+    }
+    Ok(())
+}
+
+/// Little endian header of version, vertex count, index count, padding and voxel hash, then the arrays back to back
+fn encode(voxel_hash: u64, data: &ChunkMeshData) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN + data.positions.len() * 32 + data.indices.len() * 4);
+    for word in [CACHE_VERSION, data.positions.len() as u32, data.indices.len() as u32, 0] {
+        bytes.extend_from_slice(&word.to_le_bytes());
+    }
+    bytes.extend_from_slice(&voxel_hash.to_le_bytes());
+    let floats = data.positions.iter().flatten()
+        .chain(data.normals.iter().flatten())
+        .chain(data.uvs.iter().flatten());
+    for value in floats {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    for index in data.indices.iter() {
+        bytes.extend_from_slice(&index.to_le_bytes());
+    }
+    bytes
+}
+
+/// None when the file is from another version or other voxels
+fn decode(bytes: &[u8], voxel_hash: u64) -> Result<Option<ChunkMeshData>, ChunkMeshCacheError> {
+    if bytes.len() < HEADER_LEN {
+        return Err(ChunkMeshCacheError::Corrupt);
+    }
+    let words: Vec<u32> = bytes.chunks_exact(4).map(|word| u32::from_le_bytes(word.try_into().unwrap())).collect();
+    let (version, vertex_count, index_count) = (words[0], words[1] as usize, words[2] as usize);
+    let file_hash = u64::from_le_bytes(bytes[16..24].try_into().unwrap());
+    if version != CACHE_VERSION || file_hash != voxel_hash {
+        return Ok(None);
+    }
+    let body = &words[HEADER_LEN / 4..];
+    if body.len() != vertex_count * 8 + index_count {
+        return Err(ChunkMeshCacheError::Corrupt);
+    }
+
+    let (positions, rest) = body.split_at(vertex_count * 3);
+    let (normals, rest) = rest.split_at(vertex_count * 3);
+    let (uvs, indices) = rest.split_at(vertex_count * 2);
+    let float = |word: &u32| f32::from_bits(*word);
+    Ok(Some(ChunkMeshData {
+        positions: positions.chunks_exact(3).map(|v| [float(&v[0]), float(&v[1]), float(&v[2])]).collect(),
+        normals: normals.chunks_exact(3).map(|v| [float(&v[0]), float(&v[1]), float(&v[2])]).collect(),
+        uvs: uvs.chunks_exact(2).map(|v| [float(&v[0]), float(&v[1])]).collect(),
+        indices: indices.to_vec(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangle() -> ChunkMeshData {
+        ChunkMeshData {
+            positions: vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+            normals: vec![[0.0, 0.0, 1.0]; 3],
+            uvs: vec![[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]],
+            indices: vec![0, 1, 2],
+        }
+    }
+
+    #[test]
+    fn encode_decode_round_trips() {
+        let data = triangle();
+        assert_eq!(decode(&encode(42, &data), 42).unwrap(), Some(data));
+    }
+
+    #[test]
+    fn hash_mismatch_is_stale() {
+        assert_eq!(decode(&encode(42, &triangle()), 43).unwrap(), None);
+    }
+
+    #[test]
+    fn truncated_file_is_corrupt() {
+        let bytes = encode(42, &triangle());
+        assert!(matches!(decode(&bytes[..bytes.len() - 4], 42), Err(ChunkMeshCacheError::Corrupt)));
+        assert!(matches!(decode(&bytes[..HEADER_LEN - 1], 42), Err(ChunkMeshCacheError::Corrupt)));
+    }
+
+    #[test]
+    fn shader_edits_change_cache_key() {
+        let voxel_hash = hash_voxels(&[Voxel::default(); 4]);
+        let shader_hash = hash_shaders(&["simplex", "voxels"]);
+        assert_eq!(mesh_cache_key(voxel_hash, shader_hash), mesh_cache_key(voxel_hash, hash_shaders(&["simplex", "voxels"])));
+        assert_ne!(mesh_cache_key(voxel_hash, shader_hash), mesh_cache_key(voxel_hash, hash_shaders(&["simplex", "voxels "])));
+    }
+}
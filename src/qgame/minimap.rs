@@ -0,0 +1,131 @@
+use std::time::Duration;
+
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+
+use crate::{RenderPlayer, TerrainColorRamp, TerrainHeightmap};
+
+const UNKNOWN_COLOR: [u8; 4] = [16, 16, 16, 200];
+const MARKER_COLOR: [u8; 4] = [255, 255, 255, 255];
+
+/// Top-down view of the terrain around the camera, rotated so that the view direction is up
+#[derive(Resource)]
+pub struct MinimapSettings {
+    /// Texels along each side of the minimap texture
+    pub resolution: u32,
+    /// Side length on screen in pixels
+    pub display_size: f32,
+    /// World distance from the center to the edge of the minimap
+    pub radius: f32,
+    pub update_interval: Duration,
+}
+
+#[derive(Resource)]
+pub struct Minimap {
+    pub image: Handle<Image>,
+    timer: Timer,
+}
+
+/// UI node displaying the minimap texture
+#[derive(Component)]
+pub struct MinimapNode;
+
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<MinimapSettings>()
+            .add_systems(Startup, spawn_minimap_sys)
+            .add_systems(Update, minimap_sys);
+    }
+}
+
+impl Default for MinimapSettings {
+    fn default() -> Self {
+        Self {
+            resolution: 96,
+            display_size: 192.0,
+            radius: 64.0,
+            update_interval: Duration::from_millis(250),
+        }
+    }
+}
+
+pub fn spawn_minimap_sys(
+    mut commands: Commands,
+    settings: Res<MinimapSettings>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let image = images.add(Image::new_fill(
+        Extent3d { width: settings.resolution, height: settings.resolution, depth_or_array_layers: 1 },
+        TextureDimension::D2,
+        &UNKNOWN_COLOR,
+        TextureFormat::Rgba8UnormSrgb,
+    ));
+    commands.spawn((
+        ImageBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(12.0),
+                right: Val::Px(12.0),
+                width: Val::Px(settings.display_size),
+                height: Val::Px(settings.display_size),
+                ..default()
+            },
+            image: UiImage::new(image.clone()),
+            ..default()
+        },
+        MinimapNode,
+    ));
+    commands.insert_resource(Minimap {
+        image,
+        timer: Timer::new(settings.update_interval, TimerMode::Repeating),
+    });
+}
+
+/// Redraws the minimap from the heightmap at the settings update interval
+pub fn minimap_sys(
+    time: Res<Time>,
+    settings: Res<MinimapSettings>,
+    minimap: Option<ResMut<Minimap>>,
+    heightmap: Res<TerrainHeightmap>,
+    color_ramp: Res<TerrainColorRamp>,
+    mut images: ResMut<Assets<Image>>,
+    camera_query: Query<&GlobalTransform, With<RenderPlayer>>,
+) {
+    let Some(mut minimap) = minimap else { return; };
+    if !minimap.timer.tick(time.delta()).just_finished() { return; }
+    let Some(camera) = camera_query.iter().next() else { return; };
+    let Some(image) = images.get_mut(&minimap.image) else { return; };
+
+    let center = camera.translation().xz();
+    let forward = camera.forward().xz().try_normalize().unwrap_or(Vec2::NEG_Y);
+    let right = Vec2::new(-forward.y, forward.x);
+    let resolution = settings.resolution as usize;
+    let texel_to_unit = |i: usize| (i as f32 + 0.5) / resolution as f32 * 2.0 - 1.0;
+    for row in 0..resolution {
+        for col in 0..resolution {
+            // Top row is in front of the camera
+            let offset = (right * texel_to_unit(col) - forward * texel_to_unit(row)) * settings.radius;
+            let column = center + offset;
+            let color = heightmap.sample(column.x, column.y)
+                .map_or(UNKNOWN_COLOR, |height| color_ramp.sample(height).as_rgba_u8());
+            let i = (col + row * resolution) * 4;
+            image.data[i..i + 4].copy_from_slice(&color);
+        }
+    }
+
+    // Arrow at the center pointing up, the way the camera faces
+    let mid = resolution as i32 / 2;
+    let marker = [(0, -2), (0, -1), (-1, 0), (0, 0), (1, 0), (-1, 1), (1, 1)];
+    for (dx, dy) in marker {
+        let (col, row) = (mid + dx, mid + dy);
+        if (0..resolution as i32).contains(&col) && (0..resolution as i32).contains(&row) {
+            let i = (col as usize + row as usize * resolution) * 4;
+            image.data[i..i + 4].copy_from_slice(&MARKER_COLOR);
+        }
+    }
+}
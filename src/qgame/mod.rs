@@ -1,6 +1,7 @@
 use std::{
     mem::size_of,
     slice::Iter,
+    sync::{Arc, Mutex},
 };
 
 use bevy::{
@@ -11,18 +12,75 @@ use bevy::{
     },
 };
 use thiserror::Error;
+use wgpu::{BufferAsyncError, MaintainBase::Wait};
 
+pub use biome::*;
+pub use combat::*;
+pub use console::*;
 pub use controller::*;
+pub use crosshair::*;
+pub use damage_overlay::*;
+pub use demo::*;
+pub use effects::*;
+pub use game::*;
+pub use game_time::*;
+pub use graphics::*;
+pub use grapple::*;
+pub use hazard::*;
+pub use headless::*;
 pub use input::*;
+pub use interact::*;
 pub use inventory::*;
 pub(crate) use lookup::*;
+pub use menu::*;
+pub use mesh_cache::*;
+pub use minimap::*;
+pub use net::*;
+pub use respawn::*;
+pub use rng::*;
+pub use sky::*;
+pub use sound::*;
+pub use split_screen::*;
+pub use structure::*;
+pub use theme::*;
+pub use triplanar::*;
 pub use voxel::*;
+pub use waypoint::*;
+pub use world::*;
 
+mod biome;
+mod combat;
+mod console;
 mod controller;
+mod crosshair;
+mod damage_overlay;
+mod demo;
+mod effects;
+mod game;
+mod game_time;
+mod graphics;
+mod grapple;
+mod hazard;
+mod headless;
 mod input;
+mod interact;
 mod inventory;
 mod lookup;
+mod menu;
+mod mesh_cache;
+mod minimap;
+mod net;
+mod respawn;
+mod rng;
+mod sky;
+mod sound;
+mod split_screen;
+mod structure;
+mod theme;
+mod triplanar;
 mod voxel;
+mod waypoint;
+mod world;
 
 #[derive(Debug, Error)]
 pub enum RonLoaderError {
@@ -34,17 +92,40 @@ pub enum RonLoaderError {
     LoadDirectError(#[from] bevy::asset::LoadDirectError),
 }
 
+#[derive(Debug, Error)]
+pub enum BufError {
+    #[error("buffer was not mapped or mapping has not finished")]
+    NotMapped,
+    #[error("failed to map buffer: {0}")]
+    Map(#[from] BufferAsyncError),
+    #[error("read of {len} elements is larger than the buffer capacity of {capacity}")]
+    OutOfRange { len: usize, capacity: usize },
+}
+
+struct StagingBuffer {
+    buffer: Buffer,
+    /// Set by the map callback once mapping finishes
+    map_result: Arc<Mutex<Option<Result<(), BufferAsyncError>>>>,
+}
+
+/// Values on the CPU mirrored into a GPU storage buffer through a staging buffer.
+/// Double buffered ones alternate between two staging buffers, so one can be read while the other is copied into.
 pub struct BufVec<T: Pod> {
+    /// Prefix of the GPU buffer labels, shows up in validation errors and graphics debuggers
+    label: String,
     read_only: bool,
     buffer_capacity: usize,
     values: Vec<T>,
-    staging_buffer: Buffer,
+    /// One staging buffer, or two when double buffered
+    staging: Vec<StagingBuffer>,
+    /// Staging buffer that writes and reads are encoded into and mapped, the other one is read from
+    back_index: usize,
     buffer: Buffer,
 }
 
-pub fn create_staging_buffer(read_only: bool, size: usize, device: &RenderDevice) -> Buffer {
+pub fn create_staging_buffer(label: &str, read_only: bool, size: usize, device: &RenderDevice) -> Buffer {
     device.create_buffer(&BufferDescriptor {
-        label: None,
+        label: Some(label),
         size: size as BufferAddress,
         usage: BufferUsages::COPY_DST | if read_only {
             BufferUsages::MAP_READ
@@ -55,7 +136,7 @@ pub fn create_staging_buffer(read_only: bool, size: usize, device: &RenderDevice
     })
 }
 
-pub fn create_buffer(_read_only: bool, size: usize, device: &RenderDevice) -> Buffer {
+pub fn create_buffer(label: &str, _read_only: bool, size: usize, device: &RenderDevice) -> Buffer {
     // let mut usage = BufferUsages::STORAGE | if read_only {
     //     BufferUsages::COPY_SRC
     // } else {
@@ -63,27 +144,58 @@ pub fn create_buffer(_read_only: bool, size: usize, device: &RenderDevice) -> Bu
     // };
     let usage = BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST;
     device.create_buffer(&BufferDescriptor {
-        label: None,
+        label: Some(label),
         size: size as BufferAddress,
         usage,
         mapped_at_creation: false,
     })
 }
 
+impl StagingBuffer {
+    fn new(label: &str, read_only: bool, size: usize, device: &RenderDevice) -> Self {
+        Self {
+            buffer: create_staging_buffer(&format!("{} staging buffer", label), read_only, size, device),
+            map_result: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    fn create(label: &str, read_only: bool, double_buffered: bool, size: usize, device: &RenderDevice) -> Vec<Self> {
+        let count = if double_buffered { 2 } else { 1 };
+        (0..count).map(|i| Self::new(&format!("{} {}", label, i), read_only, size, device)).collect()
+    }
+}
+
 impl<T: Pod> BufVec<T> {
-    pub fn with_capacity(read_only: bool, capacity: usize, device: &RenderDevice) -> Self {
+    pub fn with_capacity(label: &str, read_only: bool, double_buffered: bool, capacity: usize, device: &RenderDevice) -> Self {
         let size = capacity * size_of::<T>();
         let mut buffer = BufVec {
+            label: label.to_string(),
             read_only,
             buffer_capacity: capacity,
             values: Vec::with_capacity(capacity),
-            staging_buffer: create_staging_buffer(read_only, size, device),
-            buffer: create_buffer(read_only, size, device),
+            staging: StagingBuffer::create(label, read_only, double_buffered, size, device),
+            back_index: 0,
+            buffer: create_buffer(&format!("{} buffer", label), read_only, size, device),
         };
         buffer.ensure_buf_cap(device);
         buffer
     }
 
+    #[inline]
+    fn staging_buffer(&self) -> &Buffer {
+        &self.staging[self.back_index].buffer
+    }
+
+    #[inline]
+    pub fn is_double_buffered(&self) -> bool {
+        self.staging.len() > 1
+    }
+
+    /// Make the back staging buffer the one that is read from, does nothing when not double buffered
+    pub fn swap_staging(&mut self) {
+        self.back_index = (self.back_index + 1) % self.staging.len();
+    }
+
     #[inline]
     pub fn buffer(&self) -> &Buffer {
         &self.buffer
@@ -111,12 +223,19 @@ impl<T: Pod> BufVec<T> {
     }
 
     fn ensure_buf_cap(&mut self, device: &RenderDevice) {
-        if self.values.len() > self.buffer_capacity {
-            let size = self.values.len() * size_of::<T>();
-            self.staging_buffer = create_staging_buffer(self.read_only, size, device);
-            self.buffer = create_buffer(self.read_only, size, device);
-            self.buffer_capacity = size;
+        self.reserve(self.values.len(), device);
+    }
+
+    /// Recreate the GPU buffers if they hold fewer than `capacity` values. Their contents are not kept.
+    pub fn reserve(&mut self, capacity: usize, device: &RenderDevice) {
+        if capacity <= self.buffer_capacity {
+            return;
         }
+        let size = capacity * size_of::<T>();
+        self.staging = StagingBuffer::create(&self.label, self.read_only, self.is_double_buffered(), size, device);
+        self.back_index = 0;
+        self.buffer = create_buffer(&format!("{} buffer", self.label), self.read_only, size, device);
+        self.buffer_capacity = capacity;
     }
 
     pub fn encode_write(&mut self, queue: &RenderQueue, command_encoder: &mut CommandEncoder) {
@@ -127,28 +246,69 @@ impl<T: Pod> BufVec<T> {
         let size_bytes = size_of::<T>() * self.values.len();
         let range = 0..size_bytes;
         let bytes: &[u8] = cast_slice(&self.values);
-        queue.write_buffer(&self.staging_buffer, 0, &bytes[range]);
-        command_encoder.copy_buffer_to_buffer(&self.staging_buffer, 0, &self.buffer, 0, size_bytes as BufferAddress);
+        queue.write_buffer(self.staging_buffer(), 0, &bytes[range]);
+        command_encoder.copy_buffer_to_buffer(self.staging_buffer(), 0, &self.buffer, 0, size_bytes as BufferAddress);
     }
 
     pub fn encode_read(&mut self, len: usize, command_encoder: &mut CommandEncoder) {
         let size = size_of::<T>() * len;
-        command_encoder.copy_buffer_to_buffer(&self.buffer, 0, &self.staging_buffer, 0, size as BufferAddress);
+        command_encoder.copy_buffer_to_buffer(&self.buffer, 0, self.staging_buffer(), 0, size as BufferAddress);
     }
 
+    /// Start mapping the back staging buffer, it can be read once the device has been polled
     pub fn map_buffer(&mut self, len: usize) {
         self.values.resize(len, T::zeroed());
-        let buffer_slice = self.staging_buffer.slice(..);
-        buffer_slice.map_async(MapMode::Read, |_| {});
+        let staging = &self.staging[self.back_index];
+        let map_result = staging.map_result.clone();
+        if let Ok(mut map_result) = map_result.lock() {
+            *map_result = None;
+        }
+        let buffer_slice = staging.buffer.slice(..);
+        buffer_slice.map_async(MapMode::Read, move |result| {
+            if let Ok(mut map_result) = map_result.lock() {
+                *map_result = Some(result);
+            }
+        });
     }
 
-    pub fn read_and_unmap_buffer(&mut self, len: usize) {
+    /// Copy `len` values out of the mapped front staging buffer, the device must have been polled since [`Self::map_buffer`].
+    /// When double buffered, [`Self::swap_staging`] has to be called in between too.
+    /// The buffer is unmapped whenever mapping succeeded, even if the read itself fails.
+    pub fn try_read_and_unmap_buffer(&mut self, len: usize) -> Result<&[T], BufError> {
+        // The back one before the last swap, the same one when not double buffered
+        let staging = &self.staging[(self.back_index + 1) % self.staging.len()];
+        let map_result = staging.map_result.lock().ok().and_then(|mut map_result| map_result.take());
+        match map_result {
+            None => return Err(BufError::NotMapped),
+            Some(Err(err)) => return Err(BufError::Map(err)),
+            Some(Ok(())) => {}
+        }
+
+        let size = size_of::<T>() * len;
+        let staging_size = staging.buffer.size() as usize;
+        if size > staging_size {
+            staging.buffer.unmap();
+            return Err(BufError::OutOfRange { len, capacity: staging_size / size_of::<T>() });
+        }
+
         self.values.resize(len, T::zeroed());
+        let buffer_slice = staging.buffer.slice(..);
+        self.values.copy_from_slice(cast_slice(&buffer_slice.get_mapped_range()[..size]));
+        staging.buffer.unmap();
+        Ok(&self.values)
+    }
 
-        let buffer_slice = self.staging_buffer.slice(..);
-        let range = 0..size_of::<T>() * len;
-        self.values.copy_from_slice(cast_slice(&buffer_slice.get_mapped_range()[range]));
-        self.staging_buffer.unmap();
+    /// Copy the first `len` values of the GPU buffer back, blocking until all submitted work is done.
+    /// Does the whole encode, submit, map, poll and read sequence.
+    pub fn read_back(&mut self, len: usize, device: &RenderDevice, queue: &RenderQueue) -> Result<&[T], BufError> {
+        let label = format!("{} read back command encoder", self.label);
+        let mut command_encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: Some(&label) });
+        self.encode_read(len, &mut command_encoder);
+        queue.submit([command_encoder.finish()]);
+        self.map_buffer(len);
+        self.swap_staging();
+        device.poll(Wait);
+        self.try_read_and_unmap_buffer(len)
     }
 
     pub fn as_slice(&self) -> &[T] {
@@ -163,3 +323,76 @@ impl<T: Pod> BufVec<T> {
         self.values.clear();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::tasks::futures_lite::future;
+
+    use super::*;
+
+    /// Device and queue on whatever adapter is available, `None` when this machine has none
+    fn render_device() -> Option<(RenderDevice, RenderQueue)> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let adapter = future::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))?;
+        let (device, queue) = future::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None)).ok()?;
+        Some((RenderDevice::from(device), RenderQueue(Arc::new(queue))))
+    }
+
+    #[test]
+    fn reserve_only_grows() {
+        let Some((device, _)) = render_device() else { return; };
+        let mut buf = BufVec::<f32>::with_capacity("test", true, false, 4, &device);
+        buf.reserve(2, &device);
+        assert_eq!(buf.capacity(), 4);
+        buf.reserve(16, &device);
+        assert_eq!(buf.capacity(), 16);
+        assert_eq!(buf.buffer().size(), 16 * size_of::<f32>() as BufferAddress);
+    }
+
+    #[test]
+    fn read_back_returns_written_values() {
+        let Some((device, queue)) = render_device() else { return; };
+        let mut buf = BufVec::<f32>::with_capacity("test", true, false, 4, &device);
+        // Read only staging buffers can not be copied from, so write the GPU buffer directly
+        queue.write_buffer(buf.buffer(), 0, cast_slice(&[1.0f32, 2.0, 3.0]));
+        assert_eq!(buf.read_back(3, &device, &queue).unwrap(), [1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn read_needs_finished_map_in_range() {
+        let Some((device, _)) = render_device() else { return; };
+        let mut buf = BufVec::<f32>::with_capacity("test", true, false, 4, &device);
+        assert!(matches!(buf.try_read_and_unmap_buffer(4), Err(BufError::NotMapped)));
+
+        buf.map_buffer(4);
+        device.poll(Wait);
+        assert!(matches!(buf.try_read_and_unmap_buffer(5), Err(BufError::OutOfRange { len: 5, capacity: 4 })));
+        // Unmapped by the failed read, so mapping again works
+        buf.map_buffer(4);
+        device.poll(Wait);
+        assert_eq!(buf.try_read_and_unmap_buffer(4).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn double_buffered_reads_overlap() {
+        let Some((device, queue)) = render_device() else { return; };
+        let mut buf = BufVec::<f32>::with_capacity("test", true, true, 2, &device);
+        assert!(buf.is_double_buffered());
+        let encode_read = |buf: &mut BufVec<f32>, values: [f32; 2]| {
+            queue.write_buffer(buf.buffer(), 0, cast_slice(&values));
+            let mut command_encoder = device.create_command_encoder(&CommandEncoderDescriptor::default());
+            buf.encode_read(2, &mut command_encoder);
+            queue.submit([command_encoder.finish()]);
+            buf.map_buffer(2);
+        };
+
+        // The second read is copied and mapped while the first is still waiting to be read
+        encode_read(&mut buf, [1.0, 2.0]);
+        buf.swap_staging();
+        encode_read(&mut buf, [3.0, 4.0]);
+        device.poll(Wait);
+        assert_eq!(buf.try_read_and_unmap_buffer(2).unwrap(), [1.0, 2.0]);
+        buf.swap_staging();
+        assert_eq!(buf.try_read_and_unmap_buffer(2).unwrap(), [3.0, 4.0]);
+    }
+}
@@ -0,0 +1,416 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::ErrorKind,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+    time::Duration,
+};
+
+use bevy::prelude::*;
+use flagset::FlagSet;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{EquipStateName, Inventory, Item, ItemName, latched_input_flags, LogicalPlayer, MoveMode, player_input_system, PlayerInput, spawn_logical_player};
+
+const DEFAULT_PORT: u16 = 7777;
+/// Largest payload a UDP datagram can carry
+const MAX_DATAGRAM_SIZE: usize = 65_507;
+const PLAYER_SPAWN: Vec3 = Vec3::new(4.0, 18.0, 4.0);
+/// Clients that send nothing for this long are dropped along with their player
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Whether this instance simulates the world, follows a server that does, or both
+#[derive(Resource, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum NetMode {
+    /// Only simulates the players of connected clients, nothing is rendered
+    Server,
+    /// Sends input and shows the positions the server sends back, there is no prediction
+    Client,
+    #[default]
+    Singleplayer,
+}
+
+/// Picked from the command line, `--server [bind addr]` or `--connect <server addr>`
+#[derive(Resource, Clone, Debug)]
+pub struct NetSettings {
+    pub bind_addr: SocketAddr,
+    pub server_addr: SocketAddr,
+}
+
+#[derive(Debug, Error)]
+pub enum NetError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    RonSpannedError(#[from] ron::error::SpannedError),
+    #[error(transparent)]
+    RonError(#[from] ron::Error),
+}
+
+/// Only exists while networking, so singleplayer never touches the network
+#[derive(Resource)]
+pub struct NetSocket {
+    socket: UdpSocket,
+    buf: Vec<u8>,
+    /// Server only
+    clients: HashMap<SocketAddr, Client>,
+    /// Server only, ids are never reused so a late packet cannot be applied to someone else
+    next_player_id: Option<u8>,
+    /// Server counts up every broadcast, clients remember the newest one they applied
+    tick: u64,
+}
+
+struct Client {
+    /// The logical player this client controls
+    player_id: u8,
+    /// Elapsed time when the last message arrived
+    last_heard: Duration,
+}
+
+/// Stand in for a player that the server simulates, client only
+#[derive(Component)]
+pub struct RemotePlayer(pub u8);
+
+#[derive(Serialize, Deserialize)]
+enum ClientMessage {
+    Input(NetInput),
+}
+
+#[derive(Serialize, Deserialize)]
+enum ServerMessage {
+    Snapshot {
+        tick: u64,
+        /// Which of the players belongs to the receiving client
+        your_player_id: u8,
+        players: Vec<PlayerSnapshot>,
+    },
+}
+
+/// The parts of [`PlayerInput`] the server acts on
+#[derive(Serialize, Deserialize)]
+struct NetInput {
+    movement: Vec3,
+    flags: u32,
+    yaw: f32,
+    pitch: f32,
+    wanted_item_slot: Option<u8>,
+    #[serde(default)]
+    wanted_move_mode: Option<MoveMode>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct PlayerSnapshot {
+    player_id: u8,
+    translation: Vec3,
+    yaw: f32,
+    pitch: f32,
+    equipped_slot: Option<u8>,
+    equip_state_name: EquipStateName,
+    item_names: Vec<Option<ItemName>>,
+}
+
+pub struct NetPlugin;
+
+impl Plugin for NetPlugin {
+    fn build(&self, app: &mut App) {
+        let (net_mode, settings) = NetSettings::from_args(std::env::args().skip(1));
+        app
+            .insert_resource(net_mode)
+            .insert_resource(settings)
+            .add_systems(Startup, open_socket_sys)
+            .add_systems(PreUpdate, (
+                server_receive_sys.run_if(resource_equals(NetMode::Server)),
+                (client_send_sys, client_receive_sys).chain().run_if(resource_equals(NetMode::Client)),
+            ).after(player_input_system).run_if(resource_exists::<NetSocket>()))
+            // Physics steps in the fixed schedule, so it has written back by now
+            .add_systems(PostUpdate, server_broadcast_sys
+                .run_if(resource_equals(NetMode::Server))
+                .run_if(resource_exists::<NetSocket>()));
+    }
+}
+
+/// Movement, items and firing only run where the world is simulated
+pub fn is_authoritative(net_mode: Res<NetMode>) -> bool {
+    *net_mode != NetMode::Client
+}
+
+/// Dedicated servers have nobody to render for
+pub fn has_local_player(net_mode: Res<NetMode>) -> bool {
+    *net_mode != NetMode::Server
+}
+
+impl NetSettings {
+    pub fn from_args(mut args: impl Iterator<Item=String>) -> (NetMode, Self) {
+        let mut net_mode = NetMode::Singleplayer;
+        let mut settings = Self {
+            bind_addr: SocketAddr::from((Ipv4Addr::UNSPECIFIED, DEFAULT_PORT)),
+            server_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, DEFAULT_PORT)),
+        };
+        while let Some(arg) = args.next() {
+            let (mode, addr) = match arg.as_str() {
+                "--server" => (NetMode::Server, &mut settings.bind_addr),
+                "--connect" => (NetMode::Client, &mut settings.server_addr),
+                _ => continue,
+            };
+            net_mode = mode;
+            // Address is optional, anything that does not parse is left for whoever else reads the arguments
+            if let Some(parsed) = args.next().and_then(|arg| arg.parse().ok()) {
+                *addr = parsed;
+            }
+        }
+        (net_mode, settings)
+    }
+}
+
+impl NetSocket {
+    fn bind(addr: SocketAddr) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_nonblocking(true)?;
+        // Zero is the id of a local player, so clients start at one
+        Ok(Self { socket, buf: vec![0; MAX_DATAGRAM_SIZE], clients: HashMap::new(), next_player_id: Some(1), tick: 0 })
+    }
+
+    fn send(&self, message: &impl Serialize, addr: SocketAddr) -> Result<(), NetError> {
+        let text = ron::to_string(message)?;
+        self.socket.send_to(text.as_bytes(), addr)?;
+        Ok(())
+    }
+
+    /// Next message that arrived, none once everything received so far has been read
+    fn recv<T: DeserializeOwned>(&mut self) -> Result<Option<(T, SocketAddr)>, NetError> {
+        match self.socket.recv_from(&mut self.buf) {
+            Ok((len, addr)) => Ok(Some((ron::de::from_bytes(&self.buf[..len])?, addr))),
+            Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Read every pending message, malformed ones are skipped
+    fn recv_all<T: DeserializeOwned>(&mut self) -> Vec<(T, SocketAddr)> {
+        let mut messages = Vec::new();
+        loop {
+            match self.recv() {
+                Ok(Some(message)) => messages.push(message),
+                Ok(None) => break,
+                Err(NetError::Io(err)) => {
+                    warn!("Failed to receive: {}", err);
+                    break;
+                }
+                Err(err) => warn!("Dropping malformed message: {}", err),
+            }
+        }
+        messages
+    }
+}
+
+impl From<&PlayerInput> for NetInput {
+    fn from(input: &PlayerInput) -> Self {
+        Self {
+            movement: input.movement,
+            flags: input.flags.bits(),
+            yaw: input.yaw,
+            pitch: input.pitch,
+            wanted_item_slot: input.wanted_item_slot,
+            wanted_move_mode: input.wanted_move_mode,
+        }
+    }
+}
+
+impl NetInput {
+    /// Later messages in the same frame add their flags to the earlier ones, so a press is never lost
+    fn apply(&self, input: &mut PlayerInput, is_first_this_frame: bool) {
+        input.movement = self.movement;
+        let flags = FlagSet::new_truncated(self.flags);
+        if is_first_this_frame {
+            // Keep toggles from an earlier frame until a movement tick consumes them
+            input.flags = flags | (input.flags & latched_input_flags());
+        } else {
+            input.flags |= flags;
+        }
+        input.yaw = self.yaw;
+        input.pitch = self.pitch;
+        input.wanted_item_slot = self.wanted_item_slot.or(input.wanted_item_slot);
+        input.wanted_move_mode = self.wanted_move_mode.or(input.wanted_move_mode);
+    }
+}
+
+pub fn open_socket_sys(
+    mut commands: Commands,
+    net_mode: Res<NetMode>,
+    settings: Res<NetSettings>,
+) {
+    let bind_addr = match *net_mode {
+        NetMode::Singleplayer => return,
+        NetMode::Server => settings.bind_addr,
+        // Any free port, the server learns it from our first input
+        NetMode::Client => match settings.server_addr {
+            SocketAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+            SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+        },
+    };
+    match NetSocket::bind(bind_addr) {
+        Ok(socket) => {
+            info!("{:?} listening on {}", *net_mode, bind_addr);
+            commands.insert_resource(socket);
+        }
+        Err(err) => error!("Failed to open {:?} socket on {}: {}", *net_mode, bind_addr, err),
+    }
+}
+
+pub fn server_receive_sys(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut socket: ResMut<NetSocket>,
+    mut player_query: Query<(Entity, &LogicalPlayer, &mut PlayerInput)>,
+) {
+    let now = time.elapsed();
+    let mut applied = HashSet::new();
+    for (message, addr) in socket.recv_all::<ClientMessage>() {
+        let player_id = match socket.clients.get_mut(&addr) {
+            Some(client) => {
+                client.last_heard = now;
+                client.player_id
+            }
+            None => {
+                let Some(player_id) = socket.next_player_id else {
+                    warn!("Out of player ids, ignoring {}", addr);
+                    continue;
+                };
+                socket.next_player_id = player_id.checked_add(1);
+                socket.clients.insert(addr, Client { player_id, last_heard: now });
+                spawn_logical_player(&mut commands, player_id, Transform::from_translation(PLAYER_SPAWN));
+                info!("Player {} joined from {}", player_id, addr);
+                player_id
+            }
+        };
+        let ClientMessage::Input(net_input) = message;
+        // Players that just joined pick up input once they are spawned next frame
+        if let Some((_, _, mut input)) = player_query.iter_mut().find(|(_, player, _)| player.0 == player_id) {
+            net_input.apply(&mut input, applied.insert(player_id));
+        }
+    }
+
+    let timed_out: Vec<(SocketAddr, u8)> = socket.clients.iter()
+        .filter(|(_, client)| now.saturating_sub(client.last_heard) > CLIENT_TIMEOUT)
+        .map(|(&addr, client)| (addr, client.player_id))
+        .collect();
+    for (addr, player_id) in timed_out {
+        socket.clients.remove(&addr);
+        if let Some((player_ent, ..)) = player_query.iter().find(|(_, player, _)| player.0 == player_id) {
+            commands.entity(player_ent).despawn_recursive();
+        }
+        info!("Player {} from {} timed out", player_id, addr);
+    }
+}
+
+pub fn server_broadcast_sys(
+    mut socket: ResMut<NetSocket>,
+    item_query: Query<&Item>,
+    player_query: Query<(&LogicalPlayer, &Transform, &PlayerInput, &Inventory)>,
+) {
+    socket.tick += 1;
+    let players: Vec<PlayerSnapshot> = player_query.iter().map(|(player, transform, input, inv)| PlayerSnapshot {
+        player_id: player.0,
+        translation: transform.translation,
+        yaw: input.yaw,
+        pitch: input.pitch,
+        equipped_slot: inv.equipped_slot,
+        equip_state_name: inv.equip_state_name.clone(),
+        item_names: inv.item_ents.0.iter()
+            .map(|item_ent| item_ent.and_then(|item_ent| item_query.get(item_ent).ok()).map(|item| item.name.clone()))
+            .collect(),
+    }).collect();
+    for (&addr, client) in socket.clients.iter() {
+        let message = ServerMessage::Snapshot { tick: socket.tick, your_player_id: client.player_id, players: players.clone() };
+        if let Err(err) = socket.send(&message, addr) {
+            warn!("Failed to send snapshot to {}: {}", addr, err);
+        }
+    }
+}
+
+pub fn client_send_sys(
+    socket: Res<NetSocket>,
+    settings: Res<NetSettings>,
+    mut player_query: Query<(&LogicalPlayer, &mut PlayerInput)>,
+) {
+    let Some((_, mut input)) = player_query.iter_mut().find(|(player, _)| player.0 == 0) else { return; };
+    if let Err(err) = socket.send(&ClientMessage::Input(NetInput::from(&*input)), settings.server_addr) {
+        warn!("Failed to send input to {}: {}", settings.server_addr, err);
+    }
+    // Movement is not ticked locally, the server consumes toggles instead
+    input.flags -= latched_input_flags();
+    input.wanted_move_mode = None;
+}
+
+pub fn client_receive_sys(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    asset_server: Res<AssetServer>,
+    mut socket: ResMut<NetSocket>,
+    item_query: Query<&Item>,
+    mut local_query: Query<(Entity, &LogicalPlayer, &mut Transform, &mut Inventory), Without<RemotePlayer>>,
+    mut remote_query: Query<(Entity, &RemotePlayer, &mut Transform), Without<LogicalPlayer>>,
+) {
+    // Only the newest snapshot matters since nothing is interpolated yet, older ones may arrive out of order
+    let newest = socket.recv_all::<ServerMessage>().into_iter()
+        .map(|(message, _)| message)
+        .filter(|ServerMessage::Snapshot { tick, .. }| *tick > socket.tick)
+        .max_by_key(|ServerMessage::Snapshot { tick, .. }| *tick);
+    let Some(ServerMessage::Snapshot { tick, your_player_id, players }) = newest else { return; };
+    socket.tick = tick;
+
+    // Players that left or timed out on the server are no longer sent
+    for (remote_ent, remote, _) in remote_query.iter() {
+        if !players.iter().any(|snapshot| snapshot.player_id == remote.0) {
+            commands.entity(remote_ent).despawn_recursive();
+        }
+    }
+    for snapshot in players {
+        if snapshot.player_id == your_player_id {
+            let Some((inv_ent, _, mut transform, mut inv)) = local_query.iter_mut().find(|(_, player, ..)| player.0 == 0) else { continue; };
+            transform.translation = snapshot.translation;
+            apply_inventory_snapshot(&mut commands, &asset_server, &item_query, inv_ent, &mut inv, &snapshot);
+        } else if let Some((_, _, mut transform)) = remote_query.iter_mut().find(|(_, remote, _)| remote.0 == snapshot.player_id) {
+            transform.translation = snapshot.translation;
+            transform.rotation = Quat::from_rotation_y(snapshot.yaw);
+        } else {
+            commands.spawn((
+                SpatialBundle::from_transform(Transform::from_translation(snapshot.translation)),
+                RemotePlayer(snapshot.player_id),
+            )).with_children(|parent| {
+                // Matches the logical player capsule, which sits on top of its origin
+                parent.spawn(PbrBundle {
+                    mesh: meshes.add(Mesh::from(shape::Capsule { radius: 0.5, depth: 1.0, ..default() })),
+                    material: materials.add(StandardMaterial { base_color: Color::ORANGE, ..default() }),
+                    transform: Transform::from_xyz(0.0, 1.0, 0.0),
+                    ..default()
+                });
+            });
+        }
+    }
+}
+
+fn apply_inventory_snapshot(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    item_query: &Query<&Item>,
+    inv_ent: Entity,
+    inv: &mut Inventory,
+    snapshot: &PlayerSnapshot,
+) {
+    for (slot, item_name) in snapshot.item_names.iter().enumerate().take(inv.item_ents.0.len()) {
+        let local_name = inv.item_ents.0[slot]
+            .and_then(|item_ent| item_query.get(item_ent).ok())
+            .map(|item| &item.name);
+        if local_name == item_name.as_ref() { continue; }
+        match item_name {
+            Some(item_name) => { inv.set_item(inv_ent, commands, asset_server, item_name, slot as u8, 1); }
+            None => if let Some(item_ent) = inv.item_ents.0[slot].take() {
+                commands.entity(item_ent).despawn_recursive();
+            },
+        }
+    }
+    inv.equipped_slot = snapshot.equipped_slot;
+    inv.equip_state_name = snapshot.equip_state_name.clone();
+}
@@ -0,0 +1,168 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{apply_input_accumulator_sys, DeathEvent, GameRng, GameTime, Health, Inventory, is_authoritative, LogicalPlayer, PlayerController, PlayerInput, PlayerSet, Shield};
+
+/// Where players can come back after dying, placed by its transform
+#[derive(Component)]
+pub struct SpawnPoint;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SpawnPointChoice {
+    Random,
+    /// Maximize the distance to the closest other living player
+    FarthestFromEnemies,
+}
+
+#[derive(Resource)]
+pub struct RespawnSettings {
+    pub delay: Duration,
+    pub choice: SpawnPointChoice,
+}
+
+/// Dead and waiting to respawn
+#[derive(Component)]
+pub struct Respawning {
+    pub remaining: Duration,
+}
+
+pub struct RespawnPlugin;
+
+impl Plugin for RespawnPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<RespawnSettings>()
+            .add_systems(FixedUpdate, zero_respawning_input_sys.after(apply_input_accumulator_sys))
+            .add_systems(Update, (
+                zero_respawning_input_sys.before(PlayerSet::Logic),
+                (start_respawn_sys, respawn_sys).chain().run_if(is_authoritative),
+            ));
+    }
+}
+
+impl Default for RespawnSettings {
+    fn default() -> Self {
+        Self {
+            delay: Duration::from_secs(3),
+            choice: SpawnPointChoice::FarthestFromEnemies,
+        }
+    }
+}
+
+impl SpawnPointChoice {
+    /// Position to respawn at, none if there are no spawn points
+    pub fn choose(self, rng: &mut GameRng, spawn_points: &[Vec3], enemies: &[Vec3]) -> Option<Vec3> {
+        match self {
+            SpawnPointChoice::Random => {
+                if spawn_points.is_empty() { return None; }
+                let index = (rng.next_u64() % spawn_points.len() as u64) as usize;
+                Some(spawn_points[index])
+            }
+            SpawnPointChoice::FarthestFromEnemies => {
+                let closest_enemy_dist = |point: Vec3| enemies.iter()
+                    .map(|&enemy| point.distance_squared(enemy))
+                    .fold(f32::INFINITY, f32::min);
+                spawn_points.iter().copied()
+                    .max_by(|&a, &b| closest_enemy_dist(a).total_cmp(&closest_enemy_dist(b)))
+            }
+        }
+    }
+}
+
+pub fn start_respawn_sys(
+    mut commands: Commands,
+    settings: Res<RespawnSettings>,
+    mut death_events: EventReader<DeathEvent>,
+    player_query: Query<(), (With<LogicalPlayer>, Without<Respawning>)>,
+) {
+    for event in death_events.read() {
+        if player_query.contains(event.entity) {
+            commands.entity(event.entity).insert(Respawning { remaining: settings.delay });
+        }
+    }
+}
+
+/// The dead do not act, only looking around is kept
+pub fn zero_respawning_input_sys(mut query: Query<(&mut PlayerInput, &mut Velocity), With<Respawning>>) {
+    for (mut input, mut velocity) in query.iter_mut() {
+        *input = PlayerInput { yaw: input.yaw, pitch: input.pitch, ..default() };
+        *velocity = Velocity::zero();
+    }
+}
+
+pub fn respawn_sys(
+    mut commands: Commands,
+    time: Res<GameTime>,
+    settings: Res<RespawnSettings>,
+    mut rng: ResMut<GameRng>,
+    spawn_point_query: Query<&GlobalTransform, With<SpawnPoint>>,
+    mut player_query: Query<(
+        Entity, &mut Respawning, &mut Transform, &mut Velocity, &mut PlayerController,
+        &mut Health, Option<&mut Shield>, &mut Inventory,
+    )>,
+    enemy_query: Query<(Entity, &Transform, &Health), (With<LogicalPlayer>, Without<Respawning>)>,
+) {
+    let spawn_points: Vec<Vec3> = spawn_point_query.iter().map(GlobalTransform::translation).collect();
+    for (player_ent, mut respawning, mut transform, mut velocity, mut controller, mut health, shield, mut inv) in player_query.iter_mut() {
+        respawning.remaining = respawning.remaining.saturating_sub(time.delta());
+        if !respawning.remaining.is_zero() { continue; }
+
+        let enemies: Vec<Vec3> = enemy_query.iter()
+            .filter(|(enemy_ent, _, enemy_health)| *enemy_ent != player_ent && !enemy_health.is_dead())
+            .map(|(_, enemy_transform, _)| enemy_transform.translation)
+            .collect();
+        if let Some(spawn_point) = settings.choice.choose(&mut rng, &spawn_points, &enemies) {
+            transform.translation = spawn_point;
+        } else {
+            warn!("No spawn points, respawning in place");
+        }
+        *velocity = Velocity::zero();
+        controller.velocity = Vec3::ZERO;
+        controller.ground_tick = 0;
+        health.current = health.max;
+        if let Some(mut shield) = shield {
+            shield.current = shield.max;
+            shield.since_hit = Duration::ZERO;
+        }
+        for &item_ent in inv.item_ents.0.iter().flatten() {
+            commands.entity(item_ent).despawn_recursive();
+        }
+        *inv = Inventory::default();
+        commands.entity(player_ent).remove::<Respawning>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::PlayerInputFlags;
+
+    use super::*;
+
+    #[test]
+    fn respawning_input_is_zeroed() {
+        let mut app = App::new();
+        app.add_systems(Update, zero_respawning_input_sys);
+        let input = || PlayerInput {
+            movement: Vec3::NEG_Z,
+            flags: PlayerInputFlags::Fire | PlayerInputFlags::Jump,
+            yaw: 1.0,
+            wanted_item_slot: Some(1),
+            ..default()
+        };
+        let dead = app.world.spawn((input(), Velocity::linear(Vec3::X), Respawning { remaining: Duration::from_secs(1) })).id();
+        let alive = app.world.spawn((input(), Velocity::linear(Vec3::X))).id();
+        app.update();
+
+        let dead_input = app.world.get::<PlayerInput>(dead).unwrap();
+        assert_eq!(dead_input.movement, Vec3::ZERO);
+        assert!(dead_input.flags.is_empty());
+        assert_eq!(dead_input.wanted_item_slot, None);
+        assert_eq!(dead_input.yaw, 1.0);
+        assert_eq!(app.world.get::<Velocity>(dead).unwrap().linvel, Vec3::ZERO);
+        let alive_input = app.world.get::<PlayerInput>(alive).unwrap();
+        assert_eq!(alive_input.movement, Vec3::NEG_Z);
+        assert!(alive_input.flags.contains(PlayerInputFlags::Fire));
+    }
+}
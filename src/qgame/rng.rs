@@ -0,0 +1,89 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+/// Small deterministic random number generator (SplitMix64).
+/// Gameplay randomness goes through this so that it is reproducible given the same seed.
+#[derive(Resource)]
+pub struct GameRng {
+    state: u64,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Uniform in [min, max)
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+
+    /// Uniform over the directions at most `half_angle` radians away from `axis`, which must be normalized
+    pub fn cone(&mut self, axis: Vec3, half_angle: f32) -> Vec3 {
+        // Uniform in cosine is uniform over the spherical cap
+        let cos_angle = 1.0 - self.next_f32() * (1.0 - half_angle.cos());
+        let sin_angle = (1.0 - cos_angle * cos_angle).max(0.0).sqrt();
+        let around = self.range(0.0, TAU);
+        let (tangent, bitangent) = axis.any_orthonormal_pair();
+        axis * cos_angle + (tangent * around.cos() + bitangent * around.sin()) * sin_angle
+    }
+}
+
+impl Default for GameRng {
+    fn default() -> Self {
+        Self::new(0x5EED)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let (mut a, mut b) = (GameRng::new(7), GameRng::new(7));
+        for _ in 0..8 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+    }
+
+    #[test]
+    fn range_stays_in_bounds() {
+        let mut rng = GameRng::default();
+        for _ in 0..1000 {
+            let value = rng.range(-0.5, 2.0);
+            assert!((-0.5..2.0).contains(&value));
+        }
+    }
+
+    #[test]
+    fn cone_stays_within_half_angle() {
+        let mut rng = GameRng::default();
+        let axis = Vec3::new(1.0, 2.0, -0.5).normalize();
+        let half_angle = 0.1;
+        let mut max_angle: f32 = 0.0;
+        for _ in 0..1000 {
+            let dir = rng.cone(axis, half_angle);
+            assert!((dir.length() - 1.0).abs() < 1e-5);
+            max_angle = max_angle.max(dir.angle_between(axis));
+        }
+        assert!(max_angle <= half_angle + 1e-4);
+        // Spread out over the whole cone, not bunched up at the center
+        assert!(max_angle > half_angle * 0.8);
+        assert_eq!(rng.cone(axis, 0.0), axis);
+    }
+}
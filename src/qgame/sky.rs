@@ -0,0 +1,225 @@
+use std::f32::consts::TAU;
+
+use bevy::{
+    pbr::NotShadowCaster,
+    prelude::*,
+    prelude::shape::UVSphere,
+    render::{mesh::VertexAttributeValues, view::RenderLayers},
+};
+
+use crate::{GameTime, RenderPlayer};
+
+/// Inside the default camera far plane
+const SKY_DOME_RADIUS: f32 = 900.0;
+/// Render layer of the sky dome of player zero, every local player gets the next one
+const FIRST_SKY_LAYER: u8 = 1;
+
+/// Light and sky colors at a point in the day
+#[derive(Copy, Clone, Debug)]
+pub struct SkyKeyframe {
+    /// Fraction of the day in [0, 1), zero is midnight
+    pub time_of_day: f32,
+    /// Also used as the horizon color of the sky dome
+    pub clear_color: Color,
+    pub zenith_color: Color,
+    pub ambient_color: Color,
+    pub illuminance: f32,
+}
+
+#[derive(Resource)]
+pub struct SkySettings {
+    pub day_length_seconds: f32,
+    /// Time of day when the game starts
+    pub start_time_of_day: f32,
+    /// Sorted by time of day, interpolation wraps around midnight
+    pub keyframes: Vec<SkyKeyframe>,
+}
+
+/// Colors of the sky dome, shifted by the day/night cycle
+#[derive(Resource)]
+pub struct SkyGradient {
+    pub zenith: Color,
+    pub horizon: Color,
+}
+
+/// Large inverted sphere around the render camera of this player.
+/// Each is on its own render layer, so split-screen views never see the dome around another camera.
+#[derive(Component)]
+pub struct SkyDome(pub u8);
+
+/// Mesh and material shared by every [`SkyDome`]
+#[derive(Resource)]
+pub struct SkyDomeAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+/// Marks the directional light moved by the day/night cycle
+#[derive(Component)]
+pub struct Sun;
+
+pub struct SkyPlugin;
+
+impl Plugin for SkyPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<SkySettings>()
+            .init_resource::<SkyGradient>()
+            .add_systems(Startup, spawn_sky_dome_sys)
+            .add_systems(Update, (sky_cycle_sys, sky_dome_colors_sys, spawn_camera_sky_dome_sys, follow_sky_dome_sys).chain());
+    }
+}
+
+impl Default for SkySettings {
+    fn default() -> Self {
+        Self {
+            day_length_seconds: 600.0,
+            start_time_of_day: 0.35,
+            keyframes: vec![
+                SkyKeyframe { time_of_day: 0.0, clear_color: Color::rgb(0.01, 0.01, 0.04), zenith_color: Color::rgb(0.0, 0.0, 0.02), ambient_color: Color::rgb(0.3, 0.3, 0.6), illuminance: 0.0 },
+                SkyKeyframe { time_of_day: 0.25, clear_color: Color::rgb(0.8, 0.45, 0.3), zenith_color: Color::rgb(0.3, 0.35, 0.6), ambient_color: Color::rgb(1.0, 0.8, 0.7), illuminance: 800.0 },
+                SkyKeyframe { time_of_day: 0.5, clear_color: Color::rgb(0.45, 0.65, 0.95), zenith_color: Color::rgb(0.2, 0.4, 0.85), ambient_color: Color::WHITE, illuminance: 2000.0 },
+                SkyKeyframe { time_of_day: 0.75, clear_color: Color::rgb(0.85, 0.4, 0.25), zenith_color: Color::rgb(0.3, 0.3, 0.55), ambient_color: Color::rgb(1.0, 0.75, 0.6), illuminance: 800.0 },
+            ],
+        }
+    }
+}
+
+impl Default for SkyGradient {
+    fn default() -> Self {
+        Self { zenith: Color::rgb(0.2, 0.4, 0.85), horizon: Color::rgb(0.45, 0.65, 0.95) }
+    }
+}
+
+impl SkySettings {
+    pub fn time_of_day(&self, elapsed_seconds: f32) -> f32 {
+        (self.start_time_of_day + elapsed_seconds / self.day_length_seconds).rem_euclid(1.0)
+    }
+
+    /// Blend of the keyframes around `time_of_day`
+    pub fn sample(&self, time_of_day: f32) -> Option<SkyKeyframe> {
+        if self.keyframes.is_empty() { return None; }
+        let next_index = self.keyframes.iter().position(|keyframe| keyframe.time_of_day > time_of_day).unwrap_or(0);
+        let prev_index = (next_index + self.keyframes.len() - 1) % self.keyframes.len();
+        let prev = &self.keyframes[prev_index];
+        let next = &self.keyframes[next_index];
+        // Gaps are measured forwards so that going past midnight works
+        let gap = (next.time_of_day - prev.time_of_day).rem_euclid(1.0);
+        let t = if gap > 0.0 { (time_of_day - prev.time_of_day).rem_euclid(1.0) / gap } else { 0.0 };
+        let lerp_color = |a: Color, b: Color| Color::from(Vec4::from(a).lerp(Vec4::from(b), t));
+        Some(SkyKeyframe {
+            time_of_day,
+            clear_color: lerp_color(prev.clear_color, next.clear_color),
+            zenith_color: lerp_color(prev.zenith_color, next.zenith_color),
+            ambient_color: lerp_color(prev.ambient_color, next.ambient_color),
+            illuminance: prev.illuminance + (next.illuminance - prev.illuminance) * t,
+        })
+    }
+}
+
+pub fn sky_cycle_sys(
+    time: Res<GameTime>,
+    settings: Res<SkySettings>,
+    mut clear_color: ResMut<ClearColor>,
+    mut gradient: ResMut<SkyGradient>,
+    mut ambient_light: ResMut<AmbientLight>,
+    mut sun_query: Query<(&mut Transform, &mut DirectionalLight), With<Sun>>,
+) {
+    let time_of_day = settings.time_of_day(time.elapsed().as_secs_f32());
+    let Some(sky) = settings.sample(time_of_day) else { return; };
+
+    clear_color.0 = sky.clear_color;
+    gradient.horizon = sky.clear_color;
+    gradient.zenith = sky.zenith_color;
+    ambient_light.color = sky.ambient_color;
+    for (mut transform, mut light) in sun_query.iter_mut() {
+        // Straight down at noon, straight up at midnight, rising in the east
+        let angle = (time_of_day - 0.5) * TAU;
+        transform.rotation = Quat::from_rotation_z(angle) * Quat::from_rotation_x(-TAU * 0.25);
+        light.illuminance = sky.illuminance;
+    }
+}
+
+fn sky_dome_color(gradient: &SkyGradient, height: f32) -> [f32; 4] {
+    // Below the horizon stays the horizon color, it is hidden by terrain anyway
+    let t = height.max(0.0).sqrt();
+    Vec4::from(gradient.horizon).lerp(Vec4::from(gradient.zenith), t).to_array()
+}
+
+pub fn spawn_sky_dome_sys(
+    mut commands: Commands,
+    gradient: Res<SkyGradient>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let mut mesh = Mesh::from(UVSphere { radius: SKY_DOME_RADIUS, sectors: 32, stacks: 16 });
+    let colors: Vec<[f32; 4]> = match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+        Some(VertexAttributeValues::Float32x3(positions)) => positions.iter()
+            .map(|position| sky_dome_color(&gradient, position[1] / SKY_DOME_RADIUS))
+            .collect(),
+        _ => Vec::new(),
+    };
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    commands.insert_resource(SkyDomeAssets {
+        mesh: meshes.add(mesh),
+        material: materials.add(StandardMaterial {
+            base_color: Color::WHITE,
+            unlit: true,
+            fog_enabled: false,
+            // Seen from the inside
+            cull_mode: None,
+            ..default()
+        }),
+    });
+}
+
+/// Give every new player camera a dome of its own, on a layer only that camera renders
+pub fn spawn_camera_sky_dome_sys(
+    mut commands: Commands,
+    assets: Option<Res<SkyDomeAssets>>,
+    camera_query: Query<(Entity, &RenderPlayer, Option<&RenderLayers>), Added<RenderPlayer>>,
+) {
+    let Some(assets) = assets else { return; };
+    for (camera_ent, render_player, layers) in camera_query.iter() {
+        let layer = FIRST_SKY_LAYER + render_player.0;
+        commands.entity(camera_ent).insert(layers.copied().unwrap_or_default().with(layer));
+        commands.spawn((
+            PbrBundle {
+                mesh: assets.mesh.clone(),
+                material: assets.material.clone(),
+                ..default()
+            },
+            NotShadowCaster,
+            RenderLayers::layer(layer),
+            SkyDome(render_player.0),
+        ));
+    }
+}
+
+pub fn sky_dome_colors_sys(
+    gradient: Res<SkyGradient>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    assets: Option<Res<SkyDomeAssets>>,
+) {
+    if !gradient.is_changed() { return; }
+
+    let Some(mesh) = assets.and_then(|assets| meshes.get_mut(&assets.mesh)) else { return; };
+    let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) else { return; };
+    let colors: Vec<[f32; 4]> = positions.iter()
+        .map(|position| sky_dome_color(&gradient, position[1] / SKY_DOME_RADIUS))
+        .collect();
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+}
+
+pub fn follow_sky_dome_sys(
+    mut commands: Commands,
+    camera_query: Query<(&Transform, &RenderPlayer), Without<SkyDome>>,
+    mut dome_query: Query<(Entity, &mut Transform, &SkyDome)>,
+) {
+    for (dome_ent, mut transform, dome) in dome_query.iter_mut() {
+        match camera_query.iter().find(|(_, render_player)| render_player.0 == dome.0) {
+            Some((camera_transform, _)) => transform.translation = camera_transform.translation,
+            None => commands.entity(dome_ent).despawn(),
+        }
+    }
+}
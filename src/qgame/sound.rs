@@ -0,0 +1,162 @@
+use bevy::{
+    audio::{SpatialScale, Volume},
+    prelude::*,
+};
+
+/// Ordered from least to most important, used to decide which voice to steal
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SoundCategory {
+    Footstep,
+    Impact,
+    Weapon,
+    Ui,
+}
+
+/// Request to play a sound, routed through the [`VoiceManager`]
+#[derive(Event)]
+pub struct PlaySoundEvent {
+    pub source: Handle<AudioSource>,
+    pub category: SoundCategory,
+    pub volume: f32,
+    /// Playback speed, which also shifts pitch
+    pub speed: f32,
+    /// Heard from this point in the world through the [`SpatialListener`], otherwise heard everywhere equally
+    pub position: Option<Vec3>,
+    /// Multiplier on the distance to the listener, higher fades out sooner, only used with a position
+    pub falloff: f32,
+}
+
+/// Caps the number of concurrently playing sounds
+#[derive(Resource)]
+pub struct VoiceManager {
+    pub max_voices: usize,
+    voices: Vec<Voice>,
+}
+
+/// Marker for audio entities spawned by the [`VoiceManager`]
+#[derive(Component)]
+pub struct ManagedVoice;
+
+struct Voice {
+    entity: Entity,
+    category: SoundCategory,
+}
+
+pub struct SoundPlugin;
+
+impl Plugin for SoundPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<VoiceManager>()
+            .add_event::<PlaySoundEvent>()
+            .add_systems(PostUpdate, play_sound_sys);
+    }
+}
+
+impl Default for VoiceManager {
+    fn default() -> Self {
+        Self { max_voices: 24, voices: Vec::new() }
+    }
+}
+
+impl VoiceManager {
+    #[inline]
+    pub fn active_voices(&self) -> usize {
+        self.voices.len()
+    }
+
+    /// Index of the voice to stop in favor of a new sound of `category`.
+    /// Picks the lowest priority voice, oldest first, and never one more important than the new sound.
+    fn find_victim(&self, category: SoundCategory) -> Option<usize> {
+        // Voices are kept oldest first, so the first minimum is also the oldest
+        let (index, victim) = self.voices.iter().enumerate()
+            .min_by_key(|(index, voice)| (voice.category, *index))?;
+        (victim.category <= category).then_some(index)
+    }
+}
+
+pub fn play_sound_sys(
+    mut commands: Commands,
+    mut voices: ResMut<VoiceManager>,
+    mut sound_events: EventReader<PlaySoundEvent>,
+    voice_query: Query<(Option<&AudioSink>, Option<&SpatialAudioSink>), With<ManagedVoice>>,
+) {
+    // Sounds that finished have despawned themselves
+    voices.voices.retain(|voice| voice_query.contains(voice.entity));
+
+    for event in sound_events.read() {
+        if voices.voices.len() >= voices.max_voices {
+            let Some(victim_index) = voices.find_victim(event.category) else { continue; };
+            let victim = voices.voices.remove(victim_index);
+            match voice_query.get(victim.entity) {
+                Ok((Some(sink), _)) => sink.stop(),
+                Ok((_, Some(sink))) => sink.stop(),
+                _ => {}
+            }
+            commands.entity(victim.entity).despawn();
+        }
+
+        let mut settings = PlaybackSettings::DESPAWN
+            .with_volume(Volume::new_relative(event.volume))
+            .with_speed(event.speed);
+        let mut voice = commands.spawn(ManagedVoice);
+        if let Some(position) = event.position {
+            settings = settings.with_spatial(true);
+            settings.spatial_scale = Some(SpatialScale::new(event.falloff));
+            voice.insert(TransformBundle::from_transform(Transform::from_translation(position)));
+        }
+        voice.insert(AudioBundle { source: event.source.clone(), settings });
+        let entity = voice.id();
+        voices.voices.push(Voice { entity, category: event.category });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager(categories: &[SoundCategory]) -> VoiceManager {
+        VoiceManager {
+            max_voices: categories.len(),
+            voices: categories.iter().enumerate()
+                .map(|(index, &category)| Voice { entity: Entity::from_raw(index as u32), category })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn steals_oldest_lowest_priority() {
+        let voices = manager(&[SoundCategory::Weapon, SoundCategory::Footstep, SoundCategory::Impact, SoundCategory::Footstep]);
+        assert_eq!(voices.find_victim(SoundCategory::Weapon), Some(1));
+        assert_eq!(voices.find_victim(SoundCategory::Footstep), Some(1));
+    }
+
+    #[test]
+    fn never_steals_more_important_voice() {
+        let voices = manager(&[SoundCategory::Weapon, SoundCategory::Ui]);
+        assert_eq!(voices.find_victim(SoundCategory::Impact), None);
+        assert_eq!(voices.find_victim(SoundCategory::Ui), Some(0));
+    }
+
+    #[test]
+    fn play_sound_caps_voices() {
+        let mut app = App::new();
+        app
+            .insert_resource(VoiceManager { max_voices: 2, voices: Vec::new() })
+            .add_event::<PlaySoundEvent>()
+            .add_systems(Update, play_sound_sys);
+
+        let event = |category| PlaySoundEvent { source: Handle::default(), category, volume: 1.0, speed: 1.0, position: None, falloff: 1.0 };
+        app.world.send_event(event(SoundCategory::Footstep));
+        app.world.send_event(event(SoundCategory::Weapon));
+        app.update();
+        app.world.send_event(event(SoundCategory::Impact));
+        app.update();
+
+        let voices = app.world.resource::<VoiceManager>();
+        assert_eq!(voices.active_voices(), 2);
+        let categories: Vec<_> = voices.voices.iter().map(|voice| voice.category).collect();
+        assert_eq!(categories, [SoundCategory::Weapon, SoundCategory::Impact]);
+        assert_eq!(app.world.query::<&ManagedVoice>().iter(&app.world).count(), 2);
+    }
+}
@@ -0,0 +1,87 @@
+use bevy::{
+    prelude::*,
+    render::camera::Viewport,
+    window::PrimaryWindow,
+};
+
+use crate::RenderPlayer;
+
+/// One keyboard and mouse plus one per gamepad, more would not fit on screen anyway
+pub const MAX_LOCAL_PLAYERS: u8 = 4;
+
+/// How many players share this screen, picked from the command line with `--local-players <count>`.
+/// Only singleplayer spawns more than one, see [`crate::spawn_player_sys`].
+#[derive(Resource, Copy, Clone, Debug)]
+pub struct LocalPlayers {
+    pub count: u8,
+}
+
+/// Tiles the window with the camera of each local player, in reading order of their ids
+pub struct SplitScreenPlugin;
+
+impl Plugin for SplitScreenPlugin {
+    fn build(&self, app: &mut App) {
+        // Embedders can pick the count themselves by inserting the resource first
+        if !app.world.contains_resource::<LocalPlayers>() {
+            app.insert_resource(LocalPlayers::from_args(std::env::args().skip(1)));
+        }
+        app.add_systems(PostUpdate, split_screen_viewport_sys);
+    }
+}
+
+impl Default for LocalPlayers {
+    fn default() -> Self {
+        Self { count: 1 }
+    }
+}
+
+impl LocalPlayers {
+    pub fn from_args(mut args: impl Iterator<Item=String>) -> Self {
+        let mut local_players = Self::default();
+        while let Some(arg) = args.next() {
+            if arg != "--local-players" { continue; }
+            if let Some(count) = args.next().and_then(|arg| arg.parse::<u8>().ok()) {
+                local_players.count = count.clamp(1, MAX_LOCAL_PLAYERS);
+            }
+        }
+        local_players
+    }
+}
+
+/// Columns and rows of the grid that `count` views are laid out in, as square as possible and wider than tall
+pub fn split_screen_grid(count: u32) -> UVec2 {
+    let columns = (count as f32).sqrt().ceil().max(1.0) as u32;
+    UVec2::new(columns, count.div_ceil(columns).max(1))
+}
+
+/// Keep each player camera in its own cell of the window as the window resizes.
+/// A single camera covers the whole window.
+pub fn split_screen_viewport_sys(
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut camera_query: Query<(&mut Camera, &RenderPlayer)>,
+) {
+    let Ok(window) = window_query.get_single() else { return; };
+    let count = camera_query.iter().count() as u32;
+    let grid = split_screen_grid(count);
+    let cell_size = UVec2::new(window.physical_width(), window.physical_height()) / grid;
+    // Minimized windows have nothing to divide, and empty viewports are invalid
+    if cell_size.cmpeq(UVec2::ZERO).any() { return; }
+
+    for (mut camera, render_player) in camera_query.iter_mut() {
+        let index = render_player.0 as u32;
+        let viewport = (count > 1 && index < count).then(|| Viewport {
+            physical_position: UVec2::new(index % grid.x, index / grid.x) * cell_size,
+            physical_size: cell_size,
+            ..default()
+        });
+        // Avoid triggering change detection every frame
+        let is_same = match (&camera.viewport, &viewport) {
+            (Some(current), Some(wanted)) => current.physical_position == wanted.physical_position && current.physical_size == wanted.physical_size,
+            (None, None) => true,
+            _ => false,
+        };
+        if !is_same {
+            camera.viewport = viewport;
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use bevy::{
+    asset::{
+        AssetLoader,
+        AsyncReadExt,
+        io::Reader,
+        LoadContext,
+    },
+    prelude::*,
+    reflect::TypePath,
+    utils::BoxedFuture,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{Chunk, ChunkMeshCache, CHUNK_SZ, CHUNK_SZ_2, ConsoleCommandEvent, RonLoaderError, Voxel};
+
+/// Block of voxels that can be stamped into the world, indexed by `x + y * size.x + z * size.x * size.y`
+#[derive(Asset, Serialize, Deserialize, TypePath)]
+pub struct StructureTemplate {
+    pub size: UVec3,
+    pub voxels: Vec<Voxel>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StampMode {
+    /// Replace terrain voxels with the template
+    Overwrite,
+    /// Keep whichever is more solid
+    Merge,
+}
+
+pub struct PlacedStructure {
+    pub template: Handle<StructureTemplate>,
+    /// World voxel position of the minimum corner
+    pub origin: IVec3,
+    pub mode: StampMode,
+}
+
+/// Structures applied on top of generated terrain, in placement order
+#[derive(Resource, Default)]
+pub struct Structures {
+    pub placed: Vec<PlacedStructure>,
+    pending: Vec<PlacedStructure>,
+}
+
+pub struct StructurePlugin;
+
+impl Plugin for StructurePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Structures>()
+            .init_asset::<StructureTemplate>()
+            .register_asset_loader(StructureTemplateAssetLoader)
+            .add_systems(Update, (stamp_command_sys, place_structures_sys).chain());
+    }
+}
+
+#[derive(Default)]
+pub struct StructureTemplateAssetLoader;
+
+impl AssetLoader for StructureTemplateAssetLoader {
+    type Asset = StructureTemplate;
+    type Settings = ();
+    type Error = RonLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a Self::Settings,
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<StructureTemplate, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let asset: StructureTemplate = ron::de::from_bytes(&bytes)?;
+            Ok(asset)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["structure.ron"]
+    }
+}
+
+impl Structures {
+    /// Stamp once the template is loaded, overlapped chunks are marked dirty at that point
+    pub fn place(&mut self, template: Handle<StructureTemplate>, origin: IVec3, mode: StampMode) {
+        self.pending.push(PlacedStructure { template, origin, mode });
+    }
+}
+
+/// Whether a template placed at `origin` overlaps the chunk at `chunk_position`
+pub fn structure_overlaps_chunk(template: &StructureTemplate, origin: IVec3, chunk_position: IVec3) -> bool {
+    let chunk_min = chunk_position * CHUNK_SZ as i32;
+    let min = origin.max(chunk_min);
+    let max = (origin + template.size.as_ivec3()).min(chunk_min + IVec3::splat(CHUNK_SZ as i32));
+    min.cmplt(max).all()
+}
+
+/// Write the part of a template that falls inside a chunk into its voxels
+pub fn stamp_structure(
+    template: &StructureTemplate, origin: IVec3, mode: StampMode,
+    chunk_position: IVec3, voxels: &mut [Voxel],
+) {
+    if !structure_overlaps_chunk(template, origin, chunk_position) { return; }
+
+    let size = template.size.as_ivec3();
+    let chunk_min = chunk_position * CHUNK_SZ as i32;
+    let min = origin.max(chunk_min);
+    let max = (origin + size).min(chunk_min + IVec3::splat(CHUNK_SZ as i32));
+    for z in min.z..max.z {
+        for y in min.y..max.y {
+            for x in min.x..max.x {
+                let local = IVec3::new(x, y, z) - chunk_min;
+                let in_template = IVec3::new(x, y, z) - origin;
+                let template_index = (in_template.x + in_template.y * size.x + in_template.z * size.x * size.y) as usize;
+                let Some(&stamped) = template.voxels.get(template_index) else { continue; };
+                let voxel = &mut voxels[local.x as usize + local.y as usize * CHUNK_SZ + local.z as usize * CHUNK_SZ_2];
+                match mode {
+                    StampMode::Overwrite => *voxel = stamped,
+                    StampMode::Merge => if stamped.density > voxel.density {
+                        *voxel = stamped;
+                    },
+                }
+            }
+        }
+    }
+}
+
+pub fn stamp_command_sys(
+    asset_server: Res<AssetServer>,
+    mut command_events: EventReader<ConsoleCommandEvent>,
+    mut structures: ResMut<Structures>,
+) {
+    for command in command_events.read() {
+        if command.name != "stamp" { continue; }
+
+        let mode = match command.args.get(4).map(String::as_str) {
+            Some("merge") => StampMode::Merge,
+            _ => StampMode::Overwrite,
+        };
+        match (command.args.first(), command.arg::<i32>(1), command.arg::<i32>(2), command.arg::<i32>(3)) {
+            (Some(name), Some(x), Some(y), Some(z)) => {
+                let template = asset_server.load(format!("structures/{}.structure.ron", name));
+                structures.place(template, IVec3::new(x, y, z), mode);
+            }
+            _ => warn!("Usage: stamp <name> <x> <y> <z> [merge]"),
+        }
+    }
+}
+
+pub fn place_structures_sys(
+    templates: Res<Assets<StructureTemplate>>,
+    mut structures: ResMut<Structures>,
+    mesh_cache: Option<Res<ChunkMeshCache>>,
+    mut chunk_query: Query<&mut Chunk>,
+) {
+    if structures.pending.is_empty() { return; }
+
+    let structures = structures.as_mut();
+    let mut i = 0;
+    while i < structures.pending.len() {
+        let Some(template) = templates.get(&structures.pending[i].template) else {
+            i += 1;
+            continue;
+        };
+        let placed = structures.pending.remove(i);
+        for mut chunk in chunk_query.iter_mut() {
+            if structure_overlaps_chunk(template, placed.origin, chunk.position) {
+                chunk.dirty = true;
+                if let Some(mesh_cache) = &mesh_cache {
+                    mesh_cache.invalidate(chunk.position);
+                }
+            }
+        }
+        structures.placed.push(placed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::CHUNK_SZ_3;
+
+    use super::*;
+
+    fn voxel(density: f32) -> Voxel {
+        Voxel { density, ..default() }
+    }
+
+    /// 2x2x2 block whose density counts up with the template index
+    fn counting_template() -> StructureTemplate {
+        StructureTemplate { size: UVec3::splat(2), voxels: (0..8).map(|i| voxel(i as f32 / 8.0)).collect() }
+    }
+
+    fn chunk_voxel(voxels: &[Voxel], x: usize, y: usize, z: usize) -> f32 {
+        voxels[x + y * CHUNK_SZ + z * CHUNK_SZ_2].density
+    }
+
+    #[test]
+    fn cube_template_is_full() {
+        let template: StructureTemplate = ron::from_str(include_str!("../../assets/structures/cube.structure.ron")).unwrap();
+        assert_eq!(template.voxels.len(), (template.size.x * template.size.y * template.size.z) as usize);
+    }
+
+    #[test]
+    fn stamp_is_clipped_to_chunk() {
+        let template = counting_template();
+        // Straddles the +x face of chunk zero
+        let origin = IVec3::new(CHUNK_SZ as i32 - 1, 0, 0);
+        let mut voxels = vec![voxel(0.0); CHUNK_SZ_3];
+        stamp_structure(&template, origin, StampMode::Overwrite, IVec3::ZERO, &mut voxels);
+        assert_eq!(chunk_voxel(&voxels, CHUNK_SZ - 1, 1, 1), 6.0 / 8.0);
+        assert_eq!(voxels.iter().filter(|voxel| voxel.density != 0.0).count(), 3);
+
+        let mut neighbor = vec![voxel(0.0); CHUNK_SZ_3];
+        stamp_structure(&template, origin, StampMode::Overwrite, IVec3::X, &mut neighbor);
+        assert_eq!(chunk_voxel(&neighbor, 0, 1, 1), 7.0 / 8.0);
+
+        assert!(!structure_overlaps_chunk(&template, origin, IVec3::Y));
+    }
+
+    #[test]
+    fn merge_keeps_more_solid() {
+        let template = counting_template();
+        let mut voxels = vec![voxel(0.5); CHUNK_SZ_3];
+        stamp_structure(&template, IVec3::ZERO, StampMode::Merge, IVec3::ZERO, &mut voxels);
+        assert_eq!(chunk_voxel(&voxels, 0, 0, 0), 0.5);
+        assert_eq!(chunk_voxel(&voxels, 1, 1, 1), 7.0 / 8.0);
+
+        stamp_structure(&template, IVec3::ZERO, StampMode::Overwrite, IVec3::ZERO, &mut voxels);
+        assert_eq!(chunk_voxel(&voxels, 0, 0, 0), 0.0);
+    }
+}
@@ -0,0 +1,134 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{Config, ConfigState, CrosshairSettings};
+
+/// Palettes picked so that feedback colors stay apart for each kind of color blindness
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColorThemePreset {
+    #[default]
+    Default,
+    /// Red and green look alike
+    Deuteranopia,
+    /// Like deuteranopia, with reds also appearing dark
+    Protanopia,
+    /// Blue and yellow look alike
+    Tritanopia,
+}
+
+/// Colors of the HUD and of hit feedback, every themed element reads from here
+#[derive(Resource, Clone, Debug)]
+pub struct ColorTheme {
+    pub preset: ColorThemePreset,
+    pub crosshair: Color,
+    pub hit: Color,
+    pub kill: Color,
+    /// Flashed when the local player takes damage
+    pub damage: Color,
+    pub hud_text: Color,
+    pub hud_secondary_text: Color,
+    pub friendly: Color,
+    pub enemy: Color,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ThemeRole {
+    HudText,
+    HudSecondaryText,
+    Friendly,
+    Enemy,
+}
+
+/// Text recolored when the theme changes, section `i` takes the color of role `i`.
+/// Sections past the end of the roles take the last role.
+#[derive(Component)]
+pub struct ThemedText(pub Vec<ThemeRole>);
+
+pub struct ThemePlugin;
+
+impl Plugin for ThemePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ColorTheme>()
+            .add_systems(Update, (
+                color_theme_config_sys,
+                apply_color_theme_sys.run_if(resource_changed::<ColorTheme>()),
+            ).chain());
+    }
+}
+
+impl Default for ColorTheme {
+    fn default() -> Self {
+        Self::from(ColorThemePreset::default())
+    }
+}
+
+impl From<ColorThemePreset> for ColorTheme {
+    fn from(preset: ColorThemePreset) -> Self {
+        // Accents are from the Okabe-Ito palette where the default red and green would clash
+        let (hit, kill, damage, friendly, enemy) = match preset {
+            ColorThemePreset::Default => (
+                Color::WHITE, Color::RED, Color::rgb(0.8, 0.0, 0.0), Color::rgb(0.2, 0.8, 0.2), Color::RED,
+            ),
+            ColorThemePreset::Deuteranopia => (
+                Color::WHITE, Color::rgb(0.9, 0.6, 0.0), Color::rgb(0.84, 0.37, 0.0), Color::rgb(0.0, 0.45, 0.7), Color::rgb(0.9, 0.6, 0.0),
+            ),
+            ColorThemePreset::Protanopia => (
+                Color::WHITE, Color::rgb(0.94, 0.89, 0.26), Color::rgb(0.9, 0.6, 0.0), Color::rgb(0.34, 0.71, 0.91), Color::rgb(0.94, 0.89, 0.26),
+            ),
+            ColorThemePreset::Tritanopia => (
+                Color::WHITE, Color::rgb(0.8, 0.47, 0.65), Color::rgb(0.84, 0.37, 0.0), Color::rgb(0.0, 0.62, 0.45), Color::rgb(0.84, 0.37, 0.0),
+            ),
+        };
+        Self {
+            preset,
+            crosshair: Color::rgba(1.0, 1.0, 1.0, 0.8),
+            hit,
+            kill,
+            damage,
+            hud_text: Color::WHITE,
+            hud_secondary_text: Color::ANTIQUE_WHITE,
+            friendly,
+            enemy,
+        }
+    }
+}
+
+impl ColorTheme {
+    pub fn color(&self, role: ThemeRole) -> Color {
+        match role {
+            ThemeRole::HudText => self.hud_text,
+            ThemeRole::HudSecondaryText => self.hud_secondary_text,
+            ThemeRole::Friendly => self.friendly,
+            ThemeRole::Enemy => self.enemy,
+        }
+    }
+}
+
+/// Switch presets when the config asks for a different one
+pub fn color_theme_config_sys(
+    configs: Res<Assets<Config>>,
+    config_state: Option<Res<ConfigState>>,
+    mut theme: ResMut<ColorTheme>,
+) {
+    let Some(config) = config_state.and_then(|state| configs.get(&state.handle)) else { return; };
+    if theme.preset != config.color_theme {
+        *theme = ColorTheme::from(config.color_theme);
+    }
+}
+
+pub fn apply_color_theme_sys(
+    theme: Res<ColorTheme>,
+    mut crosshair_settings: ResMut<CrosshairSettings>,
+    mut text_query: Query<(&ThemedText, &mut Text)>,
+) {
+    crosshair_settings.color = theme.crosshair;
+    crosshair_settings.hit_color = theme.hit;
+    crosshair_settings.kill_color = theme.kill;
+    for (themed, mut text) in text_query.iter_mut() {
+        let Some(&last) = themed.0.last() else { continue; };
+        for (i, section) in text.sections.iter_mut().enumerate() {
+            section.style.color = theme.color(themed.0.get(i).copied().unwrap_or(last));
+        }
+    }
+}
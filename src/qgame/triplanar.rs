@@ -0,0 +1,108 @@
+use bevy::{
+    pbr::{ExtendedMaterial, MaterialExtension},
+    prelude::*,
+    render::{
+        render_asset::RenderAssets,
+        render_resource::{AsBindGroup, AsBindGroupShaderType, ShaderRef, ShaderType},
+    },
+};
+
+// Match the flags in `triplanar.wgsl`
+const HAS_NORMAL_MAP: u32 = 1;
+const HAS_STEEP_ALBEDO: u32 = 2;
+
+/// Material of terrain chunks
+pub type TerrainMaterial = ExtendedMaterial<StandardMaterial, TriplanarExtension>;
+
+/// Projects textures along the world axes and blends them by the surface normal, so terrain does not need good UVs.
+/// Textures that are not set leave the base [`StandardMaterial`] as it is.
+/// With a steep albedo, flat ground uses `albedo` (grass) and slopes use `steep_albedo` (rock).
+#[derive(Asset, AsBindGroup, Reflect, Debug, Clone)]
+#[uniform(100, TriplanarUniform)]
+pub struct TriplanarExtension {
+    #[texture(101)]
+    #[sampler(102)]
+    pub albedo: Option<Handle<Image>>,
+    #[texture(107)]
+    #[sampler(108)]
+    pub steep_albedo: Option<Handle<Image>>,
+    /// Tangent space normals, each projection is treated as its own tangent space
+    #[texture(103)]
+    #[sampler(104)]
+    pub normal_map: Option<Handle<Image>>,
+    /// Green channel scales the perceptual roughness of the base material, like its metallic roughness texture
+    #[texture(105)]
+    #[sampler(106)]
+    pub roughness_map: Option<Handle<Image>>,
+    /// Texture repeats per world unit
+    pub scale: f32,
+    /// Higher narrows the transition between projections on slopes
+    pub blend_sharpness: f32,
+    /// Surfaces whose normal has a smaller up component than this are steep, `0.7` is about 45 degrees
+    pub steep_threshold: f32,
+    /// Width of the band around the threshold where flat and steep albedo mix
+    pub steep_blend: f32,
+}
+
+#[derive(Clone, Default, ShaderType)]
+pub struct TriplanarUniform {
+    pub scale: f32,
+    pub blend_sharpness: f32,
+    pub steep_threshold: f32,
+    pub steep_blend: f32,
+    pub flags: u32,
+}
+
+pub struct TriplanarPlugin;
+
+impl Plugin for TriplanarPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(MaterialPlugin::<TerrainMaterial>::default());
+    }
+}
+
+impl Default for TriplanarExtension {
+    fn default() -> Self {
+        Self {
+            albedo: None,
+            steep_albedo: None,
+            normal_map: None,
+            roughness_map: None,
+            scale: 0.25,
+            blend_sharpness: 4.0,
+            steep_threshold: 0.7,
+            steep_blend: 0.1,
+        }
+    }
+}
+
+impl AsBindGroupShaderType<TriplanarUniform> for TriplanarExtension {
+    fn as_bind_group_shader_type(&self, _images: &RenderAssets<Image>) -> TriplanarUniform {
+        let mut flags = 0;
+        // Without a normal map the fallback texture would bend every normal
+        if self.normal_map.is_some() {
+            flags |= HAS_NORMAL_MAP;
+        }
+        if self.steep_albedo.is_some() {
+            flags |= HAS_STEEP_ALBEDO;
+        }
+        TriplanarUniform {
+            scale: self.scale,
+            blend_sharpness: self.blend_sharpness,
+            steep_threshold: self.steep_threshold,
+            steep_blend: self.steep_blend,
+            flags,
+        }
+    }
+}
+
+impl MaterialExtension for TriplanarExtension {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/triplanar.wgsl".into()
+    }
+}
+
+/// Plain terrain material, colored by the vertex colors of the terrain color ramp
+pub fn terrain_material(base: StandardMaterial) -> TerrainMaterial {
+    TerrainMaterial { base, extension: TriplanarExtension::default() }
+}
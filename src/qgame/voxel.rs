@@ -5,28 +5,271 @@ use std::{
 
 use bevy::{
     core::{cast_slice, Pod, Zeroable},
+    ecs::system::SystemParam,
+    pbr::wireframe::Wireframe,
     prelude::*,
+    math::Affine3A,
     render::{
-        mesh::{Indices, VertexAttributeValues},
+        mesh::{GenerateTangentsError, Indices, VertexAttributeValues},
+        primitives::{Aabb, Frustum},
         render_resource::*,
         renderer::{RenderDevice, RenderQueue},
     },
+    tasks::{AsyncComputeTaskPool, ComputeTaskPool, futures_lite::future, Task},
     utils::HashMap,
 };
-use wgpu::MaintainBase::Wait;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
 use crate::*;
 
 // use flagset::{flags, FlagSet};
 
-const CHUNK_SZ: usize = 32;
-const CHUNK_SZ_2: usize = CHUNK_SZ * CHUNK_SZ;
-const CHUNK_SZ_3: usize = CHUNK_SZ * CHUNK_SZ * CHUNK_SZ;
+pub const CHUNK_SZ: usize = 32;
+pub const CHUNK_SZ_2: usize = CHUNK_SZ * CHUNK_SZ;
+pub const CHUNK_SZ_3: usize = CHUNK_SZ * CHUNK_SZ * CHUNK_SZ;
+/// Worst case is a chunk of isolated blocks, each with six quads.
+/// Smooth cells emit at most five triangles, so 15 vertices and 15 indices.
+pub const MAX_VERTICES_PER_VOXEL: usize = 4 * 6;
+pub const MAX_INDICES_PER_VOXEL: usize = 6 * 6;
+
+/// Post-processes the voxels of a chunk after generation and before meshing.
+/// Voxels are indexed by `x + y * CHUNK_SZ + z * CHUNK_SZ_2`.
+pub type ChunkGenerationHook = Box<dyn Fn(IVec3, &mut [Voxel]) + Send + Sync>;
+
+/// Terrain shape, one inside the ground and zero outside.
+/// Marching cubes puts the surface where density crosses one half.
+pub trait DensityField: Send + Sync {
+    fn sample(&self, world_pos: Vec3) -> f32;
+
+    /// Stored per voxel so terrain can be textured differently by region
+    fn material(&self, _world_pos: Vec3) -> u32 {
+        0
+    }
+}
+
+/// Port of `simplexNoise2` in `simplex.wgsl`, so terrain matches when there is no GPU to run it on.
+/// Float `%` truncates the same way as in WGSL.
+pub fn simplex_noise(v: Vec2) -> f32 {
+    const C: Vec4 = Vec4::new(0.211324865405187, 0.366025403784439, -0.577350269189626, 0.024390243902439);
+    let permute = |x: Vec3| ((x * 34.0 + 1.0) * x) % 289.0;
+    let fract = |x: Vec3| x - x.floor();
+
+    let mut i = (v + v.dot(Vec2::splat(C.y))).floor();
+    let x0 = v - i + i.dot(Vec2::splat(C.x));
+    let i1 = if x0.x > x0.y { Vec2::new(0.0, 1.0) } else { Vec2::new(1.0, 0.0) };
+    let x12 = Vec4::new(x0.x + C.x - i1.x, x0.y + C.x - i1.y, x0.x + C.z, x0.y + C.z);
+    i %= 289.0;
+    let p = permute(permute(i.y + Vec3::new(0.0, i1.y, 1.0)) + i.x + Vec3::new(0.0, i1.x, 1.0));
+    let mut m = (0.5 - Vec3::new(x0.dot(x0), x12.xy().dot(x12.xy()), x12.zw().dot(x12.zw()))).max(Vec3::ZERO);
+    m = m * m;
+    m = m * m;
+    let x = 2.0 * fract(p * C.w) - 1.0;
+    let h = x.abs() - 0.5;
+    let ox = (x + 0.5).floor();
+    let a0 = x - ox;
+    m *= 1.79284291400159 - 0.85373472095314 * (a0 * a0 + h * h);
+    let g = Vec3::new(a0.x * x0.x + h.x * x0.y, a0.y * x12.x + h.y * x12.y, a0.z * x12.z + h.z * x12.w);
+    130.0 * m.dot(g)
+}
+
+/// Surface height above the chunk bottom for a simplex sample in [-1, 1], between 8 and 12 voxels
+pub fn noise_surface_height(noise: f32) -> f32 {
+    (noise + 1.0) * 0.5 * 4.0 + 8.0
+}
+
+/// Solid below a surface height, fading over one voxel
+pub struct HeightmapDensity<F>(pub F);
+
+impl<F: Fn(Vec2) -> f32 + Send + Sync> DensityField for HeightmapDensity<F> {
+    fn sample(&self, world_pos: Vec3) -> f32 {
+        ((self.0)(world_pos.xz()) - world_pos.y).clamp(0.0, 1.0)
+    }
+}
+
+/// Replaces the built-in noise heightmap when set, sampled on the CPU for every voxel
+#[derive(Resource, Default)]
+pub struct TerrainDensity(pub Option<Box<dyn DensityField>>);
+
+impl TerrainDensity {
+    pub fn new(field: impl DensityField + 'static) -> Self {
+        Self(Some(Box::new(field)))
+    }
+}
+
+/// Everything that shapes the voxels of a chunk before it is meshed
+#[derive(SystemParam)]
+pub struct ChunkGenerators<'w> {
+    pub density: Res<'w, TerrainDensity>,
+    pub hooks: Res<'w, ChunkGenerationHooks>,
+    pub structures: Res<'w, Structures>,
+    pub structure_templates: Res<'w, Assets<StructureTemplate>>,
+}
 
 #[derive(Component)]
 pub struct Chunk {
     pub position: IVec3,
     pub voxels: Vec<Voxel>,
+    /// Voxels need to be regenerated and meshed
+    pub dirty: bool,
+    /// Set when the voxels are generated, `None` before that
+    pub fill: Option<ChunkFill>,
+}
+
+/// Trimesh collider being cooked off the main thread, swapped in by [`apply_chunk_collider_sys`]
+#[derive(Component)]
+pub struct ChunkColliderTask(Task<Collider>);
+
+/// Chunks without a density crossing have no surface, so meshing them is skipped
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ChunkFill {
+    Empty,
+    Solid,
+    Mixed,
+}
+
+/// Vertex colors for terrain by world height, stops are (height, color) sorted by height
+#[derive(Resource, Clone)]
+pub struct TerrainColorRamp {
+    pub stops: Vec<(f32, Color)>,
+}
+
+impl Default for TerrainColorRamp {
+    fn default() -> Self {
+        Self {
+            stops: vec![
+                (8.5, Color::DARK_GREEN),
+                (10.0, Color::rgb(0.4, 0.3, 0.2)),
+                (11.5, Color::WHITE),
+            ],
+        }
+    }
+}
+
+impl TerrainColorRamp {
+    pub fn sample(&self, height: f32) -> Color {
+        let (Some(&(first_height, first_color)), Some(&(_, last_color))) = (self.stops.first(), self.stops.last()) else {
+            return Color::WHITE;
+        };
+        if height <= first_height {
+            return first_color;
+        }
+        for stop in self.stops.windows(2) {
+            let (low_height, low_color) = stop[0];
+            let (high_height, high_color) = stop[1];
+            if height <= high_height {
+                let t = ((height - low_height) / (high_height - low_height)).clamp(0.0, 1.0);
+                let color = Vec4::from(low_color.as_rgba_f32()).lerp(Vec4::from(high_color.as_rgba_f32()), t);
+                return Color::rgba(color.x, color.y, color.z, color.w);
+            }
+        }
+        last_color
+    }
+}
+
+/// CPU copy of the generated terrain surface height of every voxel column
+#[derive(Resource, Default)]
+pub struct TerrainHeightmap {
+    chunks: HashMap<IVec2, Vec<f32>>,
+}
+
+impl TerrainHeightmap {
+    /// `heights` are world space surface heights indexed by `x + z * CHUNK_SZ`
+    pub fn insert_chunk(&mut self, position: IVec2, heights: Vec<f32>) {
+        self.chunks.insert(position, heights);
+    }
+
+    fn column(&self, x: i32, z: i32) -> Option<f32> {
+        let chunk_sz = CHUNK_SZ as i32;
+        let chunk_pos = IVec2::new(x.div_euclid(chunk_sz), z.div_euclid(chunk_sz));
+        let (local_x, local_z) = (x.rem_euclid(chunk_sz) as usize, z.rem_euclid(chunk_sz) as usize);
+        self.chunks.get(&chunk_pos).map(|heights| heights[local_x + local_z * CHUNK_SZ])
+    }
+
+    /// Bilinearly interpolated surface height, `None` if the column is not generated
+    pub fn sample(&self, x: f32, z: f32) -> Option<f32> {
+        let (x0, z0) = (x.floor(), z.floor());
+        let (tx, tz) = (x - x0, z - z0);
+        let (x0, z0) = (x0 as i32, z0 as i32);
+        let h00 = self.column(x0, z0)?;
+        // Neighbors may be in a chunk that is not generated yet
+        let h10 = self.column(x0 + 1, z0).unwrap_or(h00);
+        let h01 = self.column(x0, z0 + 1).unwrap_or(h00);
+        let h11 = self.column(x0 + 1, z0 + 1).unwrap_or(h00);
+        let h0 = h00 + (h10 - h00) * tx;
+        let h1 = h01 + (h11 - h01) * tx;
+        Some(h0 + (h1 - h0) * tz)
+    }
+
+    /// Cast straight down from `origin`, a hit if the surface is at most `max_dist` below it
+    pub fn cast_down(&self, origin: Vec3, max_dist: f32) -> Option<TerrainHit> {
+        let height = self.sample(origin.x, origin.z)?;
+        let toi = (origin.y - height).max(0.0);
+        if toi > max_dist { return None; }
+        let height_at = |dx: f32, dz: f32| self.sample(origin.x + dx, origin.z + dz).unwrap_or(height);
+        let normal = Vec3::new(
+            height_at(-1.0, 0.0) - height_at(1.0, 0.0),
+            2.0,
+            height_at(0.0, -1.0) - height_at(0.0, 1.0),
+        ).normalize();
+        Some(TerrainHit { toi, normal })
+    }
+}
+
+/// Where a downward cast meets the heightmap surface, like a shape cast against the chunk colliders
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TerrainHit {
+    /// Zero when the cast starts below the surface
+    pub toi: f32,
+    pub normal: Vec3,
+}
+
+/// Tints every chunk a different color so chunk borders are visible
+#[derive(Resource, Default)]
+pub struct ChunkDebugColoring {
+    pub enabled: bool,
+}
+
+/// Draws chunk triangle edges, requires the `WireframePlugin` and a device with polygon line mode
+#[derive(Resource, Default)]
+pub struct ChunkWireframe {
+    pub enabled: bool,
+}
+
+/// Limits how many dirty chunks are meshed in one frame, the rest wait for later frames
+#[derive(Resource)]
+pub struct ChunkMeshingBudget {
+    pub max_chunks_per_frame: usize,
+}
+
+/// Starting size of the mesh buffers shared by all chunks. A chunk that needs more grows them and is meshed again,
+/// up to [`MAX_VERTICES_PER_VOXEL`] and [`MAX_INDICES_PER_VOXEL`] for every voxel.
+#[derive(Resource)]
+pub struct VoxelBufferSettings {
+    pub initial_vertices: usize,
+    pub initial_indices: usize,
+}
+
+/// Outlines every loaded chunk, toggled with the `chunk_bounds` console command
+#[derive(Resource, Default)]
+pub struct ChunkBoundsGizmos {
+    pub enabled: bool,
+}
+
+/// How terrain normals are generated, switched with the `shading <smooth|flat>` console command
+#[derive(Resource, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ShadingMode {
+    /// Normals from the density gradient, interpolated across triangles
+    #[default]
+    Smooth,
+    /// Every triangle gets its own vertices with the face normal, for faceted terrain
+    Flat,
+}
+
+/// Material the chunk had before debug coloring replaced it
+#[derive(Component)]
+pub struct ChunkDebugMaterial {
+    original: Handle<TerrainMaterial>,
 }
 
 #[derive(Component)]
@@ -34,6 +277,22 @@ pub struct Map {
     pub chunks: HashMap<IVec3, Entity>,
 }
 
+impl Default for ChunkMeshingBudget {
+    fn default() -> Self {
+        Self { max_chunks_per_frame: 4 }
+    }
+}
+
+impl Default for VoxelBufferSettings {
+    fn default() -> Self {
+        // Enough for a bumpy heightmap surface crossing every column a few times
+        Self {
+            initial_vertices: CHUNK_SZ_2 * 12,
+            initial_indices: CHUNK_SZ_2 * 12,
+        }
+    }
+}
+
 impl Default for Map {
     fn default() -> Self {
         Self {
@@ -46,7 +305,63 @@ impl Chunk {
     pub fn new(position: IVec3) -> Self {
         let mut voxels = Vec::with_capacity(CHUNK_SZ_3);
         voxels.resize(CHUNK_SZ_3, Voxel::default());
-        Self { position, voxels }
+        Self { position, voxels, dirty: true, fill: None }
+    }
+
+    /// Voxel sampled closest to a world position, none if that is outside of this chunk
+    pub fn voxel_at(&self, world_pos: Vec3) -> Option<&Voxel> {
+        let local = world_pos.round().as_ivec3() - self.position * CHUNK_SZ as i32;
+        if local.cmplt(IVec3::ZERO).any() || local.cmpge(IVec3::splat(CHUNK_SZ as i32)).any() {
+            return None;
+        }
+        self.voxels.get(local.x as usize + local.y as usize * CHUNK_SZ + local.z as usize * CHUNK_SZ_2)
+    }
+}
+
+/// Spawn a chunk with an empty mesh, its voxels are generated and meshed once it is wanted.
+/// Vertices and the collider are relative to the chunk corner, so the entity is placed there.
+pub fn spawn_chunk(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    material: Handle<TerrainMaterial>,
+    position: IVec3,
+) -> Entity {
+    commands.spawn((
+        Chunk::new(position),
+        CollisionGroups::new(TERRAIN_GROUP, Group::ALL),
+        MaterialMeshBundle {
+            mesh: meshes.add(empty_chunk_mesh()),
+            material,
+            transform: Transform::from_translation(position.as_vec3() * CHUNK_SZ as f32),
+            ..default()
+        },
+    )).id()
+}
+
+/// Attributes are refilled in place every time the chunk is meshed, see [`fill_chunk_mesh`]
+fn empty_chunk_mesh() -> Mesh {
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.set_indices(Some(Indices::U32(Vec::with_capacity(4096))));
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, VertexAttributeValues::Float32x3(Vec::with_capacity(4096)));
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, VertexAttributeValues::Float32x3(Vec::with_capacity(4096)));
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, VertexAttributeValues::Float32x2(Vec::with_capacity(4096)));
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, VertexAttributeValues::Float32x4(Vec::with_capacity(4096)));
+    mesh
+}
+
+impl ChunkFill {
+    pub fn classify(voxels: &[Voxel]) -> Self {
+        // Block voxels are meshed as cubes regardless of density
+        if voxels.iter().any(|voxel| voxel.flags != 0) {
+            return ChunkFill::Mixed;
+        }
+        if voxels.iter().all(|voxel| voxel.density <= 0.0) {
+            ChunkFill::Empty
+        } else if voxels.iter().all(|voxel| voxel.density >= 1.0) {
+            ChunkFill::Solid
+        } else {
+            ChunkFill::Mixed
+        }
     }
 }
 
@@ -57,17 +372,50 @@ impl Chunk {
 //     }
 // }
 
-#[derive(Copy, Clone, Default, Pod, Zeroable)]
+#[derive(Copy, Clone, Default, Debug, Pod, Zeroable, Serialize, Deserialize)]
 #[repr(C)]
 pub struct Voxel {
-    flags: u32,
-    density: f32,
+    pub flags: u32,
+    pub density: f32,
+    #[serde(default)]
+    pub material: u32,
+}
+
+/// Extension point for carving structures, placing ores, etc. into generated terrain
+#[derive(Resource, Default)]
+pub struct ChunkGenerationHooks(pub Vec<ChunkGenerationHook>);
+
+impl ChunkGenerationHooks {
+    pub fn add(&mut self, hook: impl Fn(IVec3, &mut [Voxel]) + Send + Sync + 'static) -> &mut Self {
+        self.0.push(Box::new(hook));
+        self
+    }
 }
 
 #[derive(Resource)]
 pub struct VoxelsPipeline {
     simplex_pipeline: ComputePipeline,
     voxels_pipeline: ComputePipeline,
+    /// Mixed into mesh cache keys, see [`hash_shaders`]
+    shader_hash: u64,
+}
+
+/// Creating the pipelines failed, they are only retried once a shader changes on disk
+#[derive(Resource)]
+pub struct VoxelsPipelineFailed;
+
+/// Compute shaders behind [`VoxelsPipeline`], rebuilt whenever these are modified
+#[derive(Resource)]
+pub struct VoxelShaders {
+    simplex: Handle<Shader>,
+    voxels: Handle<Shader>,
+}
+
+#[derive(Debug, Error)]
+#[error("failed to create {label}: {source}")]
+pub struct VoxelsPipelineError {
+    label: String,
+    source: wgpu::Error,
 }
 
 #[derive(Resource)]
@@ -88,6 +436,137 @@ pub struct VoxelBuffers {
     atomics_staging: Buffer,
 }
 
+impl VoxelBuffers {
+    /// Make room for a mesh of this size, with headroom so that similar chunks do not grow them again
+    fn grow_mesh(&mut self, vertex_count: usize, index_count: usize, device: &RenderDevice) {
+        let vertex_capacity = usize::min(vertex_count.next_power_of_two(), CHUNK_SZ_3 * MAX_VERTICES_PER_VOXEL);
+        let index_capacity = usize::min(index_count.next_power_of_two(), CHUNK_SZ_3 * MAX_INDICES_PER_VOXEL);
+        self.vertices.reserve(vertex_capacity, device);
+        self.normals.reserve(vertex_capacity, device);
+        self.uvs.reserve(vertex_capacity, device);
+        self.indices.reserve(index_capacity, device);
+    }
+
+    /// Copy the counts and all of the mesh buffers into the back staging buffers, the counts are only known once the pass has run
+    fn encode_mesh_read(&mut self, command_encoder: &mut CommandEncoder) {
+        self.atomics.encode_read(2, command_encoder);
+        self.vertices.encode_read(self.vertices.capacity(), command_encoder);
+        self.normals.encode_read(self.normals.capacity(), command_encoder);
+        self.uvs.encode_read(self.uvs.capacity(), command_encoder);
+        self.indices.encode_read(self.indices.capacity(), command_encoder);
+    }
+
+    fn map_mesh(&mut self) {
+        self.atomics.map_buffer(2);
+        self.vertices.map_buffer(self.vertices.capacity());
+        self.normals.map_buffer(self.normals.capacity());
+        self.uvs.map_buffer(self.uvs.capacity());
+        self.indices.map_buffer(self.indices.capacity());
+    }
+
+    fn swap_mesh_staging(&mut self) {
+        self.atomics.swap_staging();
+        self.vertices.swap_staging();
+        self.normals.swap_staging();
+        self.uvs.swap_staging();
+        self.indices.swap_staging();
+    }
+
+    /// Read the mesh out of the front staging buffers, the device must have been polled since they were mapped
+    fn read_mesh(&mut self) -> Result<MeshReadback, BufError> {
+        let counts = self.atomics.try_read_and_unmap_buffer(2).map(|counts| (counts[0] as usize, counts[1] as usize));
+        // Every staging buffer is read even when the mesh is unusable, reading is what unmaps them
+        let (vertex_count, index_count) = counts.as_ref().copied().unwrap_or((0, 0));
+        let vertex_len = vertex_count.min(self.vertices.capacity());
+        let index_len = index_count.min(self.indices.capacity());
+        let reads = [
+            self.vertices.try_read_and_unmap_buffer(vertex_len).err(),
+            self.normals.try_read_and_unmap_buffer(vertex_len).err(),
+            self.uvs.try_read_and_unmap_buffer(vertex_len).err(),
+            self.indices.try_read_and_unmap_buffer(index_len).err(),
+        ];
+        let (vertex_count, index_count) = counts?;
+        if let Some(err) = reads.into_iter().flatten().next() {
+            return Err(err);
+        }
+        if vertex_count > self.vertices.capacity() || index_count > self.indices.capacity() {
+            return Ok(MeshReadback::Overflow { vertex_count, index_count });
+        }
+        Ok(MeshReadback::Mesh(ChunkMeshData {
+            positions: self.vertices.iter().map(|v| [v[0], v[1], v[2]]).collect(),
+            normals: self.normals.iter().map(|v| [v[0], v[1], v[2]]).collect(),
+            uvs: self.uvs.iter().map(|v| (*v).into()).collect(),
+            indices: self.indices.as_slice().to_vec(),
+        }))
+    }
+}
+
+enum MeshReadback {
+    Mesh(ChunkMeshData),
+    /// Writes past the end of the buffers were dropped, holds the counts they need room for
+    Overflow { vertex_count: usize, index_count: usize },
+}
+
+/// Chunk whose voxels pass was submitted, its mesh is in the front staging buffers once the submission is done
+struct PendingChunkMesh {
+    entity: Entity,
+    position: IVec3,
+    voxel_hash: u64,
+    submission: wgpu::SubmissionIndex,
+}
+
+/// Meshes gathered by one run of [`voxel_polygonize_system`], applied to the chunks after every chunk was dispatched
+#[derive(Default)]
+struct MeshReadbacks {
+    pending: Option<PendingChunkMesh>,
+    /// Mesh buffers are grown once nothing is mapped anymore
+    grow_to: Option<(usize, usize)>,
+    ready: Vec<(Entity, ChunkMeshData)>,
+}
+
+impl MeshReadbacks {
+    /// Wait for the pending chunk alone, later submissions keep running, and read its mesh
+    fn finish_pending(
+        &mut self,
+        buffers: &mut VoxelBuffers,
+        render_device: &RenderDevice,
+        mesh_cache: &ChunkMeshCache,
+        query: &mut Query<(Entity, &Handle<Mesh>, &mut Chunk)>,
+    ) {
+        let Some(pending) = self.pending.take() else { return; };
+        render_device.poll(wgpu::Maintain::WaitForSubmissionIndex(pending.submission));
+        let data = match buffers.read_mesh() {
+            Ok(MeshReadback::Mesh(data)) => data,
+            Ok(MeshReadback::Overflow { vertex_count, index_count }) => {
+                // Mesh again next frame with room for all of it
+                let (vertex_capacity, index_capacity) = self.grow_to.unwrap_or_default();
+                self.grow_to = Some((vertex_capacity.max(vertex_count), index_capacity.max(index_count)));
+                if let Ok((_, _, mut chunk)) = query.get_mut(pending.entity) {
+                    chunk.dirty = true;
+                }
+                return;
+            }
+            Err(err) => {
+                error!("Failed to read mesh of chunk {}: {}", pending.position, err);
+                if let Ok((_, _, mut chunk)) = query.get_mut(pending.entity) {
+                    chunk.dirty = true;
+                }
+                return;
+            }
+        };
+        if data.positions.is_empty() {
+            return;
+        }
+
+        if mesh_cache.enabled {
+            if let Err(err) = mesh_cache.save(pending.position, pending.voxel_hash, &data) {
+                warn!("Failed to cache mesh of chunk {}: {}", pending.position, err);
+            }
+        }
+        self.ready.push((pending.entity, data));
+    }
+}
+
 struct BindingGroups {
     simplex: BindGroup,
     voxels: BindGroup,
@@ -98,14 +577,145 @@ pub struct VoxelsPlugin;
 impl Plugin for VoxelsPlugin {
     fn build(&self, app: &mut App) {
         app
+            .init_resource::<ChunkDebugColoring>()
+            .init_resource::<ChunkWireframe>()
+            .init_resource::<ChunkBoundsGizmos>()
+            .init_resource::<ChunkMeshingBudget>()
+            .init_resource::<VoxelBufferSettings>()
+            .init_resource::<ShadingMode>()
+            .init_resource::<TerrainColorRamp>()
+            .init_resource::<TerrainHeightmap>()
+            .init_resource::<ChunkGenerationHooks>()
+            .init_resource::<TerrainDensity>()
+            .init_resource::<ChunkMeshCache>()
+            .add_systems(Startup, load_voxel_shaders_sys)
             .add_systems(PreUpdate, (
-                init_pipeline_system.run_if(not(resource_exists::<VoxelsPipeline>())),
-                voxel_polygonize_system.run_if(resource_exists::<VoxelsPipeline>()),
+                init_buffers_system.run_if(not(resource_exists::<VoxelBuffers>())),
+                init_pipeline_system
+                    .run_if(not(resource_exists::<VoxelsPipeline>()))
+                    .run_if(not(resource_exists::<VoxelsPipelineFailed>())),
+                reload_voxel_shaders_sys.run_if(resource_exists::<VoxelShaders>()),
+                voxel_polygonize_system
+                    .run_if(resource_exists::<VoxelsPipeline>())
+                    .run_if(resource_exists::<VoxelBuffers>()),
+                apply_chunk_collider_sys,
+            ))
+            .add_systems(Update, (
+                (toggle_chunk_debug_sys, chunk_debug_coloring_sys).chain(),
+                (toggle_chunk_wireframe_sys, chunk_wireframe_sys).chain(),
+                (chunk_bounds_command_sys, draw_chunk_bounds_sys).chain(),
+                shading_command_sys,
             ));
     }
 }
 
-fn init_pipeline_system(mut commands: Commands, render_device: Res<RenderDevice>) {
+/// Run `create` in an error scope, so invalid shaders and pipelines are reported instead of panicking
+fn validate<T>(render_device: &RenderDevice, label: String, create: impl FnOnce() -> T) -> Result<T, VoxelsPipelineError> {
+    let device = render_device.wgpu_device();
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let value = create();
+    match future::block_on(device.pop_error_scope()) {
+        Some(source) => Err(VoxelsPipelineError { label, source }),
+        None => Ok(value),
+    }
+}
+
+fn create_compute_pipeline(render_device: &RenderDevice, name: &str, shader_source: &str) -> Result<ComputePipeline, VoxelsPipelineError> {
+    let shader_label = format!("{} shader", name);
+    let shader = validate(render_device, shader_label.clone(), || render_device.create_shader_module(ShaderModuleDescriptor {
+        label: Some(&shader_label),
+        source: ShaderSource::Wgsl(shader_source.into()),
+    }))?;
+    // TODO:arch update to Bevy compute creation when they allow PipelineCache to be used in main world
+    let pipeline_label = format!("{} pipeline", name);
+    validate(render_device, pipeline_label.clone(), || render_device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some(&pipeline_label),
+        layout: None,
+        module: &shader,
+        entry_point: "main",
+    }))
+}
+
+/// None until both shaders have loaded
+fn create_voxels_pipeline(
+    render_device: &RenderDevice, shaders: &VoxelShaders, shader_assets: &Assets<Shader>,
+) -> Option<Result<VoxelsPipeline, VoxelsPipelineError>> {
+    let wgsl = |handle: &Handle<Shader>| match &shader_assets.get(handle)?.source {
+        Source::Wgsl(source) => Some(source.clone()),
+        _ => None,
+    };
+    let (simplex_source, voxels_source) = (wgsl(&shaders.simplex)?, wgsl(&shaders.voxels)?);
+    Some(create_compute_pipeline(render_device, "simplex", &simplex_source).and_then(|simplex_pipeline| {
+        create_compute_pipeline(render_device, "voxels", &voxels_source)
+            .map(|voxels_pipeline| VoxelsPipeline {
+                simplex_pipeline,
+                voxels_pipeline,
+                shader_hash: hash_shaders(&[&*simplex_source, &*voxels_source]),
+            })
+    }))
+}
+
+pub fn load_voxel_shaders_sys(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(VoxelShaders {
+        simplex: asset_server.load("shaders/simplex.wgsl"),
+        voxels: asset_server.load("shaders/voxels.wgsl"),
+    });
+}
+
+fn init_pipeline_system(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    shaders: Option<Res<VoxelShaders>>,
+    shader_assets: Res<Assets<Shader>>,
+) {
+    let Some(shaders) = shaders else { return; };
+    let Some(pipeline) = create_voxels_pipeline(&render_device, &shaders, &shader_assets) else { return; };
+    match pipeline {
+        Ok(pipeline) => commands.insert_resource(pipeline),
+        Err(err) => {
+            error!("Terrain generation is disabled until the shader is fixed: {}", err);
+            commands.insert_resource(VoxelsPipelineFailed);
+        }
+    }
+}
+
+/// Swap in new pipelines when a shader file changes and regenerate every chunk with them.
+/// A broken edit keeps the previous pipelines running.
+pub fn reload_voxel_shaders_sys(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    shaders: Res<VoxelShaders>,
+    shader_assets: Res<Assets<Shader>>,
+    mut shader_events: EventReader<AssetEvent<Shader>>,
+    mesh_cache: Res<ChunkMeshCache>,
+    mut chunk_query: Query<&mut Chunk>,
+) {
+    let is_modified = shader_events.read().any(|event| match event {
+        AssetEvent::Modified { id } => *id == shaders.simplex.id() || *id == shaders.voxels.id(),
+        _ => false,
+    });
+    if !is_modified { return; }
+    let Some(pipeline) = create_voxels_pipeline(&render_device, &shaders, &shader_assets) else { return; };
+    match pipeline {
+        Ok(pipeline) => {
+            info!("Reloaded voxel shaders");
+            commands.insert_resource(pipeline);
+            commands.remove_resource::<VoxelsPipelineFailed>();
+            // Cached meshes came from the old shader
+            mesh_cache.clear();
+            for mut chunk in chunk_query.iter_mut() {
+                chunk.dirty = true;
+            }
+        }
+        Err(err) => error!("Keeping the previous voxel shaders: {}", err),
+    }
+}
+
+fn init_buffers_system(mut commands: Commands, settings: Res<VoxelBufferSettings>, render_device: Res<RenderDevice>) {
+    let max_vertices = CHUNK_SZ_3 * MAX_VERTICES_PER_VOXEL;
+    let max_indices = CHUNK_SZ_3 * MAX_INDICES_PER_VOXEL;
+    let vertex_capacity = settings.initial_vertices.clamp(1, max_vertices);
+    let index_capacity = settings.initial_indices.clamp(1, max_indices);
     let edge_table = render_device.create_buffer_with_data(&BufferInitDescriptor {
         label: Some("edge table buffer"),
         contents: cast_slice(EDGE_TABLE),
@@ -116,8 +726,8 @@ fn init_pipeline_system(mut commands: Commands, render_device: Res<RenderDevice>
         contents: cast_slice(TRI_TABLE),
         usage: BufferUsages::STORAGE,
     });
-    let points: BufVec<Vec2> = BufVec::with_capacity(false, CHUNK_SZ_2, render_device.as_ref());
-    let heights: BufVec<f32> = BufVec::with_capacity(true, CHUNK_SZ_2, render_device.as_ref());
+    let points: BufVec<Vec2> = BufVec::with_capacity("points", false, false, CHUNK_SZ_2, render_device.as_ref());
+    let heights: BufVec<f32> = BufVec::with_capacity("heights", true, false, CHUNK_SZ_2, render_device.as_ref());
     let voxels = render_device.create_buffer(&BufferDescriptor {
         label: Some("voxels buffer"),
         size: (CHUNK_SZ_3 * size_of::<Voxel>()) as BufferAddress,
@@ -130,46 +740,19 @@ fn init_pipeline_system(mut commands: Commands, render_device: Res<RenderDevice>
         usage: BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
         mapped_at_creation: false,
     });
-    let vertices: BufVec<Vec4> = BufVec::with_capacity(true, CHUNK_SZ_3 * 4 * 6, render_device.as_ref());
-    let uvs: BufVec<Vec2> = BufVec::with_capacity(true, CHUNK_SZ_3 * 4 * 6, render_device.as_ref());
-    let normals: BufVec<Vec4> = BufVec::with_capacity(true, CHUNK_SZ_3 * 4 * 6, render_device.as_ref());
-    let indices: BufVec<u32> = BufVec::with_capacity(true, CHUNK_SZ_3 * 6 * 6, render_device.as_ref());
-    let atomics: BufVec<u32> = BufVec::with_capacity(true, 2, render_device.as_ref());
+    // Double buffered so reading one chunk back overlaps with meshing the next
+    let vertices: BufVec<Vec4> = BufVec::with_capacity("vertices", true, true, vertex_capacity, render_device.as_ref());
+    let uvs: BufVec<Vec2> = BufVec::with_capacity("uvs", true, true, vertex_capacity, render_device.as_ref());
+    let normals: BufVec<Vec4> = BufVec::with_capacity("normals", true, true, vertex_capacity, render_device.as_ref());
+    let indices: BufVec<u32> = BufVec::with_capacity("indices", true, true, index_capacity, render_device.as_ref());
+    let atomics: BufVec<u32> = BufVec::with_capacity("atomics", true, true, 2, render_device.as_ref());
     let atomics_staging = render_device.create_buffer_with_data(&BufferInitDescriptor {
-        label: Some("atomics staging buffer"),
+        label: Some("atomics reset buffer"),
         contents: cast_slice(&[0u32, 0u32]),
         usage: BufferUsages::COPY_SRC,
     });
 
-    // let simplex_shader = asset_server.load("shaders/simplex.wgsl");
-    let shader_source = include_str!("../../assets/shaders/simplex.wgsl");
-    let shader = render_device.create_shader_module(ShaderModuleDescriptor {
-        label: Some("simplex shader"),
-        source: ShaderSource::Wgsl(shader_source.into()),
-    });
-    // TODO:arch update to Bevy compute creation when they allow PipelineCache to be used in main world
-    let simplex_pipeline = render_device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-        label: Some("simplex pipeline"),
-        layout: None,
-        module: &shader,
-        entry_point: "main",
-    });
-
-    // let voxel_shader = asset_server.load("shaders/voxels.wgsl");
-    let shader_source = include_str!("../../assets/shaders/voxels.wgsl");
-    let shader = render_device.create_shader_module(ShaderModuleDescriptor {
-        label: Some("voxels shader"),
-        source: ShaderSource::Wgsl(shader_source.into()),
-    });
-    let voxels_pipeline = render_device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-        label: Some("voxels pipeline"),
-        layout: None,
-        module: &shader,
-        entry_point: "main",
-    });
-
     commands.insert_resource(VoxelBuffers { edge_table, tri_table, points, heights, voxels, voxels_staging, vertices, normals, uvs, indices, atomics, atomics_staging });
-    commands.insert_resource(VoxelsPipeline { simplex_pipeline, voxels_pipeline });
 }
 
 pub fn _sync_added_chunks_system(
@@ -183,28 +766,72 @@ pub fn _sync_added_chunks_system(
     }
 }
 
+/// Whether a chunk should be meshed now, chunks out of view stay dirty until they come into view.
+/// Chunks next to the camera are always meshed so the ground under the player gets a collider.
+pub fn is_chunk_mesh_wanted(position: IVec3, camera: Option<(&Frustum, Vec3)>) -> bool {
+    let Some((frustum, camera_pos)) = camera else { return true; };
+    let camera_chunk = (camera_pos / CHUNK_SZ as f32).floor().as_ivec3();
+    if (position - camera_chunk).abs().max_element() <= 1 { return true; }
+
+    let min = position.as_vec3() * CHUNK_SZ as f32;
+    let aabb = Aabb::from_min_max(min, min + Vec3::splat(CHUNK_SZ as f32));
+    frustum.intersects_obb(&aabb, &Affine3A::IDENTITY, true, false)
+}
+
 pub fn voxel_polygonize_system(
     mut commands: Commands,
     mut query: Query<(Entity, &Handle<Mesh>, &mut Chunk)>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut buffers: ResMut<VoxelBuffers>,
-    time: Res<Time>,
+    color_ramp: Res<TerrainColorRamp>,
+    mut heightmap: ResMut<TerrainHeightmap>,
+    generators: ChunkGenerators,
+    seed: Res<WorldSeed>,
+    budget: Res<ChunkMeshingBudget>,
+    shading: Res<ShadingMode>,
     render_device: Res<RenderDevice>,
     render_queue: Res<RenderQueue>,
     pipeline: Res<VoxelsPipeline>,
+    mesh_cache: Res<ChunkMeshCache>,
+    camera_query: Query<(&Frustum, &GlobalTransform), With<RenderPlayer>>,
 ) {
     // let now = std::time::Instant::now();
 
-    for (entity, mesh, mut chunk) in query.iter_mut() {
+    // Split-screen has a camera per local player, a chunk is wanted if any of them wants it
+    let cameras: Vec<(&Frustum, Vec3)> = camera_query.iter().map(|(frustum, transform)| (frustum, transform.translation())).collect();
+    let is_wanted = |position: IVec3| cameras.is_empty() || cameras.iter().any(|&camera| is_chunk_mesh_wanted(position, Some(camera)));
+    // Closest chunks first, so terrain around the players fills in before terrain in the distance
+    let mut queue: Vec<(Entity, f32)> = query.iter()
+        .filter(|(_, _, chunk)| chunk.dirty && is_wanted(chunk.position))
+        .map(|(entity, _, chunk)| {
+            let center = (chunk.position.as_vec3() + 0.5) * CHUNK_SZ as f32;
+            let distance = cameras.iter()
+                .map(|&(_, camera_pos)| center.distance_squared(camera_pos))
+                .reduce(f32::min)
+                .unwrap_or_else(|| center.length_squared());
+            (entity, distance)
+        })
+        .collect();
+    queue.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+    queue.truncate(budget.max_chunks_per_frame);
+
+    let mut readbacks = MeshReadbacks::default();
+    for (entity, _) in queue {
+        let Ok((entity, mesh, mut chunk)) = query.get_mut(entity) else { continue; };
+        chunk.dirty = false;
+
         buffers.atomics.clear();
         buffers.atomics.push(0);
         buffers.atomics.push(0);
 
-        let time = time.elapsed().as_secs_f32();
+        // Heights are read back indexed by `x + z * CHUNK_SZ`
+        let chunk_origin = chunk.position.xz().as_vec2() * CHUNK_SZ as f32;
+        let noise_offset = seed.noise_offset();
         buffers.points.clear();
-        for x in 0..CHUNK_SZ {
-            for y in 0..CHUNK_SZ {
-                buffers.points.push(0.05 * Vec2::new(x as f32 + time, y as f32 + time));
+        for z in 0..CHUNK_SZ {
+            for x in 0..CHUNK_SZ {
+                let column = chunk_origin + Vec2::new(x as f32, z as f32);
+                buffers.points.push(0.05 * column + noise_offset);
             }
         }
 
@@ -233,7 +860,11 @@ pub fn voxel_polygonize_system(
             ),
         };
 
-        if !buffers.points.is_empty() {
+        let chunk_height = (chunk.position.y * CHUNK_SZ as i32) as f32;
+        if let Some(field) = &generators.density.0 {
+            fill_voxels(field.as_ref(), chunk.position, &mut chunk.voxels);
+            heightmap.insert_chunk(IVec2::new(chunk.position.x, chunk.position.z), column_surface_heights(&chunk.voxels, chunk_height));
+        } else if !buffers.points.is_empty() {
             let mut command_encoder = render_device.create_command_encoder(&CommandEncoderDescriptor { label: Some("simplex command encoder") });
             buffers.points.encode_write(render_queue.as_ref(), &mut command_encoder);
             {
@@ -242,35 +873,61 @@ pub fn voxel_polygonize_system(
                 pass.set_bind_group(0, &binding_groups.simplex, &[]);
                 pass.dispatch_workgroups((CHUNK_SZ / 32) as u32, (CHUNK_SZ / 32) as u32, 1);
             }
-            buffers.heights.encode_read(CHUNK_SZ_2, &mut command_encoder);
             render_queue.submit(once(command_encoder.finish()));
-            buffers.heights.map_buffer(CHUNK_SZ_2);
-            render_device.poll(Wait);
-            buffers.heights.read_and_unmap_buffer(CHUNK_SZ_2);
+            if let Err(err) = buffers.heights.read_back(CHUNK_SZ_2, &render_device, &render_queue) {
+                error!("Failed to read heights of chunk {}: {}", chunk.position, err);
+                chunk.dirty = true;
+                continue;
+            }
 
-            for z in 0..CHUNK_SZ {
-                for y in 0..CHUNK_SZ {
-                    for x in 0..CHUNK_SZ {
-                        let noise01 = (buffers.heights.as_slice()[x + z * CHUNK_SZ] + 1.0) * 0.5;
-                        let height = noise01 * 4.0 + 8.0 - (y as f32);
-                        let mut density = 0.0;
-
-                        if height > 1.0 {
-                            density = 1.0;
-                        } else if height > 0.0 {
-                            density = height;
-                        }
-                        // voxels.0[x + y * CHUNK_SZ + z * CHUNK_SZ_2] = Voxel {
-                        //     flags: if z == (noise01 * 4.0) as usize { 1 } else { 0 },
-                        //     density: 0.0,
-                        // };
-                        chunk.voxels[x + y * CHUNK_SZ + z * CHUNK_SZ_2] = Voxel {
-                            flags: 0,
-                            density,
-                        };
-                    }
-                }
+            let chunk_origin = chunk.position.as_vec3() * CHUNK_SZ as f32;
+            let heights = buffers.heights.as_slice();
+            let field = HeightmapDensity(|column: Vec2| {
+                let local = column - chunk_origin.xz();
+                let noise = heights[local.x as usize + local.y as usize * CHUNK_SZ];
+                chunk_height + noise_surface_height(noise)
+            });
+            fill_voxels(&field, chunk.position, &mut chunk.voxels);
+
+            // The surface is where density crosses one half
+            let surface_heights = buffers.heights.iter()
+                .map(|&noise| chunk_height + noise_surface_height(noise) - 0.5)
+                .collect();
+            heightmap.insert_chunk(IVec2::new(chunk.position.x, chunk.position.z), surface_heights);
+        }
+
+        let position = chunk.position;
+        for hook in generators.hooks.0.iter() {
+            hook(position, &mut chunk.voxels);
+        }
+        for placed in generators.structures.placed.iter() {
+            if let Some(template) = generators.structure_templates.get(&placed.template) {
+                stamp_structure(template, placed.origin, placed.mode, position, &mut chunk.voxels);
+            }
+        }
+
+        let fill = ChunkFill::classify(&chunk.voxels);
+        chunk.fill = Some(fill);
+        if fill != ChunkFill::Mixed {
+            if let Some(mesh) = meshes.get_mut(mesh) {
+                clear_mesh(mesh);
             }
+            commands.entity(entity).remove::<(Collider, ChunkColliderTask)>();
+            continue;
+        }
+
+        let voxel_hash = mesh_cache_key(hash_voxels(&chunk.voxels), pipeline.shader_hash);
+        let cached = if mesh_cache.enabled {
+            mesh_cache.load(position, voxel_hash).unwrap_or_else(|err| {
+                warn!("Failed to load cached mesh of chunk {}: {}", position, err);
+                None
+            })
+        } else {
+            None
+        };
+        if let Some(data) = cached {
+            readbacks.ready.push((entity, data));
+            continue;
         }
 
         let mut command_encoder = render_device.create_command_encoder(&CommandEncoderDescriptor { label: Some("voxel 1 command encoder") });
@@ -284,66 +941,610 @@ pub fn voxel_polygonize_system(
             let dispatch_size = (CHUNK_SZ / 8) as u32;
             pass.dispatch_workgroups(dispatch_size, dispatch_size, dispatch_size);
         }
-        buffers.atomics.encode_read(2, &mut command_encoder);
-        render_queue.submit(once(command_encoder.finish()));
-        buffers.atomics.map_buffer(2);
-        render_device.poll(Wait);
-        buffers.atomics.read_and_unmap_buffer(2);
-        let vertex_count = buffers.atomics.as_slice()[0] as usize;
-        let index_count = buffers.atomics.as_slice()[1] as usize;
+        buffers.encode_mesh_read(&mut command_encoder);
+        let submission = render_queue.submit(once(command_encoder.finish()));
+        buffers.map_mesh();
+        // The previous chunk is read out of the other staging buffers while this one runs on the GPU
+        readbacks.finish_pending(&mut buffers, &render_device, &mesh_cache, &mut query);
+        buffers.swap_mesh_staging();
+        readbacks.pending = Some(PendingChunkMesh { entity, position, voxel_hash, submission });
+    }
+    readbacks.finish_pending(&mut buffers, &render_device, &mesh_cache, &mut query);
+    if let Some((vertex_count, index_count)) = readbacks.grow_to {
+        buffers.grow_mesh(vertex_count, index_count, &render_device);
+    }
 
-        if vertex_count == 0 {
-            continue;
+    for (entity, data) in readbacks.ready {
+        let Ok((entity, mesh, chunk)) = query.get(entity) else { continue; };
+        let chunk_height = (chunk.position.y * CHUNK_SZ as i32) as f32;
+        let mesh = meshes.get_mut(mesh).unwrap();
+        if let Err(err) = fill_chunk_mesh(mesh, &data, chunk_height, &color_ramp, *shading) {
+            error!("Failed to generate tangents of chunk {}: {}", chunk.position, err);
         }
 
-        let mut command_encoder = render_device.create_command_encoder(&CommandEncoderDescriptor { label: Some("voxel 2 command encoder") });
-        buffers.vertices.encode_read(vertex_count, &mut command_encoder);
-        buffers.normals.encode_read(vertex_count, &mut command_encoder);
-        buffers.uvs.encode_read(vertex_count, &mut command_encoder);
-        buffers.indices.encode_read(index_count, &mut command_encoder);
-        render_queue.submit(once(command_encoder.finish()));
-        buffers.vertices.map_buffer(vertex_count);
-        buffers.normals.map_buffer(vertex_count);
-        buffers.uvs.map_buffer(vertex_count);
-        buffers.indices.map_buffer(index_count);
-        render_device.poll(Wait);
+        // Replacing a task that is still running drops it, which cancels it
+        let vertices: Vec<Vec3> = data.positions.into_iter().map(Vec3::from).collect();
+        let indices: Vec<[u32; 3]> = data.indices.chunks_exact(3).map(|tri| [tri[0], tri[1], tri[2]]).collect();
+        let task = AsyncComputeTaskPool::get().spawn(async move { Collider::trimesh(vertices, indices) });
+        commands.entity(entity).insert(ChunkColliderTask(task));
+    }
 
-        buffers.vertices.read_and_unmap_buffer(vertex_count);
-        buffers.normals.read_and_unmap_buffer(vertex_count);
-        buffers.uvs.read_and_unmap_buffer(vertex_count);
-        buffers.indices.read_and_unmap_buffer(index_count);
+    // println!("Elapsed: {:.2?}", now.elapsed());
+}
 
-        let mesh = meshes.get_mut(mesh).unwrap();
+/// Sample `field` at every voxel of the chunk at `position`, one task per `z` slice
+pub fn fill_voxels(field: &dyn DensityField, position: IVec3, voxels: &mut [Voxel]) {
+    let origin = position * CHUNK_SZ as i32;
+    ComputeTaskPool::get().scope(|scope| {
+        for (z, slice) in voxels.chunks_mut(CHUNK_SZ_2).enumerate() {
+            scope.spawn(async move {
+                for y in 0..CHUNK_SZ {
+                    for x in 0..CHUNK_SZ {
+                        let world_pos = (origin + IVec3::new(x as i32, y as i32, z as i32)).as_vec3();
+                        slice[x + y * CHUNK_SZ] = Voxel {
+                            flags: 0,
+                            density: field.sample(world_pos),
+                            material: field.material(world_pos),
+                        };
+                    }
+                }
+            });
+        }
+    });
+}
+
+/// Highest density crossing of each column, indexed by `x + z * CHUNK_SZ`.
+/// Columns without a crossing are clamped to the top or bottom of the chunk.
+fn column_surface_heights(voxels: &[Voxel], chunk_height: f32) -> Vec<f32> {
+    let density = |x: usize, y: usize, z: usize| voxels[x + y * CHUNK_SZ + z * CHUNK_SZ_2].density;
+    let mut heights = Vec::with_capacity(CHUNK_SZ_2);
+    for z in 0..CHUNK_SZ {
+        for x in 0..CHUNK_SZ {
+            let top = CHUNK_SZ - 1;
+            let height = if density(x, top, z) >= 0.5 {
+                top as f32
+            } else {
+                (0..top).rev()
+                    .find(|&y| density(x, y, z) >= 0.5)
+                    .map_or(0.0, |y| {
+                        let (below, above) = (density(x, y, z), density(x, y + 1, z));
+                        y as f32 + (below - 0.5) / (below - above)
+                    })
+            };
+            heights.push(chunk_height + height);
+        }
+    }
+    heights
+}
+
+/// Color by position parity so that chunks sharing a face never share a color
+pub fn chunk_debug_color(position: IVec3) -> Color {
+    let parity = (position.x & 1) | (position.y & 1) << 1 | (position.z & 1) << 2;
+    Color::hsl(parity as f32 * 45.0, 0.8, 0.5)
+}
+
+pub fn toggle_chunk_debug_sys(
+    key_input: Res<Input<KeyCode>>,
+    config: Res<Assets<Config>>,
+    config_state: Res<ConfigState>,
+    mut debug: ResMut<ChunkDebugColoring>,
+) {
+    if let Some(config) = config.get(&config_state.handle) {
+        if key_input.just_pressed(config.key_debug_chunks) {
+            debug.enabled = !debug.enabled;
+        }
+    }
+}
+
+pub fn chunk_debug_coloring_sys(
+    mut commands: Commands,
+    debug: Res<ChunkDebugColoring>,
+    mut materials: ResMut<Assets<TerrainMaterial>>,
+    mut chunk_query: Query<(Entity, &Chunk, &mut Handle<TerrainMaterial>, Option<&ChunkDebugMaterial>)>,
+) {
+    for (entity, chunk, mut material, debug_material) in chunk_query.iter_mut() {
+        match (debug.enabled, debug_material) {
+            (true, None) => {
+                let original = material.clone();
+                *material = materials.add(terrain_material(StandardMaterial {
+                    base_color: chunk_debug_color(chunk.position),
+                    ..default()
+                }));
+                commands.entity(entity).insert(ChunkDebugMaterial { original });
+            }
+            (false, Some(debug_material)) => {
+                *material = debug_material.original.clone();
+                commands.entity(entity).remove::<ChunkDebugMaterial>();
+            }
+            _ => {}
+        }
+    }
+}
+
+pub fn toggle_chunk_wireframe_sys(
+    key_input: Res<Input<KeyCode>>,
+    config: Res<Assets<Config>>,
+    config_state: Res<ConfigState>,
+    mut wireframe: ResMut<ChunkWireframe>,
+    render_device: Option<Res<RenderDevice>>,
+) {
+    let is_supported = render_device.is_some_and(|device| device.features().contains(wgpu::Features::POLYGON_MODE_LINE));
+    if !is_supported { return; }
+    if let Some(config) = config.get(&config_state.handle) {
+        if key_input.just_pressed(config.key_wireframe) {
+            wireframe.enabled = !wireframe.enabled;
+        }
+    }
+}
+
+pub fn chunk_wireframe_sys(
+    mut commands: Commands,
+    wireframe: Res<ChunkWireframe>,
+    chunk_query: Query<(Entity, Has<Wireframe>), With<Chunk>>,
+) {
+    for (entity, has_wireframe) in chunk_query.iter() {
+        match (wireframe.enabled, has_wireframe) {
+            (true, false) => { commands.entity(entity).insert(Wireframe); }
+            (false, true) => { commands.entity(entity).remove::<Wireframe>(); }
+            _ => {}
+        }
+    }
+}
+
+pub fn chunk_bounds_command_sys(
+    mut command_events: EventReader<ConsoleCommandEvent>,
+    mut bounds: ResMut<ChunkBoundsGizmos>,
+) {
+    for command in command_events.read() {
+        if command.name == "chunk_bounds" {
+            bounds.enabled = !bounds.enabled;
+        }
+    }
+}
+
+pub fn apply_chunk_collider_sys(
+    mut commands: Commands,
+    mut task_query: Query<(Entity, &mut ChunkColliderTask)>,
+) {
+    for (entity, mut task) in task_query.iter_mut() {
+        let Some(collider) = future::block_on(future::poll_once(&mut task.0)) else { continue; };
+        commands.entity(entity)
+            .insert(collider)
+            .remove::<ChunkColliderTask>();
+    }
+}
+
+/// Meshes dirty chunks on the CPU when there is no GPU, only colliders are generated
+pub fn cpu_polygonize_sys(
+    mut commands: Commands,
+    seed: Res<WorldSeed>,
+    mut heightmap: ResMut<TerrainHeightmap>,
+    generators: ChunkGenerators,
+    mut chunk_query: Query<(Entity, &mut Chunk)>,
+) {
+    for (entity, mut chunk) in chunk_query.iter_mut() {
+        if !chunk.dirty { continue; }
+        chunk.dirty = false;
 
-        if let Some(Indices::U32(indices)) = mesh.indices_mut() {
-            indices.resize(index_count, 0);
-            indices.copy_from_slice(buffers.indices.as_slice());
+        let position = chunk.position;
+        let chunk_height = (position.y * CHUNK_SZ as i32) as f32;
+        let noise_offset = seed.noise_offset();
+        match &generators.density.0 {
+            Some(field) => fill_voxels(field.as_ref(), position, &mut chunk.voxels),
+            // Same points and noise as the GPU path, so servers collide with the terrain clients see
+            None => fill_voxels(&HeightmapDensity(|column: Vec2| {
+                chunk_height + noise_surface_height(simplex_noise(0.05 * column + noise_offset))
+            }), position, &mut chunk.voxels),
+        }
+        heightmap.insert_chunk(IVec2::new(position.x, position.z), column_surface_heights(&chunk.voxels, chunk_height));
+        for hook in generators.hooks.0.iter() {
+            hook(position, &mut chunk.voxels);
         }
-        if let Some(VertexAttributeValues::Float32x3(vertices)) = mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION) {
-            vertices.clear();
-            vertices.reserve(vertex_count);
-            for v in buffers.vertices.iter() {
-                vertices.push([v[0], v[1], v[2]]);
+        for placed in generators.structures.placed.iter() {
+            if let Some(template) = generators.structure_templates.get(&placed.template) {
+                stamp_structure(template, placed.origin, placed.mode, position, &mut chunk.voxels);
             }
         }
-        if let Some(VertexAttributeValues::Float32x3(normals)) = mesh.attribute_mut(Mesh::ATTRIBUTE_NORMAL) {
-            normals.clear();
-            normals.reserve(vertex_count);
-            for v in buffers.normals.iter() {
-                normals.push([v[0], v[1], v[2]]);
+
+        let fill = ChunkFill::classify(&chunk.voxels);
+        chunk.fill = Some(fill);
+        if fill != ChunkFill::Mixed {
+            commands.entity(entity).remove::<(Collider, ChunkColliderTask)>();
+            continue;
+        }
+        let voxels = chunk.voxels.clone();
+        let task = AsyncComputeTaskPool::get().spawn(async move {
+            let (vertices, indices) = polygonize_cpu(&voxels);
+            Collider::trimesh(vertices, indices)
+        });
+        commands.entity(entity).insert(ChunkColliderTask(task));
+    }
+}
+
+/// CPU port of the smooth path of `voxels.wgsl`, block voxels are treated like any other voxel
+pub fn polygonize_cpu(voxels: &[Voxel]) -> (Vec<Vec3>, Vec<[u32; 3]>) {
+    const CORNERS: [IVec3; 8] = [
+        IVec3::new(0, 0, 1), IVec3::new(1, 0, 1), IVec3::new(1, 0, 0), IVec3::new(0, 0, 0),
+        IVec3::new(0, 1, 1), IVec3::new(1, 1, 1), IVec3::new(1, 1, 0), IVec3::new(0, 1, 0),
+    ];
+    const EDGES: [(usize, usize); 12] = [
+        (0, 1), (1, 2), (2, 3), (3, 0), (4, 5), (5, 6), (6, 7), (7, 4), (0, 4), (1, 5), (2, 6), (3, 7),
+    ];
+    let chunk_sz = CHUNK_SZ as i32;
+    let density = |pos: IVec3| {
+        let is_inside = pos.cmpge(IVec3::ZERO).all() && pos.cmplt(IVec3::splat(chunk_sz)).all();
+        if is_inside { voxels[(pos.x + pos.y * chunk_sz + pos.z * chunk_sz * chunk_sz) as usize].density } else { 0.0 }
+    };
+
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    for z in 0..chunk_sz {
+        for y in 0..chunk_sz {
+            for x in 0..chunk_sz {
+                let pos = IVec3::new(x, y, z);
+                let densities = CORNERS.map(|corner| density(pos + corner));
+                let cube_idx = densities.iter().enumerate()
+                    .filter(|(_, &d)| d < 0.5)
+                    .fold(0, |cube_idx, (i, _)| cube_idx | 1 << i);
+                if cube_idx == 0x00 || cube_idx == 0xff { continue; }
+
+                let edge_vertex = |edge: i32| {
+                    let (a, b) = EDGES[edge as usize];
+                    let (p1, p2) = ((pos + CORNERS[a]).as_vec3(), (pos + CORNERS[b]).as_vec3());
+                    let mu = (0.5 - densities[a]) / (densities[b] - densities[a]);
+                    p1 + mu * (p2 - p1)
+                };
+                for tri in TRI_TABLE[cube_idx].chunks_exact(3).take_while(|tri| tri[0] != -1) {
+                    let start = vertices.len() as u32;
+                    vertices.extend(tri.iter().map(|&edge| edge_vertex(edge)));
+                    indices.push([start, start + 1, start + 2]);
+                }
             }
         }
-        if let Some(VertexAttributeValues::Float32x2(uvs)) = mesh.attribute_mut(Mesh::ATTRIBUTE_UV_0) {
-            uvs.clear();
-            uvs.reserve(vertex_count);
-            for v in buffers.uvs.iter() {
-                uvs.push((*v).into());
+    }
+    (vertices, indices)
+}
+
+/// Empty every attribute and the indices, keeping the attributes themselves so the mesh can be refilled
+/// Copy meshed data into the chunk mesh, reusing its allocations
+fn fill_chunk_mesh(
+    mesh: &mut Mesh, data: &ChunkMeshData, chunk_height: f32, color_ramp: &TerrainColorRamp, shading: ShadingMode,
+) -> Result<(), GenerateTangentsError> {
+    if let Some(Indices::U32(indices)) = mesh.indices_mut() {
+        indices.clone_from(&data.indices);
+    }
+    if let Some(VertexAttributeValues::Float32x3(vertices)) = mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION) {
+        vertices.clone_from(&data.positions);
+    }
+    if let Some(VertexAttributeValues::Float32x3(normals)) = mesh.attribute_mut(Mesh::ATTRIBUTE_NORMAL) {
+        normals.clone_from(&data.normals);
+    }
+    if let Some(VertexAttributeValues::Float32x2(uvs)) = mesh.attribute_mut(Mesh::ATTRIBUTE_UV_0) {
+        uvs.clone_from(&data.uvs);
+    }
+    if let Some(VertexAttributeValues::Float32x4(colors)) = mesh.attribute_mut(Mesh::ATTRIBUTE_COLOR) {
+        colors.clear();
+        colors.extend(data.positions.iter().map(|v| color_ramp.sample(chunk_height + v[1]).as_linear_rgba_f32()));
+    }
+    if shading == ShadingMode::Flat {
+        flat_shade(mesh);
+    }
+    // For the normal map of the base StandardMaterial, after flat shading since that changes the normals.
+    // The triplanar normal map does not use them, each projection is its own tangent space built in the shader.
+    mesh.generate_tangents()
+}
+
+fn clear_mesh(mesh: &mut Mesh) {
+    if let Some(Indices::U32(indices)) = mesh.indices_mut() {
+        indices.clear();
+    }
+    for attribute in [Mesh::ATTRIBUTE_POSITION, Mesh::ATTRIBUTE_NORMAL, Mesh::ATTRIBUTE_UV_0, Mesh::ATTRIBUTE_TANGENT, Mesh::ATTRIBUTE_COLOR] {
+        match mesh.attribute_mut(attribute) {
+            Some(VertexAttributeValues::Float32x2(values)) => values.clear(),
+            Some(VertexAttributeValues::Float32x3(values)) => values.clear(),
+            Some(VertexAttributeValues::Float32x4(values)) => values.clear(),
+            _ => {}
+        }
+    }
+}
+
+/// Give every triangle its own vertices so its normal can be the face normal.
+/// Indices are put back afterwards so the mesh can be refilled next time the chunk is meshed.
+pub fn flat_shade(mesh: &mut Mesh) {
+    mesh.duplicate_vertices();
+    mesh.compute_flat_normals();
+    let vertex_count = mesh.count_vertices() as u32;
+    mesh.set_indices(Some(Indices::U32((0..vertex_count).collect())));
+}
+
+pub fn shading_command_sys(
+    mut command_events: EventReader<ConsoleCommandEvent>,
+    mut shading: ResMut<ShadingMode>,
+    mut chunk_query: Query<&mut Chunk>,
+) {
+    for command in command_events.read() {
+        if command.name != "shading" { continue; }
+        let wanted = match command.args.first().map(String::as_str) {
+            Some("smooth") => ShadingMode::Smooth,
+            Some("flat") => ShadingMode::Flat,
+            _ => {
+                warn!("Usage: shading <smooth|flat>");
+                continue;
+            }
+        };
+        if *shading != wanted {
+            *shading = wanted;
+            for mut chunk in chunk_query.iter_mut() {
+                chunk.dirty = true;
             }
         }
+    }
+}
+
+pub fn draw_chunk_bounds_sys(
+    mut gizmos: Gizmos,
+    bounds: Res<ChunkBoundsGizmos>,
+    chunk_query: Query<&Chunk>,
+    player_query: Query<(&Transform, &LogicalPlayer)>,
+) {
+    if !bounds.enabled { return; }
 
-        // TODO:perf inefficient
-        commands.entity(entity).insert(Collider::from_bevy_mesh(mesh, &ComputedColliderShape::TriMesh).unwrap());
+    let player_chunk = player_query.iter()
+        .find(|(_, player)| player.0 == 0)
+        .map(|(transform, _)| (transform.translation / CHUNK_SZ as f32).floor().as_ivec3());
+    for chunk in chunk_query.iter() {
+        let size = CHUNK_SZ as f32;
+        let center = chunk.position.as_vec3() * size + Vec3::splat(size * 0.5);
+        let color = if player_chunk == Some(chunk.position) { Color::YELLOW } else { Color::CYAN };
+        gizmos.cuboid(Transform::from_translation(center).with_scale(Vec3::splat(size)), color);
     }
+}
 
-    // println!("Elapsed: {:.2?}", now.elapsed());
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use wgpu::util::DeviceExt;
+
+    use super::*;
+
+    const SIMPLEX_SHADER: &str = include_str!("../../assets/shaders/simplex.wgsl");
+
+    /// One workgroup of the simplex shader, or `None` when this machine has no adapter
+    fn gpu_simplex(points: &[Vec2]) -> Option<Vec<f32>> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let adapter = future::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))?;
+        let (device, queue) = future::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None)).ok()?;
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(SIMPLEX_SHADER.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: None,
+            layout: None,
+            module: &module,
+            entry_point: "main",
+        });
+        let size = (points.len() * size_of::<f32>()) as u64;
+        let points = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: cast_slice(points),
+            usage: BufferUsages::STORAGE,
+        });
+        let heights = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: points.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: heights.as_entire_binding() },
+            ],
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            pass.set_pipeline(&pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(1, 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&heights, 0, &staging, 0, size);
+        queue.submit(once(encoder.finish()));
+        let slice = staging.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        device.poll(wgpu::Maintain::Wait);
+        let heights: Vec<f32> = cast_slice(&slice.get_mapped_range()).to_vec();
+        Some(heights)
+    }
+
+    #[test]
+    fn simplex_matches_shader_reference() {
+        // Evaluated from the WGSL source in double precision
+        let expected = [
+            (Vec2::new(0.0, 0.0), 0.0),
+            (Vec2::new(0.3, 0.7), 0.121_749_88),
+            (Vec2::new(1.25, -2.5), -0.006_908_651),
+            (Vec2::new(12.34, 56.78), -0.157_653_08),
+            (Vec2::new(100.5, 200.25), 0.324_567_76),
+        ];
+        for (point, noise) in expected {
+            assert!((simplex_noise(point) - noise).abs() < 1e-3, "{} at {}", simplex_noise(point), point);
+        }
+    }
+
+    #[test]
+    fn simplex_matches_gpu() {
+        let offset = WorldSeed(7).noise_offset();
+        let points: Vec<Vec2> = (0..CHUNK_SZ_2)
+            .map(|index| 0.05 * Vec2::new((index % CHUNK_SZ) as f32, (index / CHUNK_SZ) as f32) + offset)
+            .collect();
+        let Some(gpu_heights) = gpu_simplex(&points) else { return; };
+        for (point, gpu_height) in points.iter().zip(gpu_heights) {
+            let cpu_height = noise_surface_height(simplex_noise(*point));
+            assert!((cpu_height - noise_surface_height(gpu_height)).abs() < 1e-3, "at {}", point);
+        }
+    }
+
+    #[test]
+    fn noise_surface_spans_eight_to_twelve() {
+        assert_eq!(noise_surface_height(-1.0), 8.0);
+        assert_eq!(noise_surface_height(1.0), 12.0);
+        for x in 0..64 {
+            let noise = simplex_noise(Vec2::new(x as f32 * 0.37, x as f32 * 0.11));
+            assert!((-1.0..=1.0).contains(&noise));
+        }
+    }
+
+    #[test]
+    fn chunk_debug_colors_differ_across_faces() {
+        let position = IVec3::new(3, -2, 7);
+        for axis in [IVec3::X, IVec3::Y, IVec3::Z] {
+            assert_ne!(chunk_debug_color(position), chunk_debug_color(position + axis));
+            assert_ne!(chunk_debug_color(position), chunk_debug_color(position - axis));
+        }
+        assert_eq!(chunk_debug_color(position), chunk_debug_color(position + IVec3::splat(2)));
+    }
+
+    #[test]
+    fn color_ramp_clamps_and_interpolates() {
+        let ramp = TerrainColorRamp { stops: vec![(0.0, Color::BLACK), (10.0, Color::WHITE)] };
+        assert_eq!(ramp.sample(-5.0), Color::BLACK);
+        assert_eq!(ramp.sample(20.0), Color::WHITE);
+        let [r, g, b, a] = ramp.sample(2.5).as_rgba_f32();
+        assert!((r - 0.25).abs() < 1e-6 && (g - 0.25).abs() < 1e-6 && (b - 0.25).abs() < 1e-6);
+        assert_eq!(a, 1.0);
+    }
+
+    #[test]
+    fn empty_color_ramp_is_white() {
+        assert_eq!(TerrainColorRamp { stops: Vec::new() }.sample(3.0), Color::WHITE);
+    }
+
+    /// Two chunks side by side along x, heights rise by one per column in x
+    fn sloped_heightmap() -> TerrainHeightmap {
+        let mut heightmap = TerrainHeightmap::default();
+        for chunk_x in [-1, 0] {
+            let heights = (0..CHUNK_SZ * CHUNK_SZ)
+                .map(|index| (chunk_x * CHUNK_SZ as i32 + (index % CHUNK_SZ) as i32) as f32)
+                .collect();
+            heightmap.insert_chunk(IVec2::new(chunk_x, 0), heights);
+        }
+        heightmap
+    }
+
+    #[test]
+    fn heightmap_interpolates_across_chunks() {
+        let heightmap = sloped_heightmap();
+        assert_eq!(heightmap.sample(3.0, 2.0), Some(3.0));
+        assert_eq!(heightmap.sample(3.25, 2.5), Some(3.25));
+        assert_eq!(heightmap.sample(-0.5, 1.0), Some(-0.5));
+        assert_eq!(heightmap.sample(1.0, -1.0), None);
+    }
+
+    #[test]
+    fn cast_down_hits_within_range() {
+        let heightmap = sloped_heightmap();
+        let hit = heightmap.cast_down(Vec3::new(4.0, 6.0, 4.0), 3.0).unwrap();
+        assert_eq!(hit.toi, 2.0);
+        // Slope rises along x, so the normal leans back towards -x
+        let expected = Vec3::new(-1.0, 1.0, 0.0).normalize();
+        assert!(hit.normal.abs_diff_eq(expected, 1e-5));
+
+        assert_eq!(heightmap.cast_down(Vec3::new(4.0, 8.0, 4.0), 3.0), None);
+        assert_eq!(heightmap.cast_down(Vec3::new(4.0, 2.0, 4.0), 3.0).map(|hit| hit.toi), Some(0.0));
+    }
+
+    #[test]
+    fn generation_hooks_shape_voxels_in_order() {
+        ComputeTaskPool::get_or_init(Default::default);
+        let mut voxels = vec![Voxel::default(); CHUNK_SZ_3];
+        fill_voxels(&HeightmapDensity(|_: Vec2| 10.5), IVec3::ZERO, &mut voxels);
+        // Faces of cells around the dug column, between what is put back and the surface
+        let faces_in_column = |voxels: &[Voxel]| {
+            let (vertices, triangles) = polygonize_cpu(voxels);
+            triangles.iter()
+                .map(|tri| tri.iter().map(|&i| vertices[i as usize]).sum::<Vec3>() / 3.0)
+                .filter(|center| center.x < 1.0 && center.z < 1.0 && center.y > 4.5 && center.y < 9.5)
+                .count()
+        };
+        assert_eq!(faces_in_column(&voxels), 0);
+
+        let mut hooks = ChunkGenerationHooks::default();
+        hooks
+            // Dig out column (0, 0) entirely, then put back everything below y = 4
+            .add(|_, voxels| (0..CHUNK_SZ).for_each(|y| voxels[y * CHUNK_SZ].density = 0.0))
+            .add(|_, voxels| (0..4).for_each(|y| voxels[y * CHUNK_SZ].density = 1.0));
+        for hook in hooks.0.iter() {
+            hook(IVec3::ZERO, &mut voxels);
+        }
+
+        let heights = column_surface_heights(&voxels, 0.0);
+        assert!((heights[1] - 10.0).abs() < 1e-5);
+        assert!((heights[0] - 3.5).abs() < 1e-5);
+        assert!(faces_in_column(&voxels) > 0);
+    }
+
+    #[test]
+    fn flat_shade_uses_face_normals() {
+        // Two triangles folded along their shared edge, one flat and one sloped
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vec![[0.0, 0.0, 0.0], [0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [1.0, 1.0, 1.0]]);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 1.0, 0.0]; 4]);
+        mesh.set_indices(Some(Indices::U32(vec![0, 1, 2, 2, 1, 3])));
+        flat_shade(&mut mesh);
+
+        assert_eq!(mesh.count_vertices(), 6);
+        let Some(Indices::U32(indices)) = mesh.indices() else { panic!("indices are missing") };
+        assert_eq!(indices, &(0..6).collect::<Vec<u32>>());
+        let Some(VertexAttributeValues::Float32x3(normals)) = mesh.attribute(Mesh::ATTRIBUTE_NORMAL) else { panic!("normals are missing") };
+        let sloped = Vec3::new(-1.0, 1.0, -1.0).normalize();
+        assert_eq!(normals[..3], [[0.0, 1.0, 0.0]; 3]);
+        for &normal in &normals[3..] {
+            assert!(Vec3::from(normal).abs_diff_eq(sloped, 1e-5));
+        }
+    }
+
+    #[test]
+    fn shading_command_remeshes_on_change() {
+        let mut app = App::new();
+        app
+            .add_event::<ConsoleCommandEvent>()
+            .init_resource::<ShadingMode>()
+            .add_systems(Update, shading_command_sys);
+        let chunk = app.world.spawn(Chunk { position: IVec3::ZERO, voxels: Vec::new(), dirty: false, fill: None }).id();
+
+        app.world.send_event(ConsoleCommandEvent::parse("shading smooth").unwrap());
+        app.update();
+        assert!(!app.world.get::<Chunk>(chunk).unwrap().dirty);
+
+        app.world.send_event(ConsoleCommandEvent::parse("shading flat").unwrap());
+        app.update();
+        assert_eq!(*app.world.resource::<ShadingMode>(), ShadingMode::Flat);
+        assert!(app.world.get::<Chunk>(chunk).unwrap().dirty);
+    }
+
+    #[test]
+    fn chunk_mesh_has_tangent_per_vertex() {
+        // A quad split into two triangles, uvs are needed for tangents
+        let data = ChunkMeshData {
+            positions: vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 0.0, 1.0], [0.0, 0.0, 1.0]],
+            normals: vec![[0.0, 1.0, 0.0]; 4],
+            uvs: vec![[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]],
+            indices: vec![0, 2, 1, 0, 3, 2],
+        };
+        for shading in [ShadingMode::Smooth, ShadingMode::Flat] {
+            let mut mesh = empty_chunk_mesh();
+            fill_chunk_mesh(&mut mesh, &data, 0.0, &TerrainColorRamp::default(), shading).unwrap();
+            let Some(VertexAttributeValues::Float32x4(tangents)) = mesh.attribute(Mesh::ATTRIBUTE_TANGENT) else { panic!("tangents are missing") };
+            assert_eq!(tangents.len(), mesh.count_vertices());
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use std::f32::consts::{PI, TAU};
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{Config, ConfigState, ConsoleCommandEvent, eye_transform, LogicalPlayer, PlayerController, wrap_angle};
+
+const COMPASS_WIDTH: f32 = 400.0;
+const WAYPOINT_MAX_DIST: f32 = 256.0;
+
+/// World positions shown on the compass
+#[derive(Resource, Default)]
+pub struct Waypoints(pub Vec<Vec3>);
+
+#[derive(Component)]
+pub struct CompassStrip;
+
+#[derive(Component)]
+pub enum CompassMarker {
+    /// Fixed direction given as a yaw, used for north/east/south/west
+    Cardinal(f32),
+    /// Index into [`Waypoints`]
+    Waypoint(usize),
+}
+
+pub struct WaypointPlugin;
+
+impl Plugin for WaypointPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Waypoints>()
+            .add_systems(Startup, spawn_compass_sys)
+            .add_systems(Update, (waypoint_command_sys, place_waypoint_sys, sync_compass_markers_sys, update_compass_sys).chain());
+    }
+}
+
+/// Yaw that faces along `dir`, matching the convention of [`crate::look_quat`]
+pub fn yaw_of(dir: Vec3) -> f32 {
+    f32::atan2(-dir.x, -dir.z)
+}
+
+/// Horizontal position on the compass in [0, 1] for something at `target_yaw`.
+/// Straight ahead is 0.5, directly behind is at either edge.
+pub fn compass_position(view_yaw: f32, target_yaw: f32) -> f32 {
+    // Positive yaw turns to the left, which is towards the left edge of the compass
+    0.5 - wrap_angle(target_yaw - view_yaw) / TAU
+}
+
+fn spawn_compass_sys(mut commands: Commands) {
+    commands.spawn(NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(5.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        ..default()
+    }).with_children(|parent| {
+        parent.spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Px(COMPASS_WIDTH),
+                    height: Val::Px(20.0),
+                    overflow: Overflow::clip(),
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.4).into(),
+                ..default()
+            },
+            CompassStrip,
+        )).with_children(|parent| {
+            for (label, yaw) in [("N", 0.0), ("W", PI * 0.5), ("S", PI), ("E", -PI * 0.5)] {
+                parent.spawn((
+                    compass_marker_bundle(label.to_string(), Color::WHITE),
+                    CompassMarker::Cardinal(yaw),
+                ));
+            }
+        });
+    });
+}
+
+fn compass_marker_bundle(label: String, color: Color) -> TextBundle {
+    TextBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            ..default()
+        },
+        text: Text::from_section(label, TextStyle { font_size: 14.0, color, ..default() }),
+        ..default()
+    }
+}
+
+pub fn waypoint_command_sys(
+    mut command_events: EventReader<ConsoleCommandEvent>,
+    mut waypoints: ResMut<Waypoints>,
+) {
+    for command in command_events.read() {
+        if command.name != "waypoint" { continue; }
+
+        if command.args.first().map(String::as_str) == Some("clear") {
+            waypoints.0.clear();
+            continue;
+        }
+        match (command.arg::<f32>(0), command.arg::<f32>(1), command.arg::<f32>(2)) {
+            (Some(x), Some(y), Some(z)) => waypoints.0.push(Vec3::new(x, y, z)),
+            _ => warn!("Usage: waypoint <x> <y> <z> | waypoint clear"),
+        }
+    }
+}
+
+pub fn place_waypoint_sys(
+    key_input: Res<Input<KeyCode>>,
+    config: Res<Assets<Config>>,
+    config_state: Res<ConfigState>,
+    physics_context: Res<RapierContext>,
+    mut waypoints: ResMut<Waypoints>,
+    player_query: Query<(Entity, &Transform, &PlayerController), With<LogicalPlayer>>,
+) {
+    let Some(config) = config.get(&config_state.handle) else { return; };
+    if !key_input.just_pressed(config.key_waypoint) { return; }
+
+    for (entity, transform, controller) in player_query.iter() {
+        let eye = eye_transform(transform, controller);
+        let filter = QueryFilter::default().exclude_collider(entity).exclude_sensors();
+        if let Some((_, toi)) = physics_context.cast_ray(eye.translation, eye.forward(), WAYPOINT_MAX_DIST, true, filter) {
+            waypoints.0.push(eye.translation + eye.forward() * toi);
+        }
+    }
+}
+
+fn sync_compass_markers_sys(
+    mut commands: Commands,
+    waypoints: Res<Waypoints>,
+    strip_query: Query<Entity, With<CompassStrip>>,
+    marker_query: Query<(Entity, &CompassMarker)>,
+) {
+    if !waypoints.is_changed() { return; }
+
+    for (marker_ent, marker) in marker_query.iter() {
+        if let CompassMarker::Waypoint(_) = marker {
+            commands.entity(marker_ent).despawn_recursive();
+        }
+    }
+    for strip_ent in strip_query.iter() {
+        commands.entity(strip_ent).with_children(|parent| {
+            for index in 0..waypoints.0.len() {
+                parent.spawn((
+                    compass_marker_bundle(String::new(), Color::YELLOW),
+                    CompassMarker::Waypoint(index),
+                ));
+            }
+        });
+    }
+}
+
+fn update_compass_sys(
+    waypoints: Res<Waypoints>,
+    player_query: Query<(&Transform, &PlayerController, &LogicalPlayer)>,
+    mut marker_query: Query<(&CompassMarker, &mut Style, &mut Text)>,
+) {
+    let Some((transform, controller, _)) = player_query.iter().find(|(_, _, player)| player.0 == 0) else { return; };
+    let eye = eye_transform(transform, controller);
+
+    for (marker, mut style, mut text) in marker_query.iter_mut() {
+        let target_yaw = match marker {
+            CompassMarker::Cardinal(yaw) => *yaw,
+            CompassMarker::Waypoint(index) => {
+                let Some(&waypoint) = waypoints.0.get(*index) else { continue; };
+                let to_waypoint = waypoint - eye.translation;
+                text.sections[0].value = format!("{} {:.0}m", index + 1, to_waypoint.length());
+                yaw_of(to_waypoint)
+            }
+        };
+        style.left = Val::Px(compass_position(controller.yaw, target_yaw) * COMPASS_WIDTH);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::look_quat;
+
+    use super::*;
+
+    #[test]
+    fn yaw_of_inverts_look_quat() {
+        for yaw in [0.0, 0.5, -2.0, 3.0] {
+            let forward = look_quat(0.0, yaw) * -Vec3::Z;
+            assert!(wrap_angle(yaw_of(forward) - yaw).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn compass_position_centers_ahead() {
+        assert!((compass_position(1.0, 1.0) - 0.5).abs() < 1e-6);
+        // Left of the view is left of center
+        assert!(compass_position(0.0, PI * 0.5) < 0.5);
+        assert!(compass_position(0.0, -PI * 0.5) > 0.5);
+        assert!((compass_position(0.0, PI * 0.5) - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn waypoint_command_adds_and_clears() {
+        let mut app = App::new();
+        app
+            .add_event::<ConsoleCommandEvent>()
+            .init_resource::<Waypoints>()
+            .add_systems(Update, waypoint_command_sys);
+
+        app.world.send_event(ConsoleCommandEvent::parse("waypoint 1 2 3").unwrap());
+        app.world.send_event(ConsoleCommandEvent::parse("waypoint 1 2").unwrap());
+        app.update();
+        assert_eq!(app.world.resource::<Waypoints>().0, [Vec3::new(1.0, 2.0, 3.0)]);
+
+        app.world.send_event(ConsoleCommandEvent::parse("waypoint clear").unwrap());
+        app.update();
+        assert!(app.world.resource::<Waypoints>().0.is_empty());
+    }
+}
@@ -0,0 +1,187 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{Chunk, ConsoleCommandEvent, GameRng};
+
+const SAVES_DIR: &str = "saves";
+const METADATA_FILE_NAME: &str = "world.ron";
+
+/// Seed for terrain generation, restored from [`WorldMetadata`] when a world is loaded
+#[derive(Resource, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct WorldSeed(pub u64);
+
+impl WorldSeed {
+    /// Where in the noise domain the world starts, within the period of the simplex permutation
+    pub fn noise_offset(self) -> Vec2 {
+        let mut rng = GameRng::new(self.0);
+        Vec2::new(rng.range(0.0, 289.0), rng.range(0.0, 289.0))
+    }
+
+    /// Seed for the CPU noise functions
+    pub fn noise_seed(self) -> u32 {
+        (self.0 ^ (self.0 >> 32)) as u32
+    }
+}
+
+/// How terrain density is produced
+#[derive(Resource, Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WorldGenMode {
+    /// Simplex noise heightmap
+    #[default]
+    Heightmap,
+}
+
+/// Written next to the saved chunks of a world so regenerating unvisited chunks matches
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct WorldMetadata {
+    pub seed: u64,
+    pub gen_mode: WorldGenMode,
+    /// Crate version that created the world
+    pub version: String,
+    pub created_unix_secs: u64,
+}
+
+#[derive(Debug, Error)]
+pub enum WorldMetadataError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    RonSpannedError(#[from] ron::error::SpannedError),
+    #[error(transparent)]
+    RonError(#[from] ron::Error),
+}
+
+pub struct WorldPlugin;
+
+impl Plugin for WorldPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<WorldSeed>()
+            .init_resource::<WorldGenMode>()
+            .add_systems(Update, (
+                world_command_sys,
+                regenerate_on_seed_change_sys.run_if(resource_changed::<WorldSeed>()),
+            ).chain());
+    }
+}
+
+pub fn world_dir(name: &str) -> PathBuf {
+    Path::new(SAVES_DIR).join(name)
+}
+
+impl WorldMetadata {
+    pub fn new(seed: WorldSeed, gen_mode: WorldGenMode) -> Self {
+        Self {
+            seed: seed.0,
+            gen_mode,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            created_unix_secs: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs()),
+        }
+    }
+
+    pub fn save(&self, world_dir: &Path) -> Result<(), WorldMetadataError> {
+        fs::create_dir_all(world_dir)?;
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        fs::write(world_dir.join(METADATA_FILE_NAME), text)?;
+        Ok(())
+    }
+
+    pub fn load(world_dir: &Path) -> Result<Self, WorldMetadataError> {
+        let bytes = fs::read(world_dir.join(METADATA_FILE_NAME))?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    /// Overwrite the runtime generation parameters with the saved ones
+    pub fn restore(&self, seed: &mut WorldSeed, gen_mode: &mut WorldGenMode) {
+        *seed = WorldSeed(self.seed);
+        *gen_mode = self.gen_mode;
+    }
+}
+
+pub fn regenerate_on_seed_change_sys(mut chunk_query: Query<&mut Chunk>) {
+    for mut chunk in chunk_query.iter_mut() {
+        chunk.dirty = true;
+    }
+}
+
+pub fn world_command_sys(
+    mut command_events: EventReader<ConsoleCommandEvent>,
+    mut seed: ResMut<WorldSeed>,
+    mut gen_mode: ResMut<WorldGenMode>,
+    mut chunk_query: Query<&mut Chunk>,
+) {
+    for command in command_events.read() {
+        let Some(name) = command.args.first() else {
+            if command.name == "save_world" || command.name == "load_world" {
+                warn!("Usage: {} <name>", command.name);
+            }
+            continue;
+        };
+        match command.name.as_str() {
+            "save_world" => {
+                // Keep the original creation time when saving over an existing world
+                let metadata = match WorldMetadata::load(&world_dir(name)) {
+                    Ok(existing) => WorldMetadata { seed: seed.0, gen_mode: *gen_mode, ..existing },
+                    Err(_) => WorldMetadata::new(*seed, *gen_mode),
+                };
+                if let Err(err) = metadata.save(&world_dir(name)) {
+                    error!("Failed to save world metadata for {}: {}", name, err);
+                }
+            }
+            "load_world" => match WorldMetadata::load(&world_dir(name)) {
+                Ok(metadata) => {
+                    if metadata.version != env!("CARGO_PKG_VERSION") {
+                        warn!("World {} was created with version {}", name, metadata.version);
+                    }
+                    metadata.restore(&mut seed, &mut gen_mode);
+                    // Loaded parameters may differ, so everything has to be generated again
+                    for mut chunk in chunk_query.iter_mut() {
+                        chunk.dirty = true;
+                    }
+                }
+                Err(err) => error!("Failed to load world metadata for {}: {}", name, err),
+            },
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metadata_round_trips_through_disk() {
+        let dir = std::env::temp_dir().join(format!("qgame-world-test-{}", std::process::id()));
+        let metadata = WorldMetadata::new(WorldSeed(1234), WorldGenMode::Heightmap);
+        metadata.save(&dir).unwrap();
+        let loaded = WorldMetadata::load(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded.unwrap(), metadata);
+
+        let (mut seed, mut gen_mode) = (WorldSeed(1), WorldGenMode::default());
+        metadata.restore(&mut seed, &mut gen_mode);
+        assert_eq!(seed, WorldSeed(1234));
+    }
+
+    #[test]
+    fn missing_metadata_is_an_error() {
+        let dir = std::env::temp_dir().join("qgame-world-test-missing");
+        assert!(matches!(WorldMetadata::load(&dir), Err(WorldMetadataError::Io(_))));
+    }
+
+    #[test]
+    fn noise_offset_depends_only_on_seed() {
+        let offset = WorldSeed(42).noise_offset();
+        assert_eq!(offset, WorldSeed(42).noise_offset());
+        assert_ne!(offset, WorldSeed(43).noise_offset());
+        assert!(offset.cmpge(Vec2::ZERO).all() && offset.cmplt(Vec2::splat(289.0)).all());
+    }
+}